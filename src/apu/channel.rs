@@ -0,0 +1,163 @@
+/// Turns the channel off once it reaches zero, clocked at 256 Hz
+pub(super) struct LengthCounter {
+    counter: u16,
+    // 64, or 256 for the wave channel
+    maximum: u16,
+    enabled: bool,
+}
+
+impl LengthCounter {
+    pub fn new(maximum: u16) -> Self {
+        Self {
+            counter: 0,
+            maximum,
+            enabled: false,
+        }
+    }
+
+    /// NRx1, which holds the length to subtract from the maximum
+    pub fn load(&mut self, length: u16) {
+        self.counter = self.maximum - length;
+    }
+
+    /// NRx4 is cleared, the DMG keeps the counter
+    pub fn power_off(&mut self, keep_counter: bool) {
+        self.enabled = false;
+        if !keep_counter {
+            self.counter = 0;
+        }
+    }
+
+    /// Returns whether the channel is to be turned off
+    pub fn clock(&mut self) -> bool {
+        if !self.enabled || self.counter == 0 {
+            return false;
+        }
+
+        self.counter -= 1;
+        self.counter == 0
+    }
+
+    /// Bit 6 of NRx4, with the trigger in bit 7
+    ///
+    /// When the next frame sequencer step won't clock the counter, enabling it
+    /// clocks it once right away, and a trigger reloading it from zero starts
+    /// one lower. Returns whether the channel is to be turned off.
+    pub fn write_control(&mut self, enable: bool, trigger: bool, extra_clock: bool) -> bool {
+        let mut expired = false;
+
+        if extra_clock && !self.enabled && enable && self.counter != 0 {
+            self.counter -= 1;
+            expired = self.counter == 0 && !trigger;
+        }
+        self.enabled = enable;
+
+        if trigger && self.counter == 0 {
+            self.counter = self.maximum;
+            if enable && extra_clock {
+                self.counter -= 1;
+            }
+        }
+
+        expired
+    }
+}
+
+/// Volume going up or down a step at a time, clocked at 64 Hz
+pub(super) struct Envelope {
+    // NRx2, taken into account on trigger
+    register: u8,
+    volume: u8,
+    increase: bool,
+    pace: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Self {
+            register: 0,
+            volume: 0,
+            increase: false,
+            pace: 0,
+            timer: 0,
+        }
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.register = value;
+    }
+
+    /// The DAC is on when the initial volume or the direction is set
+    pub fn dac_enabled(&self) -> bool {
+        self.register & 0xF8 != 0
+    }
+
+    pub fn trigger(&mut self) {
+        self.volume = self.register >> 4;
+        self.increase = self.register & 0x08 != 0;
+        self.pace = self.register & 0x07;
+        self.timer = self.pace;
+    }
+
+    pub fn clock(&mut self) {
+        // A pace of 0 stops the envelope
+        if self.pace == 0 {
+            return;
+        }
+
+        self.timer -= 1;
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = self.pace;
+
+        if self.increase && self.volume < 15 {
+            self.volume += 1;
+        } else if !self.increase && self.volume > 0 {
+            self.volume -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_length_extra_clock() {
+        let mut length = LengthCounter::new(64);
+        length.load(63);
+
+        // Enabled between two length steps, the last clock happens right away
+        assert!(length.write_control(true, false, true));
+
+        // Triggered from zero, starting one short of 64
+        length.write_control(false, false, true);
+        length.write_control(true, true, true);
+        for _ in 0..62 {
+            assert!(!length.clock());
+        }
+        assert!(length.clock());
+    }
+
+    #[test]
+    pub fn test_envelope() {
+        let mut envelope = Envelope::new();
+        envelope.write(0xF2);
+        assert!(envelope.dac_enabled());
+        envelope.trigger();
+
+        envelope.clock();
+        assert_eq!(15, envelope.volume());
+        envelope.clock();
+        assert_eq!(14, envelope.volume());
+
+        envelope.write(0x00);
+        assert!(!envelope.dac_enabled());
+    }
+}
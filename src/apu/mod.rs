@@ -0,0 +1,362 @@
+mod channel;
+mod noise;
+mod square;
+mod wave;
+
+use crate::{clock::CLOCK_FREQUENCY, memory::Model};
+
+use noise::Noise;
+use square::Square;
+use wave::Wave;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 48000;
+
+// Bits that always read as 1, for FF10-FF2F
+const READ_MASKS: [u8; 0x20] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR21-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR41-NR44
+    0x00, 0x00, 0x70, // NR50-NR52
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+
+// The output capacitor loses this much of its charge every 4 MHz cycle,
+// removing the DC offset of the DACs
+const DMG_CHARGE_FACTOR: f64 = 0.999958;
+const CGB_CHARGE_FACTOR: f64 = 0.998943;
+
+/// Sound hardware, NR10-NR52 (FF10-FF26) and wave RAM (FF30-FF3F)
+///
+/// Channels run at 4 MHz whatever the CPU speed, and the frame sequencer
+/// clocking lengths, sweep and envelopes is driven by DIV through the MMU.
+/// Stereo samples come out at the chosen rate, in -1 to 1, to be taken
+/// regularly as only a second of them is kept.
+pub struct Apu {
+    model: Model,
+    powered: bool,
+    // Last values written, for reading back
+    registers: [u8; 0x20],
+    square1: Square,
+    square2: Square,
+    wave: Wave,
+    noise: Noise,
+    // Next frame sequencer step, 0 to 7
+    step: u8,
+    sample_rate: u32,
+    // Counts up by the sample rate every cycle, a sample is due at the clock frequency
+    sample_phase: u32,
+    // Mix summed over the cycles of the sample being made
+    sum: [f32; 2],
+    sum_cycles: u32,
+    capacitors: [f32; 2],
+    charge: f32,
+    samples: Vec<[f32; 2]>,
+}
+
+impl Apu {
+    /// The APU starts powered, as the boot ROM leaves it
+    pub fn new(model: Model) -> Self {
+        let mut apu = Self {
+            model,
+            powered: true,
+            registers: [0; 0x20],
+            square1: Square::new(true),
+            square2: Square::new(false),
+            wave: Wave::new(),
+            noise: Noise::new(),
+            step: 0,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            sample_phase: 0,
+            sum: [0.0; 2],
+            sum_cycles: 0,
+            capacitors: [0.0; 2],
+            charge: 0.0,
+            samples: Vec::new(),
+        };
+        apu.set_sample_rate(DEFAULT_SAMPLE_RATE);
+        apu
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        let factor = match self.model {
+            Model::Dmg => DMG_CHARGE_FACTOR,
+            Model::Cgb => CGB_CHARGE_FACTOR,
+        };
+
+        self.sample_rate = sample_rate;
+        self.charge = factor.powf(CLOCK_FREQUENCY as f64 / sample_rate as f64) as f32;
+        self.samples.clear();
+    }
+
+    /// Left and right samples produced since the last call
+    pub fn take_samples(&mut self) -> Vec<[f32; 2]> {
+        std::mem::take(&mut self.samples)
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF26 => {
+                let channels = [
+                    self.square1.is_enabled(),
+                    self.square2.is_enabled(),
+                    self.wave.is_enabled(),
+                    self.noise.is_enabled(),
+                ];
+                let status = channels
+                    .iter()
+                    .enumerate()
+                    .fold(0, |status, (i, on)| status | ((*on as u8) << i));
+
+                ((self.powered as u8) << 7) | 0x70 | status
+            }
+            0xFF10..=0xFF2F => {
+                let index = address as usize - 0xFF10;
+                self.registers[index] | READ_MASKS[index]
+            }
+            _ => self.wave.read_ram(address as usize - 0xFF30),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF30..=0xFF3F => self.wave.write_ram(address as usize - 0xFF30, value),
+            0xFF26 => self.write_power(value & 0x80 != 0),
+            // Powered off, only the DMG still takes the lengths
+            _ if !self.powered => {
+                if self.model == Model::Dmg {
+                    match address {
+                        0xFF11 => self.square1.write_length(value),
+                        0xFF16 => self.square2.write_length(value),
+                        0xFF1B => self.wave.write_length(value),
+                        0xFF20 => self.noise.write_length(value),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {
+                self.registers[address as usize - 0xFF10] = value;
+
+                // Between two length steps, enabling a length counter clocks it
+                let extra_clock = self.step & 0x01 != 0;
+                match address {
+                    0xFF10..=0xFF14 => self.square1.write(address - 0xFF10, value, extra_clock),
+                    0xFF15..=0xFF19 => self.square2.write(address - 0xFF15, value, extra_clock),
+                    0xFF1A..=0xFF1E => self.wave.write(address - 0xFF1A, value, extra_clock),
+                    0xFF1F..=0xFF23 => self.noise.write(address - 0xFF1F, value, extra_clock),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Turning the APU off clears every register but wave RAM, and on DMG the
+    /// lengths
+    fn write_power(&mut self, on: bool) {
+        if self.powered == on {
+            return;
+        }
+        self.powered = on;
+
+        if on {
+            // The frame sequencer starts over
+            self.step = 0;
+            return;
+        }
+
+        let keep_lengths = self.model == Model::Dmg;
+        self.registers = [0; 0x20];
+        self.square1.power_off(keep_lengths);
+        self.square2.power_off(keep_lengths);
+        self.wave.power_off(keep_lengths);
+        self.noise.power_off(keep_lengths);
+    }
+
+    /// Falling edge of DIV bit 4 (bit 5 in double speed), 512 times a second
+    pub fn clock_frame_sequencer(&mut self) {
+        if !self.powered {
+            return;
+        }
+
+        // Lengths at 256 Hz, the sweep at 128 Hz and envelopes at 64 Hz
+        if self.step & 0x01 == 0 {
+            self.square1.clock_length();
+            self.square2.clock_length();
+            self.wave.clock_length();
+            self.noise.clock_length();
+        }
+        if self.step == 2 || self.step == 6 {
+            self.square1.clock_sweep();
+        }
+        if self.step == 7 {
+            self.square1.clock_envelope();
+            self.square2.clock_envelope();
+            self.noise.clock_envelope();
+        }
+
+        self.step = (self.step + 1) & 0x07;
+    }
+
+    /// Advances one 4 MHz cycle
+    pub fn tick(&mut self) {
+        if self.powered {
+            self.square1.tick();
+            self.square2.tick();
+            self.wave.tick();
+            self.noise.tick();
+        }
+
+        let [left, right] = self.mix();
+        self.sum[0] += left;
+        self.sum[1] += right;
+        self.sum_cycles += 1;
+
+        self.sample_phase += self.sample_rate;
+        if self.sample_phase >= CLOCK_FREQUENCY {
+            self.sample_phase -= CLOCK_FREQUENCY;
+            self.push_sample();
+        }
+    }
+
+    /// Both outputs through NR51 and NR50, before the capacitor
+    fn mix(&self) -> [f32; 2] {
+        if !self.powered {
+            return [0.0; 2];
+        }
+
+        let outputs = [
+            dac(self.square1.output(), self.square1.dac_enabled()),
+            dac(self.square2.output(), self.square2.dac_enabled()),
+            dac(self.wave.output(), self.wave.dac_enabled()),
+            dac(self.noise.output(), self.noise.dac_enabled()),
+        ];
+        let (nr50, nr51) = (self.registers[0x14], self.registers[0x15]);
+
+        // Right in the low nibble of NR51 and the low bits of NR50
+        [(4, 4), (0, 0)].map(|(panning, volume)| {
+            let sum: f32 = outputs
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| nr51 & (1 << (i + panning)) != 0)
+                .map(|(_, output)| output)
+                .sum();
+            let volume = ((nr50 >> volume) & 0x07) as f32 + 1.0;
+            sum / 4.0 * volume / 8.0
+        })
+    }
+
+    /// Averages the cycles since the last sample, then removes the DC offset
+    fn push_sample(&mut self) {
+        let mut sample = [0.0; 2];
+        for (i, value) in sample.iter_mut().enumerate() {
+            let input = self.sum[i] / self.sum_cycles.max(1) as f32;
+            *value = input - self.capacitors[i];
+            self.capacitors[i] = input - *value * self.charge;
+        }
+        self.sum = [0.0; 2];
+        self.sum_cycles = 0;
+
+        // Nobody is listening, the oldest half goes
+        if self.samples.len() >= self.sample_rate as usize {
+            self.samples.drain(..self.samples.len() / 2);
+        }
+        self.samples.push(sample);
+    }
+}
+
+/// Channel level as an analog value, silent with the DAC off
+fn dac(level: u8, enabled: bool) -> f32 {
+    if enabled {
+        level as f32 / 7.5 - 1.0
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cycles between two frame sequencer steps
+    const STEP_CYCLES: u32 = CLOCK_FREQUENCY / 512;
+
+    #[test]
+    pub fn test_square_length_and_power() {
+        let mut apu = Apu::new(Model::Dmg);
+        apu.write(0xFF24, 0x77);
+        apu.write(0xFF25, 0xFF);
+
+        // Channel 2 at full volume, lasting 2 length clocks
+        apu.write(0xFF16, 0x80 | 62);
+        apu.write(0xFF17, 0xF0);
+        apu.write(0xFF19, 0xC7);
+        assert_eq!(0xF2, apu.read(0xFF26));
+
+        apu.clock_frame_sequencer();
+        apu.clock_frame_sequencer();
+        assert_eq!(0xF2, apu.read(0xFF26));
+        apu.clock_frame_sequencer();
+        assert_eq!(0xF0, apu.read(0xFF26));
+
+        // The DAC being off keeps a trigger from starting the channel
+        apu.write(0xFF17, 0x00);
+        apu.write(0xFF19, 0x80);
+        assert_eq!(0xF0, apu.read(0xFF26));
+
+        apu.write(0xFF30, 0x12);
+        apu.write(0xFF26, 0x00);
+        assert_eq!(0x70, apu.read(0xFF26));
+        assert_eq!(0x00, apu.read(0xFF24));
+        assert_eq!(0x12, apu.read(0xFF30));
+
+        // Writes are ignored until powered on again
+        apu.write(0xFF24, 0x77);
+        apu.write(0xFF26, 0x80);
+        assert_eq!(0x00, apu.read(0xFF24));
+        assert_eq!(0x3F, apu.read(0xFF16));
+    }
+
+    #[test]
+    pub fn test_sweep_overflow() {
+        let mut apu = Apu::new(Model::Cgb);
+        apu.write(0xFF12, 0xF0);
+        // Pace 1, adding period / 2 each time
+        apu.write(0xFF10, 0x11);
+        apu.write(0xFF13, 0x00);
+        apu.write(0xFF14, 0x85);
+        assert_eq!(0x01, apu.read(0xFF26) & 0x0F);
+
+        // 0x500 becomes 0x780, then the check for 0xB40 overflows
+        apu.clock_frame_sequencer();
+        apu.clock_frame_sequencer();
+        apu.clock_frame_sequencer();
+        assert_eq!(0x00, apu.read(0xFF26) & 0x0F);
+    }
+
+    #[test]
+    pub fn test_samples() {
+        let mut apu = Apu::new(Model::Dmg);
+        apu.write(0xFF24, 0x77);
+        apu.write(0xFF25, 0x01);
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF14, 0x80);
+
+        for _ in 0..STEP_CYCLES {
+            apu.tick();
+        }
+        let samples = apu.take_samples();
+        assert_eq!(
+            (STEP_CYCLES * DEFAULT_SAMPLE_RATE / CLOCK_FREQUENCY) as usize,
+            samples.len()
+        );
+
+        // Channel 1 only goes right, left is silent
+        assert!(samples.iter().all(|[left, _]| *left == 0.0));
+        assert!(samples.iter().any(|[_, right]| *right != 0.0));
+        assert!(apu.take_samples().is_empty());
+    }
+}
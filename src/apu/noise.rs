@@ -0,0 +1,191 @@
+use std::mem;
+
+use super::channel::{Envelope, LengthCounter};
+
+// Cycles between LFSR shifts for each divider, before the clock shift
+const DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+/// Channel 4, white noise from a linear feedback shift register
+pub(super) struct Noise {
+    enabled: bool,
+    clock_shift: u8,
+    // 7-bit LFSR instead of 15, for a more metallic sound
+    short_mode: bool,
+    divider: u8,
+    timer: u32,
+    lfsr: u16,
+    length: LengthCounter,
+    envelope: Envelope,
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            clock_shift: 0,
+            short_mode: false,
+            divider: 0,
+            timer: 0,
+            lfsr: 0x7FFF,
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    fn period(&self) -> u32 {
+        DIVISORS[self.divider as usize] << self.clock_shift
+    }
+
+    /// Writes one of the unused FF1F and NR41-NR44
+    pub fn write(&mut self, register: u16, value: u8, extra_length_clock: bool) {
+        match register {
+            0 => {}
+            1 => self.write_length(value),
+            2 => {
+                self.envelope.write(value);
+                if !self.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => {
+                self.clock_shift = value >> 4;
+                self.short_mode = value & 0x08 != 0;
+                self.divider = value & 0x07;
+            }
+            _ => {
+                let trigger = value & 0x80 != 0;
+                if self
+                    .length
+                    .write_control(value & 0x40 != 0, trigger, extra_length_clock)
+                {
+                    self.enabled = false;
+                }
+                if trigger {
+                    self.enabled = self.dac_enabled();
+                    self.timer = self.period();
+                    self.lfsr = 0x7FFF;
+                    self.envelope.trigger();
+                }
+            }
+        }
+    }
+
+    pub fn write_length(&mut self, value: u8) {
+        self.length.load((value & 0x3F) as u16);
+    }
+
+    /// Every register is cleared, and the length too on CGB
+    pub fn power_off(&mut self, keep_length: bool) {
+        let length = mem::replace(&mut self.length, LengthCounter::new(64));
+        *self = Self::new();
+        self.length = length;
+        self.length.power_off(keep_length);
+    }
+
+    /// Advances one 4 MHz cycle
+    pub fn tick(&mut self) {
+        // Clock shifts of 14 and 15 stop the LFSR
+        if self.clock_shift >= 14 {
+            return;
+        }
+
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = self.period();
+
+            let feedback = (self.lfsr ^ (self.lfsr >> 1)) & 0x01;
+            self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+            if self.short_mode {
+                self.lfsr = (self.lfsr & !0x40) | (feedback << 6);
+            }
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Current level, 0 to 15, with the LFSR's bit 0 inverted
+    pub fn output(&self) -> u8 {
+        if self.enabled && self.lfsr & 0x01 == 0 {
+            self.envelope.volume()
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triggered(nr43: u8) -> Noise {
+        let mut noise = Noise::new();
+        noise.write(2, 0xF0, false);
+        noise.write(3, nr43, false);
+        noise.write(4, 0x80, false);
+        noise
+    }
+
+    /// One LFSR shift, every 8 cycles with divider 0
+    fn shift(noise: &mut Noise) -> u8 {
+        for _ in 0..8 {
+            noise.tick();
+        }
+        noise.output()
+    }
+
+    /// Shifts until the LFSR is back to where it was
+    fn period(noise: &mut Noise) -> usize {
+        let start = noise.lfsr;
+        (1..=0x8000)
+            .find(|_| {
+                shift(noise);
+                noise.lfsr == start
+            })
+            .unwrap()
+    }
+
+    #[test]
+    pub fn test_lfsr_sequences() {
+        let mut long = triggered(0x00);
+        // All ones to start, so silent until a zero reaches bit 0
+        assert_eq!(0, shift(&mut long));
+        assert_eq!(0, shift(&mut long));
+        assert_eq!(0x1FFF, long.lfsr);
+        assert_eq!(0x7FFF, period(&mut long));
+
+        // Once the bits above 6 follow the short sequence
+        let mut short = triggered(0x08);
+        for _ in 0..15 {
+            shift(&mut short);
+        }
+        assert_eq!(0x7F, period(&mut short));
+    }
+
+    #[test]
+    pub fn test_clock_shift_stops() {
+        for nr43 in [0xE0, 0xF0] {
+            let mut noise = triggered(nr43);
+            for _ in 0..0x100 {
+                shift(&mut noise);
+            }
+
+            assert_eq!(0x7FFF, noise.lfsr);
+        }
+    }
+}
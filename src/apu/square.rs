@@ -0,0 +1,218 @@
+use std::mem;
+
+use super::channel::{Envelope, LengthCounter};
+
+// Waveforms for 12.5%, 25%, 50% and 75%, played from bit 0 up
+const DUTY_PATTERNS: [u8; 4] = [0b1000_0000, 0b1000_0001, 0b1110_0001, 0b0111_1110];
+
+const MAX_PERIOD: u16 = 0x7FF;
+
+/// Channel 1's frequency sweep, clocked at 128 Hz
+struct Sweep {
+    // NR10
+    pace: u8,
+    decrease: bool,
+    shift: u8,
+    timer: u8,
+    shadow: u16,
+    enabled: bool,
+    // A decreasing calculation happened since the trigger
+    negated: bool,
+}
+
+impl Sweep {
+    fn new() -> Self {
+        Self {
+            pace: 0,
+            decrease: false,
+            shift: 0,
+            timer: 0,
+            shadow: 0,
+            enabled: false,
+            negated: false,
+        }
+    }
+
+    // A pace of 0 still runs the timer, as if it were 8
+    fn reload_timer(&mut self) {
+        self.timer = if self.pace == 0 { 8 } else { self.pace };
+    }
+
+    fn calculate(&mut self) -> u16 {
+        let delta = self.shadow >> self.shift;
+        if self.decrease {
+            self.negated = true;
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        }
+    }
+}
+
+/// Channels 1 and 2, a duty cycle wave with a volume envelope, and a sweep
+/// for channel 1
+pub(super) struct Square {
+    sweep: Option<Sweep>,
+    enabled: bool,
+    duty: u8,
+    duty_position: u8,
+    period: u16,
+    timer: u16,
+    length: LengthCounter,
+    envelope: Envelope,
+}
+
+impl Square {
+    pub fn new(sweep: bool) -> Self {
+        Self {
+            sweep: sweep.then(Sweep::new),
+            enabled: false,
+            duty: 0,
+            duty_position: 0,
+            period: 0,
+            timer: 0,
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    /// Writes one of the 5 registers, NR10-NR14 or the unused FF15 and NR21-NR24
+    pub fn write(&mut self, register: u16, value: u8, extra_length_clock: bool) {
+        match register {
+            0 => {
+                if let Some(sweep) = &mut self.sweep {
+                    sweep.pace = (value >> 4) & 0x07;
+                    sweep.decrease = value & 0x08 != 0;
+                    sweep.shift = value & 0x07;
+
+                    // Going back to increasing after a decrease stops the channel
+                    if sweep.negated && !sweep.decrease {
+                        self.enabled = false;
+                    }
+                }
+            }
+            1 => {
+                self.duty = value >> 6;
+                self.write_length(value);
+            }
+            2 => {
+                self.envelope.write(value);
+                if !self.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.period = (self.period & 0x700) | value as u16,
+            _ => {
+                self.period = (self.period & 0xFF) | ((value as u16 & 0x07) << 8);
+
+                let trigger = value & 0x80 != 0;
+                if self
+                    .length
+                    .write_control(value & 0x40 != 0, trigger, extra_length_clock)
+                {
+                    self.enabled = false;
+                }
+                if trigger {
+                    self.trigger();
+                }
+            }
+        }
+    }
+
+    /// The length part of NRx1, the only one a DMG takes while powered off
+    pub fn write_length(&mut self, value: u8) {
+        self.length.load((value & 0x3F) as u16);
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled();
+        self.timer = (2048 - self.period) * 4;
+        self.envelope.trigger();
+
+        if let Some(sweep) = &mut self.sweep {
+            sweep.shadow = self.period;
+            sweep.reload_timer();
+            sweep.enabled = sweep.pace != 0 || sweep.shift != 0;
+            sweep.negated = false;
+
+            // The overflow check happens right away
+            if sweep.shift != 0 && sweep.calculate() > MAX_PERIOD {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// Every register is cleared, and the length too on CGB
+    pub fn power_off(&mut self, keep_length: bool) {
+        let length = mem::replace(&mut self.length, LengthCounter::new(64));
+        *self = Self::new(self.sweep.is_some());
+        self.length = length;
+        self.length.power_off(keep_length);
+    }
+
+    /// Advances one 4 MHz cycle
+    pub fn tick(&mut self) {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = (2048 - self.period) * 4;
+            self.duty_position = (self.duty_position + 1) & 0x07;
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_sweep(&mut self) {
+        let Some(sweep) = &mut self.sweep else {
+            return;
+        };
+
+        sweep.timer = sweep.timer.saturating_sub(1);
+        if sweep.timer > 0 {
+            return;
+        }
+        sweep.reload_timer();
+
+        if !sweep.enabled || sweep.pace == 0 {
+            return;
+        }
+
+        let period = sweep.calculate();
+        if period > MAX_PERIOD {
+            self.enabled = false;
+        } else if sweep.shift != 0 {
+            sweep.shadow = period;
+            self.period = period;
+
+            // Checked again with the new period, without keeping the result
+            if sweep.calculate() > MAX_PERIOD {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// Current level, 0 to 15
+    pub fn output(&self) -> u8 {
+        let high = (DUTY_PATTERNS[self.duty as usize] >> self.duty_position) & 0x01 != 0;
+        if self.enabled && high {
+            self.envelope.volume()
+        } else {
+            0
+        }
+    }
+}
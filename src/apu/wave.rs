@@ -0,0 +1,200 @@
+use std::mem;
+
+use super::channel::LengthCounter;
+
+// Right shift of the samples for each NR32 output level, muting with 4
+const VOLUME_SHIFTS: [u8; 4] = [4, 0, 1, 2];
+
+/// Channel 3, playing the 32 4-bit samples of wave RAM
+pub(super) struct Wave {
+    enabled: bool,
+    dac_enabled: bool,
+    output_level: u8,
+    period: u16,
+    timer: u16,
+    position: u8,
+    // Last sample read from wave RAM
+    sample: u8,
+    length: LengthCounter,
+    ram: [u8; 16],
+}
+
+impl Wave {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            dac_enabled: false,
+            output_level: 0,
+            period: 0,
+            timer: 0,
+            position: 0,
+            sample: 0,
+            length: LengthCounter::new(256),
+            ram: [0; 16],
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.dac_enabled
+    }
+
+    /// Writes one of NR30-NR34
+    pub fn write(&mut self, register: u16, value: u8, extra_length_clock: bool) {
+        match register {
+            0 => {
+                self.dac_enabled = value & 0x80 != 0;
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            1 => self.write_length(value),
+            2 => self.output_level = (value >> 5) & 0x03,
+            3 => self.period = (self.period & 0x700) | value as u16,
+            _ => {
+                self.period = (self.period & 0xFF) | ((value as u16 & 0x07) << 8);
+
+                let trigger = value & 0x80 != 0;
+                if self
+                    .length
+                    .write_control(value & 0x40 != 0, trigger, extra_length_clock)
+                {
+                    self.enabled = false;
+                }
+                if trigger {
+                    self.enabled = self.dac_enabled;
+                    self.timer = (2048 - self.period) * 2;
+                    self.position = 0;
+                }
+            }
+        }
+    }
+
+    pub fn write_length(&mut self, value: u8) {
+        self.length.load(value as u16);
+    }
+
+    /// While the channel plays, the CPU only reaches the byte being played,
+    /// like on a CGB. The DMG is even more restrictive.
+    fn ram_offset(&self, offset: usize) -> usize {
+        if self.enabled {
+            self.position as usize / 2
+        } else {
+            offset
+        }
+    }
+
+    pub fn read_ram(&self, offset: usize) -> u8 {
+        self.ram[self.ram_offset(offset)]
+    }
+
+    pub fn write_ram(&mut self, offset: usize, value: u8) {
+        let offset = self.ram_offset(offset);
+        self.ram[offset] = value;
+    }
+
+    /// Everything but wave RAM is cleared, and the length too on CGB
+    pub fn power_off(&mut self, keep_length: bool) {
+        let length = mem::replace(&mut self.length, LengthCounter::new(256));
+        let ram = self.ram;
+        *self = Self::new();
+        self.length = length;
+        self.length.power_off(keep_length);
+        self.ram = ram;
+    }
+
+    /// Advances one 4 MHz cycle
+    pub fn tick(&mut self) {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = (2048 - self.period) * 2;
+            self.position = (self.position + 1) & 0x1F;
+
+            // High nibble first
+            let byte = self.ram[self.position as usize / 2];
+            self.sample = if self.position & 0x01 == 0 {
+                byte >> 4
+            } else {
+                byte & 0x0F
+            };
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    /// Current level, 0 to 15
+    pub fn output(&self) -> u8 {
+        if self.enabled {
+            self.sample >> VOLUME_SHIFTS[self.output_level as usize]
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Highest period, a new sample every 2 cycles
+    fn playing(output_level: u8) -> Wave {
+        let mut wave = Wave::new();
+        wave.write_ram(0, 0x12);
+        wave.write_ram(1, 0x34);
+        wave.write(0, 0x80, false);
+        wave.write(2, output_level << 5, false);
+        wave.write(3, 0xFF, false);
+        wave.write(4, 0x87, false);
+        wave
+    }
+
+    fn next_sample(wave: &mut Wave) -> u8 {
+        wave.tick();
+        wave.tick();
+        wave.output()
+    }
+
+    #[test]
+    pub fn test_nibble_order() {
+        let mut wave = playing(1);
+
+        // Playback starts at the second sample, high nibbles first
+        assert_eq!(0x2, next_sample(&mut wave));
+        assert_eq!(0x3, next_sample(&mut wave));
+        assert_eq!(0x4, next_sample(&mut wave));
+    }
+
+    #[test]
+    pub fn test_output_levels() {
+        for (output_level, sample) in [(0, 0x0), (1, 0x4), (2, 0x2), (3, 0x1)] {
+            let mut wave = playing(output_level);
+            next_sample(&mut wave);
+            next_sample(&mut wave);
+
+            assert_eq!(sample, next_sample(&mut wave));
+        }
+    }
+
+    #[test]
+    pub fn test_ram_while_playing() {
+        let mut wave = playing(1);
+        next_sample(&mut wave);
+        next_sample(&mut wave);
+
+        // Only the byte being played is reachable, whatever the offset
+        assert_eq!(0x34, wave.read_ram(0x0F));
+        wave.write_ram(0x08, 0x56);
+        assert_eq!(0x56, wave.read_ram(0x00));
+
+        wave.write(0, 0x00, false);
+        assert_eq!(0x12, wave.read_ram(0x00));
+        assert_eq!(0x56, wave.read_ram(0x01));
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Frequency of the PPU and APU, which double speed leaves alone
+pub const CLOCK_FREQUENCY: u32 = 4194304;
+
+/// One frame is 154 lines of 456 cycles
+pub const CYCLES_PER_FRAME: u32 = 70224;
+
+// Past this, the emulation gives up catching up
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Keeps the emulation at the speed of the real console
+pub struct FrameLimiter {
+    start: Instant,
+    frames: u64,
+    cycles: u32,
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameLimiter {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            frames: 0,
+            cycles: 0,
+        }
+    }
+
+    /// Counts emulated 4 MHz cycles, sleeping at the end of each frame
+    ///
+    /// Returns whether a frame was completed.
+    pub fn step(&mut self, cycles: u32) -> bool {
+        self.cycles += cycles;
+        if self.cycles < CYCLES_PER_FRAME {
+            return false;
+        }
+
+        self.cycles -= CYCLES_PER_FRAME;
+        self.frames += 1;
+
+        let target = Duration::from_secs_f64(
+            self.frames as f64 * CYCLES_PER_FRAME as f64 / CLOCK_FREQUENCY as f64,
+        );
+        let elapsed = self.start.elapsed();
+
+        if target > elapsed {
+            thread::sleep(target - elapsed);
+        } else if elapsed - target > MAX_LAG {
+            // Too slow, start counting again from now
+            self.start = Instant::now();
+            self.frames = 0;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_frame_every_70224_cycles() {
+        let mut limiter = FrameLimiter::new();
+
+        assert!(!limiter.step(CYCLES_PER_FRAME - 4));
+        assert!(limiter.step(8));
+        // The extra cycles count towards the next frame
+        assert!(!limiter.step(CYCLES_PER_FRAME - 8));
+        assert!(limiter.step(4));
+    }
+}
@@ -0,0 +1,211 @@
+use std::{fs, path::Path, process};
+
+use crate::memory::{
+    cartridge_type_name, compute_global_checksum, compute_header_checksum, Header, HeaderFix,
+    SaveFile,
+};
+
+const USAGE: &str = "Usage: sabitaboy header <rom> [--fix lhg] [--title <title>] \
+[--type <code|name>] [--rom-size <code>] [--ram-size <code>]";
+
+/// Parses `0x1B`, `$1B` or a decimal value
+fn parse_code(value: &str) -> Option<u8> {
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .or_else(|| value.strip_prefix('$'))
+    {
+        return u8::from_str_radix(hex, 16).ok();
+    }
+
+    value.parse().ok()
+}
+
+/// Also accepts the cartridge type by name, e.g. `MBC5+RAM+BATTERY`
+fn parse_cartridge_type(value: &str) -> Option<u8> {
+    parse_code(value)
+        .or_else(|| (0..=0xFF).find(|code| cartridge_type_name(*code).eq_ignore_ascii_case(value)))
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    println!("{}", message);
+    println!("{}", USAGE);
+    process::exit(2);
+}
+
+fn check(ok: bool) -> &'static str {
+    if ok {
+        "OK"
+    } else {
+        "BAD"
+    }
+}
+
+/// Prints the header and verifies it, returning whether everything checks out
+fn print_header(rom: &[u8]) -> bool {
+    let header = match Header::parse(rom) {
+        Some(header) => header,
+        None => {
+            println!("File too small to hold a header ({} bytes)", rom.len());
+            return false;
+        }
+    };
+
+    let logo_ok = header.is_logo_valid();
+    let expected_header_checksum = compute_header_checksum(rom);
+    let expected_global_checksum = compute_global_checksum(rom);
+    let size_ok = header.rom_size_bytes() == Some(rom.len());
+
+    println!(
+        "Entry point:       {:02X} {:02X} {:02X} {:02X}",
+        header.entry_point[0], header.entry_point[1], header.entry_point[2], header.entry_point[3]
+    );
+    println!("Nintendo logo:     {}", check(logo_ok));
+    println!("Title:             {}", header.title());
+    println!(
+        "Manufacturer code: {}",
+        String::from_utf8_lossy(&header.manufacturer_code)
+    );
+    println!(
+        "CGB flag:          {:02X} ({})",
+        header.cgb_flag,
+        match header.cgb_flag {
+            0x80 => "CGB enhanced",
+            0xC0 => "CGB only",
+            _ => "DMG",
+        }
+    );
+    println!("Licensee:          {}", header.licensee());
+    println!(
+        "SGB flag:          {:02X} ({})",
+        header.sgb_flag,
+        if header.sgb_flag == 0x03 {
+            "SGB functions"
+        } else {
+            "none"
+        }
+    );
+    println!(
+        "Cartridge type:    {:02X} ({})",
+        header.cartridge_type,
+        cartridge_type_name(header.cartridge_type)
+    );
+    match header.rom_size_bytes() {
+        Some(size) => println!(
+            "ROM size:          {:02X} ({} KiB, file is {} KiB) {}",
+            header.rom_size,
+            size / 1024,
+            rom.len() / 1024,
+            check(size_ok)
+        ),
+        None => println!("ROM size:          {:02X} (unknown)", header.rom_size),
+    }
+    match header.ram_size_bytes() {
+        Some(size) => println!(
+            "RAM size:          {:02X} ({} KiB)",
+            header.ram_size,
+            size / 1024
+        ),
+        None => println!("RAM size:          {:02X} (unknown)", header.ram_size),
+    }
+    println!(
+        "Destination:       {:02X} ({})",
+        header.destination_code,
+        if header.destination_code == 0x00 {
+            "Japan"
+        } else {
+            "overseas"
+        }
+    );
+    println!("Version:           {:02X}", header.version);
+    println!(
+        "Header checksum:   {:02X} (expected {:02X}) {}",
+        header.header_checksum,
+        expected_header_checksum,
+        check(header.header_checksum == expected_header_checksum)
+    );
+    println!(
+        "Global checksum:   {:04X} (expected {:04X}) {}",
+        header.global_checksum,
+        expected_global_checksum,
+        check(header.global_checksum == expected_global_checksum)
+    );
+
+    // The global checksum isn't verified by the hardware, nor is the size
+    logo_ok && header.header_checksum == expected_header_checksum
+}
+
+/// `sabitaboy header`: prints and verifies a header, optionally fixing it in place
+pub fn run(args: impl Iterator<Item = String>) {
+    let mut rom_path: Option<String> = None;
+    let mut fix = HeaderFix::default();
+
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| exit_with_usage(&format!("Missing value for {}", arg)))
+        };
+
+        match arg.as_str() {
+            "--fix" => {
+                for flag in value().chars() {
+                    match flag {
+                        'l' => fix.logo = true,
+                        'h' => fix.header_checksum = true,
+                        'g' => fix.global_checksum = true,
+                        _ => exit_with_usage(&format!("Unknown fix flag {}", flag)),
+                    }
+                }
+            }
+            "--title" => fix.title = Some(value()),
+            "--type" => {
+                let value = value();
+                fix.cartridge_type = Some(parse_cartridge_type(&value).unwrap_or_else(|| {
+                    exit_with_usage(&format!("Unknown cartridge type {}", value))
+                }));
+            }
+            "--rom-size" => {
+                let value = value();
+                fix.rom_size = Some(parse_code(&value).unwrap_or_else(|| {
+                    exit_with_usage(&format!("Invalid ROM size code {}", value))
+                }));
+            }
+            "--ram-size" => {
+                let value = value();
+                fix.ram_size = Some(parse_code(&value).unwrap_or_else(|| {
+                    exit_with_usage(&format!("Invalid RAM size code {}", value))
+                }));
+            }
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    let rom_path = rom_path.unwrap_or_else(|| exit_with_usage("Missing ROM path"));
+    let mut rom = match fs::read(&rom_path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            println!("Could not read {}: {}", rom_path, e);
+            process::exit(1);
+        }
+    };
+
+    if !fix.is_empty() {
+        if let Err(e) = fix.apply(&mut rom) {
+            println!("Could not fix {}: {}", rom_path, e);
+            process::exit(1);
+        }
+
+        // The previous version is kept as `<rom>.1`
+        let mut file = SaveFile::new(Path::new(&rom_path).to_path_buf());
+        if let Err(e) = file.write(&rom) {
+            println!("Could not write {}: {}", rom_path, e);
+            process::exit(1);
+        }
+        println!("Fixed {}, backup in {}.1", rom_path, rom_path);
+    }
+
+    if !print_header(&rom) {
+        process::exit(1);
+    }
+}
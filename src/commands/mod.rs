@@ -0,0 +1,2 @@
+// Subcommands that work on a ROM file instead of running it
+pub mod header;
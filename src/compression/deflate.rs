@@ -0,0 +1,210 @@
+use super::inflate::{DISTANCE_BASE, DISTANCE_EXTRA, LENGTH_BASE, LENGTH_EXTRA};
+
+// DEFLATE encoder (RFC 1951), a single block with the fixed Huffman codes,
+// which is plenty for screenshots
+
+const WINDOW_SIZE: usize = 0x8000;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+// Earlier positions tried for each match, more compresses better but slower
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const NONE: usize = usize::MAX;
+
+struct BitWriter {
+    output: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            output: Vec::new(),
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, value: u32, n: u32) {
+        self.buffer |= value << self.count;
+        self.count += n;
+
+        while self.count >= 8 {
+            self.output.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes go most significant bit first, unlike everything else
+    fn code(&mut self, code: u32, length: u32) {
+        self.bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.output.push(self.buffer as u8);
+        }
+        self.output
+    }
+}
+
+fn literal(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.code(0x30 + symbol, 8),
+        144..=255 => writer.code(0x190 + symbol - 144, 9),
+        256..=279 => writer.code(symbol - 256, 7),
+        _ => writer.code(0xC0 + symbol - 280, 8),
+    }
+}
+
+fn length(writer: &mut BitWriter, length: usize) {
+    let index = LENGTH_BASE
+        .iter()
+        .rposition(|base| *base as usize <= length)
+        .unwrap();
+
+    literal(writer, 257 + index as u32);
+    writer.bits(
+        (length - LENGTH_BASE[index] as usize) as u32,
+        LENGTH_EXTRA[index] as u32,
+    );
+}
+
+fn distance(writer: &mut BitWriter, distance: usize) {
+    let index = DISTANCE_BASE
+        .iter()
+        .rposition(|base| *base as usize <= distance)
+        .unwrap();
+
+    writer.code(index as u32, 5);
+    writer.bits(
+        (distance - DISTANCE_BASE[index] as usize) as u32,
+        DISTANCE_EXTRA[index] as u32,
+    );
+}
+
+fn hash(data: &[u8]) -> usize {
+    let value = ((data[0] as u32) << 16) | ((data[1] as u32) << 8) | data[2] as u32;
+    (value.wrapping_mul(0x9E3779B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Previous occurrences of each 3 byte sequence, chained through the window
+struct Matcher {
+    head: Vec<usize>,
+    previous: Vec<usize>,
+}
+
+impl Matcher {
+    fn new() -> Self {
+        Self {
+            head: vec![NONE; 1 << HASH_BITS],
+            previous: vec![NONE; WINDOW_SIZE],
+        }
+    }
+
+    fn insert(&mut self, data: &[u8], position: usize) {
+        if position + MIN_MATCH > data.len() {
+            return;
+        }
+
+        let hash = hash(&data[position..]);
+        self.previous[position % WINDOW_SIZE] = self.head[hash];
+        self.head[hash] = position;
+    }
+
+    /// Length and distance of the longest match for the data at position
+    fn longest(&self, data: &[u8], position: usize) -> (usize, usize) {
+        if position + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+
+        let max_length = MAX_MATCH.min(data.len() - position);
+        let mut best = (0, 0);
+        let mut candidate = self.head[hash(&data[position..])];
+
+        for _ in 0..MAX_CHAIN {
+            if candidate == NONE || candidate >= position || position - candidate > WINDOW_SIZE {
+                break;
+            }
+
+            let length = data[candidate..]
+                .iter()
+                .zip(&data[position..position + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            if length > best.0 {
+                best = (length, position - candidate);
+                if length == max_length {
+                    break;
+                }
+            }
+
+            candidate = self.previous[candidate % WINDOW_SIZE];
+        }
+
+        best
+    }
+}
+
+/// Compresses data into a raw deflate stream
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    let mut matcher = Matcher::new();
+
+    // Final block, fixed codes
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    let mut position = 0;
+    while position < data.len() {
+        let (match_length, match_distance) = matcher.longest(data, position);
+
+        if match_length >= MIN_MATCH {
+            length(&mut writer, match_length);
+            distance(&mut writer, match_distance);
+
+            for skipped in position..position + match_length {
+                matcher.insert(data, skipped);
+            }
+            position += match_length;
+        } else {
+            literal(&mut writer, data[position] as u32);
+            matcher.insert(data, position);
+            position += 1;
+        }
+    }
+
+    // End of block
+    literal(&mut writer, 256);
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::inflate;
+
+    #[test]
+    pub fn test_round_trip() {
+        let text = b"Sabitaboy sabitaboy sabitaboy! 0123456789 0123456789".to_vec();
+        assert_eq!(text, inflate(&deflate(&text), usize::MAX).unwrap());
+
+        // Long runs and matches reaching across the whole window
+        let data: Vec<u8> = (0..100000u32)
+            .map(|i| ((i / 7) ^ (i % 40000 / 300)) as u8)
+            .collect();
+        let compressed = deflate(&data);
+        assert!(compressed.len() < data.len() / 4);
+        assert_eq!(data, inflate(&compressed, usize::MAX).unwrap());
+
+        assert_eq!(
+            Vec::<u8>::new(),
+            inflate(&deflate(&[]), usize::MAX).unwrap()
+        );
+    }
+}
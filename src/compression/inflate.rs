@@ -0,0 +1,349 @@
+use std::fmt::Display;
+
+// DEFLATE decoder (RFC 1951), for the zip and gzip ROM archives
+
+const MAX_BITS: usize = 15;
+
+pub(super) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+pub(super) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+pub(super) const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+pub(super) const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+// Order in which the code length code lengths are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+#[derive(Debug)]
+pub struct InflateError(&'static str);
+
+impl Display for InflateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "corrupted deflate stream: {}", self.0)
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    /// Reads bits least significant first
+    fn bits(&mut self, n: u32) -> Result<u32, InflateError> {
+        while self.count < n {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or(InflateError("unexpected end of data"))?;
+            self.pos += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+
+        let value = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+
+        Ok(value)
+    }
+
+    /// Drops the bits left in the current byte
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// Canonical Huffman code, as counts of codes per length and sorted symbols
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, InflateError> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for length in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(InflateError("invalid Huffman code"))
+    }
+}
+
+/// Decompresses a raw deflate stream, failing once the output would exceed
+/// `max_size`
+pub fn inflate(data: &[u8], max_size: usize) -> Result<Vec<u8>, InflateError> {
+    let mut reader = BitReader::new(data);
+    let mut output = Output {
+        bytes: Vec::new(),
+        max_size,
+    };
+
+    loop {
+        let last = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => stored(&mut reader, &mut output)?,
+            1 => {
+                let (literals, distances) = fixed_codes();
+                codes(&mut reader, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                codes(&mut reader, &mut output, &literals, &distances)?;
+            }
+            _ => return Err(InflateError("invalid block type")),
+        }
+
+        if last {
+            return Ok(output.bytes);
+        }
+    }
+}
+
+/// Decompressed data, which a tiny stream can make huge
+struct Output {
+    bytes: Vec<u8>,
+    max_size: usize,
+}
+
+impl Output {
+    fn reserve(&self, length: usize) -> Result<(), InflateError> {
+        if self.bytes.len() + length > self.max_size {
+            return Err(InflateError("output too large"));
+        }
+        Ok(())
+    }
+}
+
+fn stored(reader: &mut BitReader, output: &mut Output) -> Result<(), InflateError> {
+    reader.align();
+
+    let header = reader
+        .data
+        .get(reader.pos..reader.pos + 4)
+        .ok_or(InflateError("unexpected end of data"))?;
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+
+    if length != !complement {
+        return Err(InflateError("stored block length mismatch"));
+    }
+
+    let start = reader.pos + 4;
+    let bytes = reader
+        .data
+        .get(start..start + length as usize)
+        .ok_or(InflateError("unexpected end of data"))?;
+    output.reserve(bytes.len())?;
+    output.bytes.extend_from_slice(bytes);
+    reader.pos = start + length as usize;
+
+    Ok(())
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[0..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..288].fill(8);
+
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), InflateError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    if literal_count > 286 || distance_count > 30 {
+        return Err(InflateError("too many codes"));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+
+    while i < lengths.len() {
+        let symbol = code_length_code.decode(reader)?;
+
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..i]
+                    .last()
+                    .ok_or(InflateError("repeat without a previous length"))?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+
+        if i + repeat > lengths.len() {
+            return Err(InflateError("too many code lengths"));
+        }
+
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+
+    if lengths[256] == 0 {
+        return Err(InflateError("missing end of block code"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+fn codes(
+    reader: &mut BitReader,
+    output: &mut Output,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), InflateError> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+
+        match symbol {
+            0..=255 => {
+                output.reserve(1)?;
+                output.bytes.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            257..=285 => {
+                let index = symbol - 257;
+                let length =
+                    LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+
+                let index = distances.decode(reader)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err(InflateError("invalid distance code"));
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+
+                if distance > output.bytes.len() {
+                    return Err(InflateError("distance too far back"));
+                }
+                output.reserve(length)?;
+
+                // Copied byte by byte, the match may overlap its own output
+                let start = output.bytes.len() - distance;
+                for i in 0..length {
+                    output.bytes.push(output.bytes[start + i]);
+                }
+            }
+            _ => return Err(InflateError("invalid literal/length code")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::crc32;
+
+    #[test]
+    pub fn test_stored() {
+        let data = [0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c'];
+        assert_eq!(b"abc".to_vec(), inflate(&data, 3).unwrap());
+        assert!(inflate(&data, 2).is_err());
+    }
+
+    #[test]
+    pub fn test_fixed() {
+        let data = [
+            0x0B, 0x4E, 0x4C, 0xCA, 0x2C, 0x49, 0x4C, 0xCA, 0xAF, 0x54, 0x28, 0xC6, 0x64, 0x29,
+            0x2A, 0x18, 0x18, 0x1A, 0x19, 0x9B, 0x98, 0x9A, 0x99, 0x5B, 0x58, 0x22, 0x31, 0x01,
+        ];
+        assert_eq!(
+            b"Sabitaboy sabitaboy sabitaboy! 0123456789 0123456789".to_vec(),
+            inflate(&data, usize::MAX).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn test_dynamic() {
+        let data = [
+            0xCD, 0x8F, 0x8B, 0x11, 0x80, 0x30, 0x08, 0x43, 0x67, 0x4D, 0xA6, 0x50, 0xA7, 0xD7,
+            0xCB, 0x07, 0x3A, 0x82, 0x5C, 0x7F, 0x69, 0xE0, 0x95, 0x02, 0x04, 0xBF, 0x80, 0x76,
+            0xF8, 0x00, 0x29, 0xE8, 0x62, 0x56, 0xDB, 0x12, 0x4E, 0x94, 0x15, 0xA9, 0x60, 0xEC,
+            0xCD, 0x1D, 0x6F, 0x98, 0xE6, 0x21, 0xAF, 0xD2, 0x0C, 0xD6, 0xF2, 0x3C, 0xD0, 0x68,
+            0x03, 0x01, 0xA0, 0x7C, 0xFB, 0x83, 0x42, 0xC5, 0x36, 0xC2, 0x23, 0x17, 0xC5, 0x1F,
+            0x5F, 0x5C, 0xE2, 0x56, 0x34, 0x29, 0x8D, 0x95, 0x9A, 0xFA, 0xEB, 0x7E, 0x7E, 0x38,
+            0x5E,
+        ];
+        let output = inflate(&data, usize::MAX).unwrap();
+
+        assert_eq!(450, output.len());
+        assert_eq!(0x35C93FC3, crc32(&output));
+        assert!(inflate(&data, 449).is_err());
+    }
+}
@@ -0,0 +1,5 @@
+mod deflate;
+mod inflate;
+
+pub use deflate::deflate;
+pub use inflate::inflate;
@@ -33,8 +33,6 @@ impl<'a> Cpu<'a> {
     // TODO Implement other CB instructions
 
     pub fn match_cb_prefix(&mut self, opcode: u8) -> Cycles {
-        println!("CB: {:02X}", opcode);
-
         let mut is_hl = false;
 
         let target_value = match &TARGETS[opcode as usize % 8] {
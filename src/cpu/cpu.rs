@@ -46,10 +46,25 @@ impl<'a> Cpu<'a> {
         }
     }
 
+    /// Puts the registers back to their power on values and restarts the boot ROM
+    pub fn reset(&mut self) {
+        self.a.set(0x01);
+        self.f.set(0xB0);
+        self.b.set(0x00);
+        self.c.set(0x13);
+        self.d.set(0x00);
+        self.e.set(0xD8);
+        self.h.set(0x01);
+        self.l.set(0x4D);
+        self.sp.set(0xFFFE);
+        self.pc.set(0x0000);
+        self.flags = Flags::new();
+
+        self.mmu.power_up();
+    }
+
     pub fn execute(&mut self) -> u8 {
         let opcode = self.read_byte();
-        println!("Opcode: {:#04X}", opcode);
-
         self.decode(opcode)
     }
 
@@ -10,6 +10,7 @@ enum RegisterAction {
     None,
 }
 
+#[allow(dead_code)]
 pub enum Target {
     Address8(u8),
     Address16(u16),
@@ -19,6 +20,19 @@ pub enum Target {
 }
 
 impl<'a> Cpu<'a> {
+    // STOP, 2, 4
+    fn stop(&mut self) -> Cycles {
+        // The opcode is followed by an ignored byte
+        self.read_byte();
+
+        // On CGB, an armed KEY1 turns STOP into a speed switch
+        if !self.mmu.stop() {
+            // TODO Enter low power mode until a button is pressed
+        }
+
+        4
+    }
+
     // JP a16, 3, 16
     fn jp_a16(&mut self, condition: bool) -> Cycles {
         let next = self.read_word();
@@ -200,6 +214,7 @@ impl<'a> Cpu<'a> {
         match opcode {
             // NOP
             0x00 => 4,
+            0x10 => self.stop(),
             0xCB => {
                 let cb_opcode = self.read_byte();
                 self.match_cb_prefix(cb_opcode)
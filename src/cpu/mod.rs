@@ -1,5 +1,6 @@
 mod byteutils;
 mod cb_prefix;
+#[allow(clippy::module_inception)]
 mod cpu;
 mod flags;
 mod instr;
@@ -72,6 +72,7 @@ impl Register<u16> {
         (result, overflow)
     }
 
+    #[allow(dead_code)]
     pub fn dec(&mut self) -> (u16, bool) {
         let (result, overflow) = self.value.overflowing_sub(1);
         self.value = result;
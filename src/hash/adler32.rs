@@ -0,0 +1,23 @@
+// Largest prime below 2^16
+const MODULUS: u32 = 65521;
+
+/// Checksum closing zlib streams
+pub fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1, 0), |(a, b), byte| {
+        let a = (a + *byte as u32) % MODULUS;
+        (a, (b + a) % MODULUS)
+    });
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_adler32() {
+        assert_eq!(0x11E60398, adler32(b"Wikipedia"));
+        assert_eq!(0x00000001, adler32(b""));
+    }
+}
@@ -0,0 +1,39 @@
+mod adler32;
+mod crc32;
+mod md5;
+mod sha1;
+
+pub use adler32::adler32;
+pub use crc32::crc32;
+pub use md5::md5;
+pub use sha1::sha1;
+
+/// Lowercase hexadecimal representation of a digest
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_md5() {
+        assert_eq!("d41d8cd98f00b204e9800998ecf8427e", to_hex(&md5(b"")));
+        assert_eq!("900150983cd24fb0d6963f7d28e17f72", to_hex(&md5(b"abc")));
+    }
+
+    #[test]
+    pub fn test_sha1() {
+        assert_eq!(
+            "a9993e364706816aba3e25717850c26c9cd0d89d",
+            to_hex(&sha1(b"abc"))
+        );
+        assert_eq!(
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1",
+            to_hex(&sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            ))
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::{collections::HashMap, io};
+
+/// One `<rom>` of a Logiqx XML DAT, as used by No-Intro and Redump
+#[derive(Clone, Debug, Default)]
+pub struct DatEntry {
+    /// Name of the enclosing `<game>`
+    pub game: String,
+    pub size: Option<usize>,
+    pub crc32: Option<u32>,
+    pub md5: Option<String>,
+    pub sha1: Option<String>,
+    /// `baddump`, `verified`, `nodump`... when the DAT states one
+    pub status: Option<String>,
+    /// From a `<release>` element, older DATs only have it in the name
+    pub region: Option<String>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Splits the inside of a tag into its name and attributes
+fn parse_tag(tag: &str) -> io::Result<(&str, HashMap<String, String>)> {
+    let tag = tag.trim_end_matches('/').trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let (name, mut rest) = tag.split_at(name_end);
+
+    let mut attributes = HashMap::new();
+
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+
+        let equals = rest
+            .find('=')
+            .ok_or_else(|| invalid("attribute without value"))?;
+        let key = rest[..equals].trim();
+        rest = rest[equals + 1..].trim_start();
+
+        let quote = rest
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| invalid("unquoted attribute"))?;
+        let end = rest[1..]
+            .find(quote)
+            .ok_or_else(|| invalid("unterminated attribute"))?;
+
+        attributes.insert(key.to_lowercase(), unescape(&rest[1..end + 1]));
+        rest = &rest[end + 2..];
+    }
+
+    Ok((name, attributes))
+}
+
+/// Reads every ROM entry of a DAT, ignoring everything but the tags
+pub fn parse(xml: &str) -> io::Result<Vec<DatEntry>> {
+    let mut entries: Vec<DatEntry> = Vec::new();
+    let mut game: Option<String> = None;
+    let mut game_region: Option<String> = None;
+    let mut game_start = 0;
+
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+
+        // Comments, declarations and doctypes don't hold anything useful
+        if let Some(comment) = rest.strip_prefix("!--") {
+            let end = comment
+                .find("-->")
+                .ok_or_else(|| invalid("unterminated comment"))?;
+            rest = &comment[end + 3..];
+            continue;
+        }
+
+        let end = rest.find('>').ok_or_else(|| invalid("unterminated tag"))?;
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+
+        if let Some(closing) = tag.strip_prefix('/') {
+            if matches!(closing.trim(), "game" | "machine") {
+                // A release can come after the ROMs it applies to
+                for entry in &mut entries[game_start..] {
+                    if entry.region.is_none() {
+                        entry.region = game_region.clone();
+                    }
+                }
+                game = None;
+                game_region = None;
+            }
+            continue;
+        }
+
+        let (name, mut attributes) = parse_tag(tag)?;
+
+        match name {
+            "game" | "machine" => {
+                game = attributes.remove("name");
+                game_region = None;
+                game_start = entries.len();
+            }
+            "release" if game_region.is_none() => game_region = attributes.remove("region"),
+            "rom" => {
+                let game = game
+                    .clone()
+                    .ok_or_else(|| invalid("rom outside of a game"))?;
+
+                entries.push(DatEntry {
+                    game,
+                    size: attributes.get("size").and_then(|size| size.parse().ok()),
+                    crc32: attributes
+                        .get("crc")
+                        .and_then(|crc| u32::from_str_radix(crc, 16).ok()),
+                    md5: attributes.remove("md5").map(|md5| md5.to_lowercase()),
+                    sha1: attributes.remove("sha1").map(|sha1| sha1.to_lowercase()),
+                    status: attributes.remove("status"),
+                    region: None,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(entries)
+}
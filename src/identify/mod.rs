@@ -0,0 +1,177 @@
+mod dat;
+
+use std::{fs, io, path::Path};
+
+use crate::hash::{crc32, md5, sha1, to_hex};
+
+pub use dat::DatEntry;
+
+// Region tags used by No-Intro, anything else in parentheses is a flag
+const REGIONS: [&str; 22] = [
+    "World",
+    "USA",
+    "Europe",
+    "Japan",
+    "Asia",
+    "Australia",
+    "Brazil",
+    "Canada",
+    "China",
+    "France",
+    "Germany",
+    "Hong Kong",
+    "Italy",
+    "Korea",
+    "Netherlands",
+    "Russia",
+    "Scandinavia",
+    "Spain",
+    "Sweden",
+    "Taiwan",
+    "United Kingdom",
+    "Unknown",
+];
+
+pub struct RomHashes {
+    pub size: usize,
+    pub crc32: u32,
+    pub md5: String,
+    pub sha1: String,
+}
+
+impl RomHashes {
+    pub fn compute(rom: &[u8]) -> Self {
+        Self {
+            size: rom.len(),
+            crc32: crc32(rom),
+            md5: to_hex(&md5(rom)),
+            sha1: to_hex(&sha1(rom)),
+        }
+    }
+}
+
+/// What a DAT knows about a ROM
+#[derive(Debug, PartialEq)]
+pub struct Identification {
+    pub name: String,
+    pub region: Option<String>,
+    pub revision: Option<String>,
+    pub bad_dump: bool,
+}
+
+impl Identification {
+    fn from_entry(entry: &DatEntry) -> Self {
+        let tags: Vec<&str> = entry
+            .game
+            .split('(')
+            .skip(1)
+            .filter_map(|tag| tag.split(')').next())
+            .collect();
+
+        let region = tags
+            .iter()
+            .find(|tag| {
+                tag.split(", ")
+                    .all(|region| REGIONS.contains(&region.trim()))
+            })
+            .map(|tag| tag.to_string())
+            .or_else(|| entry.region.clone());
+
+        let revision = tags
+            .iter()
+            .find(|tag| {
+                tag.starts_with("Rev ")
+                    || (tag.starts_with('v') && tag[1..].starts_with(|c: char| c.is_ascii_digit()))
+            })
+            .map(|tag| tag.to_string());
+
+        // GoodTools style names flag bad dumps with [b], No-Intro uses the status
+        let bad_dump = entry.status.as_deref() == Some("baddump") || entry.game.contains("[b");
+
+        Self {
+            name: entry.game.clone(),
+            region,
+            revision,
+            bad_dump,
+        }
+    }
+}
+
+/// A local DAT file, loaded once and searched by hash
+pub struct Dat {
+    entries: Vec<DatEntry>,
+}
+
+impl Dat {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let xml = fs::read_to_string(path)?;
+        Self::parse(&xml)
+    }
+
+    pub fn parse(xml: &str) -> io::Result<Self> {
+        Ok(Self {
+            entries: dat::parse(xml)?,
+        })
+    }
+
+    /// Looks for the strongest hash first, CRC32 alone must also match the size
+    pub fn identify(&self, hashes: &RomHashes) -> Option<Identification> {
+        let by_sha1 = || {
+            self.entries
+                .iter()
+                .find(|entry| entry.sha1.as_deref() == Some(hashes.sha1.as_str()))
+        };
+        let by_md5 = || {
+            self.entries
+                .iter()
+                .find(|entry| entry.md5.as_deref() == Some(hashes.md5.as_str()))
+        };
+        let by_crc32 = || {
+            self.entries.iter().find(|entry| {
+                entry.crc32 == Some(hashes.crc32)
+                    && entry.size.is_none_or(|size| size == hashes.size)
+            })
+        };
+
+        by_sha1()
+            .or_else(by_md5)
+            .or_else(by_crc32)
+            .map(Identification::from_entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAT: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE datafile PUBLIC "-//Logiqx//DTD ROM Management Datafile//EN" "http://www.logiqx.com/dtds/datafile.dtd">
+<datafile>
+	<header><name>Nintendo - Game Boy</name></header>
+	<game name="Test Game (USA, Europe) (Rev 1)">
+		<description>Test Game (USA, Europe) (Rev 1)</description>
+		<rom name="Test Game (USA, Europe) (Rev 1).gb" size="3" crc="352441C2" md5="900150983CD24FB0D6963F7D28E17F72" sha1="a9993e364706816aba3e25717850c26c9cd0d89d" status="verified"/>
+	</game>
+	<game name="Other &amp; Game (Japan)">
+		<rom name="Other &amp; Game (Japan).gb" size="1" crc="E8B7BE43" status="baddump"/>
+	</game>
+</datafile>"#;
+
+    #[test]
+    pub fn test_identify() {
+        let dat = Dat::parse(DAT).unwrap();
+
+        let identification = dat.identify(&RomHashes::compute(b"abc")).unwrap();
+        assert_eq!("Test Game (USA, Europe) (Rev 1)", identification.name);
+        assert_eq!(Some("USA, Europe"), identification.region.as_deref());
+        assert_eq!(Some("Rev 1"), identification.revision.as_deref());
+        assert!(!identification.bad_dump);
+
+        // Only a CRC32 in the DAT
+        let identification = dat.identify(&RomHashes::compute(b"a")).unwrap();
+        assert_eq!("Other & Game (Japan)", identification.name);
+        assert!(identification.bad_dump);
+
+        assert_eq!(None, dat.identify(&RomHashes::compute(b"abcd")));
+    }
+}
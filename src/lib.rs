@@ -0,0 +1,13 @@
+pub mod apu;
+mod boot_rom;
+pub mod clock;
+pub mod commands;
+mod compression;
+pub mod cpu;
+pub mod hash;
+pub mod identify;
+pub mod memory;
+pub mod ppu;
+pub mod signal;
+pub mod terminal;
+pub mod video;
@@ -1,24 +1,415 @@
-use memory::Cartridge;
+use std::{
+    env,
+    fmt::Display,
+    fs::{self, File},
+    io::{self, BufWriter},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+};
 
-use crate::{cpu::Cpu, memory::Mmu};
+use sabitaboy::{
+    clock::FrameLimiter,
+    commands,
+    cpu::Cpu,
+    identify::{Dat, RomHashes},
+    memory::{Cartridge, ImageSource, LoadOptions, Mmu, Model, PnmImage, SaveManager, TestPattern},
+    ppu::{ButtonCombo, CompatibilityPalettes, Renderer, SCREEN_HEIGHT, SCREEN_WIDTH},
+    signal,
+    terminal::{ColorMode, Terminal},
+    video::{
+        save_screenshot, ColorCorrection, DmgPalette, Filter, FilterChain, Ghosting, Image,
+        PostProcessor, Y4mWriter,
+    },
+};
 
-mod boot_rom;
-mod cpu;
-mod memory;
+/// What gets saved once the emulation reaches a frame, which then stops
+struct Capture {
+    screenshot: Option<PathBuf>,
+    vram_directory: Option<PathBuf>,
+    after_frames: u64,
+    filters: FilterChain,
+}
+
+/// Video written at the console's refresh rate
+struct Recording {
+    recorder: Y4mWriter<BufWriter<File>>,
+    filters: FilterChain,
+}
 
 fn main() {
-    let cartridge = Cartridge::new(String::from("./tetris.gb"));
-    let mut mmu = Mmu::new(cartridge);
+    let mut rom_path = String::from("./tetris.gb");
+    let mut camera_source: Option<String> = None;
+    let mut dat_path: Option<String> = None;
+    let mut model: Option<Model> = None;
+    let mut renderer = Renderer::Scanline;
+    let mut button_combo: Option<ButtonCombo> = None;
+    let mut palette = DmgPalette::default();
+    let mut correction = ColorCorrection::Balanced;
+    let mut ghosting = Ghosting::Off;
+    let mut screenshot_path: Option<PathBuf> = None;
+    let mut vram_directory: Option<PathBuf> = None;
+    let mut capture_after = 1;
+    let mut filters = FilterChain::default();
+    let mut record_path: Option<PathBuf> = None;
+    let mut dedupe_lcd_off = false;
+    let mut color_mode: Option<ColorMode> = None;
+    let mut options = LoadOptions::default();
+
+    let mut args = env::args().skip(1).peekable();
+
+    if args.peek().map(String::as_str) == Some("header") {
+        args.next();
+        commands::header::run(args);
+        return;
+    }
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--camera" => camera_source = args.next(),
+            "--mapper" => options.mapper = Some(parse_flag(&arg, args.next())),
+            "--patch" => options.patch = args.next().map(PathBuf::from),
+            "--entry" => options.entry = args.next(),
+            "--dat" => dat_path = args.next(),
+            "--model" => model = Some(parse_flag(&arg, args.next())),
+            "--ppu" => renderer = parse_flag(&arg, args.next()),
+            "--dmg-palette" => button_combo = Some(parse_flag(&arg, args.next())),
+            "--palette" => palette = parse_flag(&arg, args.next()),
+            "--color-correction" => correction = parse_flag(&arg, args.next()),
+            "--ghosting" => ghosting = parse_flag(&arg, args.next()),
+            "--screenshot" => screenshot_path = args.next().map(PathBuf::from),
+            "--dump-vram" => vram_directory = args.next().map(PathBuf::from),
+            "--capture-after" | "--screenshot-after" => {
+                capture_after = parse_flag(&arg, args.next())
+            }
+            "--filter" => filters = parse_flag(&arg, args.next()),
+            "--scale" => filters
+                .filters
+                .push(Filter::Nearest(parse_flag(&arg, args.next()))),
+            "--record" => record_path = args.next().map(PathBuf::from),
+            "--record-dedupe" => dedupe_lcd_off = true,
+            "--terminal" => color_mode = Some(ColorMode::TrueColor),
+            "--colors" => color_mode = Some(parse_flag(&arg, args.next())),
+            _ => rom_path = arg,
+        }
+    }
+
+    let mut cartridge = match Cartridge::load(Path::new(&rom_path), &options) {
+        Ok(cartridge) => cartridge,
+        Err(e) => {
+            println!("Could not load {}: {}", rom_path, e);
+            process::exit(1);
+        }
+    };
+
+    if let Some(path) = dat_path {
+        print_identification(Path::new(&path), &cartridge);
+    }
+
+    if let Some(source) = camera_source {
+        let source: Box<dyn ImageSource> = match source.as_str() {
+            "pattern" => Box::new(TestPattern::new()),
+            path => match PnmImage::new(path) {
+                Ok(image) => Box::new(image),
+                Err(e) => {
+                    println!("Could not load camera image {}: {}", path, e);
+                    process::exit(1);
+                }
+            },
+        };
+        cartridge.set_image_source(source);
+    }
+
+    // Saves are kept next to the ROM, which is never written to
+    let mut saves = SaveManager::new(cartridge.rom_path(), &cartridge);
+    // Running without it would overwrite the save on exit
+    if let Err(e) = saves.load(&mut cartridge) {
+        println!("Could not load the save: {}", e);
+        process::exit(1);
+    }
+
+    let model = model.unwrap_or_else(|| Model::detect(&cartridge));
+    let mut mmu = Mmu::new(cartridge, model);
+    mmu.ppu_mut().set_renderer(renderer);
+
+    // Like holding buttons during the CGB logo, only for DMG games
+    if let Some(combo) = button_combo {
+        if mmu.ppu().compatibility_palettes().is_some() {
+            let palettes = CompatibilityPalettes::for_buttons(combo);
+            mmu.ppu_mut().set_compatibility_palettes(palettes);
+        }
+    }
     mmu.power_up();
 
-    println!("Booting game: {}", mmu.cartridge().get_rom_title());
+    println!(
+        "Booting game: {} ({})",
+        mmu.cartridge().get_rom_title(),
+        mmu.model()
+    );
+
+    let mut processor = PostProcessor::new(palette, correction);
+    processor.set_ghosting(ghosting);
+
+    let capture = (screenshot_path.is_some() || vram_directory.is_some()).then_some(Capture {
+        screenshot: screenshot_path,
+        vram_directory,
+        after_frames: capture_after,
+        filters: filters.clone(),
+    });
+
+    let mut recorder = record_path.map(|path| {
+        let size = filters.output_size(SCREEN_WIDTH, SCREEN_HEIGHT);
+        let mut recorder = match Y4mWriter::create(&path, size.0, size.1) {
+            Ok(recorder) => recorder,
+            Err(e) => {
+                println!("Could not create {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        };
+        recorder.set_dedupe_lcd_off(dedupe_lcd_off);
+        Recording { recorder, filters }
+    });
+
+    let mut terminal =
+        color_mode.map(
+            |mode| match Terminal::new(mode, mmu.cartridge().get_rom_title()) {
+                Ok(terminal) => terminal,
+                Err(e) => {
+                    println!("Could not set up the terminal: {}", e);
+                    process::exit(1);
+                }
+            },
+        );
+
+    // Whatever stops the emulation, the save gets written before exiting
+    signal::install();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        run(
+            &mut mmu,
+            &mut saves,
+            &mut processor,
+            capture.as_ref(),
+            recorder.as_mut(),
+            terminal.as_mut(),
+        )
+    }));
+
+    // Back to a normal terminal before printing anything
+    drop(terminal);
+
+    if let Ok(messages) = &result {
+        for message in messages {
+            println!("{}", message);
+        }
+    }
+
+    if let Some(recording) = &mut recorder {
+        if let Err(e) = recording.recorder.flush() {
+            println!("Could not write the video: {}", e);
+        }
+    }
+
+    if let Err(e) = saves.flush(mmu.cartridge_mut()) {
+        println!("Could not write the save: {}", e);
+    }
+
+    if let Err(panic) = result {
+        panic::resume_unwind(panic);
+    }
+}
+
+/// Value given to a flag, exiting with the reason when it is missing or invalid
+fn parse_flag<T>(flag: &str, value: Option<String>) -> T
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let Some(value) = value else {
+        println!("Missing value for {}", flag);
+        process::exit(1);
+    };
+
+    match value.parse() {
+        Ok(value) => value,
+        Err(e) => {
+            println!("Invalid value for {}: {}", flag, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Labels the ROM with its canonical name, the header title being too short
+fn print_identification(dat_path: &Path, cartridge: &Cartridge) {
+    let dat = match Dat::load(dat_path) {
+        Ok(dat) => dat,
+        Err(e) => {
+            println!("Could not read {}: {}", dat_path.display(), e);
+            return;
+        }
+    };
+
+    let hashes = RomHashes::compute(cartridge.rom());
+    println!("CRC32: {:08X}", hashes.crc32);
+    println!("MD5: {}", hashes.md5);
+    println!("SHA-1: {}", hashes.sha1);
+
+    match dat.identify(&hashes) {
+        Some(identification) => {
+            println!("Name: {}", identification.name);
+            println!(
+                "Region: {}",
+                identification.region.as_deref().unwrap_or("unknown")
+            );
+            println!(
+                "Revision: {}",
+                identification.revision.as_deref().unwrap_or("original")
+            );
+            if identification.bad_dump {
+                println!("Known bad dump");
+            }
+        }
+        None => println!("Not found in {}", dat_path.display()),
+    }
+}
 
-    let mut cpu = Cpu::new(&mut mmu);
+/// Tile sheet, tile maps and OAM, each as a PNG and a text file
+fn dump_vram(mmu: &Mmu, directory: &Path) -> io::Result<()> {
+    fs::create_dir_all(directory)?;
+
+    let (ppu, vram, oam) = (mmu.ppu(), mmu.vram(), mmu.oam());
+    ppu.tile_sheet(vram).save(&directory.join("tiles.png"))?;
+    fs::write(directory.join("tiles.txt"), ppu.tile_sheet_text(vram))?;
+
+    for map in 0..2 {
+        ppu.tile_map(vram, map)
+            .save(&directory.join(format!("map{}.png", map)))?;
+        fs::write(
+            directory.join(format!("map{}.txt", map)),
+            ppu.tile_map_text(vram, map),
+        )?;
+    }
+
+    ppu.oam_sheet(vram, oam).save(&directory.join("oam.png"))?;
+    fs::write(directory.join("oam.txt"), ppu.oam_text(oam))
+}
+
+/// Status line of the terminal frontend, printing would garble its screen
+fn report(terminal: Option<&mut Terminal>, message: String) {
+    match terminal {
+        Some(terminal) => terminal.set_message(message),
+        None => println!("{}", message),
+    }
+}
+
+/// Emulates until something stops it, returning the messages to print once
+/// the terminal is back to normal
+fn run(
+    mmu: &mut Mmu,
+    saves: &mut SaveManager,
+    processor: &mut PostProcessor,
+    capture: Option<&Capture>,
+    mut recording: Option<&mut Recording>,
+    mut terminal: Option<&mut Terminal>,
+) -> Vec<String> {
+    let mut cpu = Cpu::new(mmu);
 
     cpu.power_up();
 
+    let mut limiter = FrameLimiter::new();
+    let mut frames = 0;
+    // Last frame, kept when it is needed between PPU frames
+    let mut image: Option<Image> = None;
+
     loop {
         // TODO Redo the whole loop
-        cpu.execute();
+        let cycles = cpu.execute();
+
+        // Time is counted at 4 MHz, in double speed the CPU gets twice the cycles
+        let elapsed = cpu.mmu.step(cycles);
+        let refreshed = limiter.step(elapsed);
+
+        let cartridge = cpu.mmu.cartridge_mut();
+        if let Err(e) = saves.step(cartridge, elapsed) {
+            report(terminal.as_deref_mut(), format!("Autosave failed: {}", e));
+        }
+
+        if cartridge.take_reset_request() {
+            cpu.reset();
+        }
+
+        if cpu.mmu.ppu_mut().take_frame_ready() {
+            frames += 1;
+
+            // Ghosting needs to see every frame
+            if processor.ghosting() != Ghosting::Off || recording.is_some() || terminal.is_some() {
+                image = Some(processor.process(cpu.mmu.ppu()));
+            }
+
+            if let Some(capture) = capture.filter(|c| frames >= c.after_frames) {
+                let mut messages = Vec::new();
+
+                if let Some(path) = &capture.screenshot {
+                    // Only processed here when ghosting is off, so the blender is untouched
+                    let image = image.unwrap_or_else(|| processor.process(cpu.mmu.ppu()));
+                    messages.push(match save_screenshot(&image, &capture.filters, path) {
+                        Ok(()) => format!("Saved {}", path.display()),
+                        Err(e) => format!("Could not write {}: {}", path.display(), e),
+                    });
+                }
+
+                if let Some(directory) = &capture.vram_directory {
+                    messages.push(match dump_vram(cpu.mmu, directory) {
+                        Ok(()) => format!("Saved the VRAM views in {}", directory.display()),
+                        Err(e) => format!("Could not write the VRAM views: {}", e),
+                    });
+                }
+                return messages;
+            }
+        }
+
+        // The video follows the refresh rate even while the LCD is off
+        if let Some(recording) = recording.as_deref_mut().filter(|_| refreshed) {
+            let ppu = cpu.mmu.ppu();
+            let lcd_on = ppu.is_enabled() && image.is_some();
+            let frame = match &image {
+                Some(image) if lcd_on => recording.filters.apply(image),
+                _ => recording.filters.apply(&processor.lcd_off(ppu.model())),
+            };
+
+            if let Err(e) = recording.recorder.write_frame(&frame, lcd_on) {
+                return vec![format!("Could not write the video: {}", e)];
+            }
+        }
+
+        // Input is read once per frame, like games do
+        if let Some(terminal) = terminal.as_deref_mut().filter(|_| refreshed) {
+            let ppu = cpu.mmu.ppu();
+            let lcd_off;
+            let frame = match &image {
+                Some(image) if ppu.is_enabled() => image,
+                _ => {
+                    lcd_off = processor.lcd_off(ppu.model());
+                    &lcd_off
+                }
+            };
+
+            if terminal.draw(frame).is_err() || !terminal.update_input(cpu.mmu) {
+                return Vec::new();
+            }
+
+            if terminal.take_flush_request() {
+                let message = match saves.flush(cpu.mmu.cartridge_mut()) {
+                    Ok(()) => String::from("Saved"),
+                    Err(e) => format!("Could not write the save: {}", e),
+                };
+                terminal.set_message(message);
+            }
+        }
+
+        // Ctrl-C or a kill, the saves get written on the way out
+        if refreshed && signal::exit_requested() {
+            return Vec::new();
+        }
     }
 }
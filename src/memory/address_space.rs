@@ -1,9 +1,18 @@
+use super::{bytes_to_word, word_to_bytes};
+
 pub trait AddressSpace {
     fn get(&self, address: u16) -> u8;
 
     fn set(&mut self, address: u16, value: u8);
 
-    fn get_word(&self, address: u16) -> u16;
+    fn get_word(&self, address: u16) -> u16 {
+        bytes_to_word(self.get(address.wrapping_add(1)), self.get(address))
+    }
 
-    fn set_word(&mut self, address: u16, value: u16);
+    #[allow(dead_code)]
+    fn set_word(&mut self, address: u16, value: u16) {
+        let (h, l) = word_to_bytes(value);
+        self.set(address.wrapping_add(1), h);
+        self.set(address, l);
+    }
 }
@@ -0,0 +1,333 @@
+use std::io;
+
+use crate::{compression::inflate, hash::crc32};
+
+use super::MAX_ROM_SIZE;
+
+const ZIP_LOCAL_HEADER: u32 = 0x04034B50;
+const ZIP_CENTRAL_HEADER: u32 = 0x02014B50;
+const ZIP_END_OF_DIRECTORY: u32 = 0x06054B50;
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// ROM taken out of an archive
+pub struct Extracted {
+    /// Name of the ROM inside the archive, without any directory
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> io::Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| invalid(String::from("archive is truncated")))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> io::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| invalid(String::from("archive is truncated")))
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+fn is_rom_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.ends_with(".gb") || name.ends_with(".gbc")
+}
+
+/// Extracts the ROM if the data is a zip or gzip archive
+///
+/// Without an entry name, the first `.gb`/`.gbc` file of a zip is picked.
+/// `archive_name` is used to name the ROM of a gzip file without a stored name.
+pub fn extract(
+    data: &[u8],
+    archive_name: &str,
+    entry: Option<&str>,
+) -> io::Result<Option<Extracted>> {
+    if data.starts_with(&ZIP_LOCAL_HEADER.to_le_bytes())
+        || data.starts_with(&ZIP_END_OF_DIRECTORY.to_le_bytes())
+    {
+        extract_zip(data, entry).map(Some)
+    } else if data.starts_with(&GZIP_MAGIC) {
+        extract_gzip(data, archive_name).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn extract_zip(data: &[u8], entry: Option<&str>) -> io::Result<Extracted> {
+    // The end of central directory record is followed by a comment of up to 64 KiB
+    let search_start = data.len().saturating_sub(22 + 0xFFFF);
+    let end = (search_start..data.len().saturating_sub(21))
+        .rev()
+        .find(|&offset| read_u32(data, offset).ok() == Some(ZIP_END_OF_DIRECTORY))
+        .ok_or_else(|| invalid(String::from("zip end of central directory not found")))?;
+
+    let entry_count = read_u16(data, end + 10)?;
+    let mut offset = read_u32(data, end + 16)? as usize;
+
+    for _ in 0..entry_count {
+        if read_u32(data, offset)? != ZIP_CENTRAL_HEADER {
+            return Err(invalid(String::from("corrupted zip central directory")));
+        }
+
+        let flags = read_u16(data, offset + 8)?;
+        let method = read_u16(data, offset + 10)?;
+        let crc = read_u32(data, offset + 16)?;
+        let compressed_size = read_u32(data, offset + 20)? as usize;
+        let size = read_u32(data, offset + 24)? as usize;
+        let name_length = read_u16(data, offset + 28)? as usize;
+        let extra_length = read_u16(data, offset + 30)? as usize;
+        let comment_length = read_u16(data, offset + 32)? as usize;
+        let local_offset = read_u32(data, offset + 42)? as usize;
+
+        let name = data
+            .get(offset + 46..offset + 46 + name_length)
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .ok_or_else(|| invalid(String::from("archive is truncated")))?;
+        offset += 46 + name_length + extra_length + comment_length;
+
+        let selected = match entry {
+            Some(entry) => name == entry || file_name(&name) == entry,
+            None => is_rom_name(&name),
+        };
+        if !selected {
+            continue;
+        }
+
+        if flags & 0x01 != 0 {
+            return Err(invalid(format!("{} is encrypted", name)));
+        }
+
+        if read_u32(data, local_offset)? != ZIP_LOCAL_HEADER {
+            return Err(invalid(format!("corrupted zip header for {}", name)));
+        }
+        let start = local_offset
+            + 30
+            + read_u16(data, local_offset + 26)? as usize
+            + read_u16(data, local_offset + 28)? as usize;
+        let compressed = data
+            .get(start..start + compressed_size)
+            .ok_or_else(|| invalid(String::from("archive is truncated")))?;
+
+        let rom = match method {
+            STORED => compressed.to_vec(),
+            // Anything past the declared size fails the check below anyway
+            DEFLATED => inflate(compressed, size.min(MAX_ROM_SIZE))
+                .map_err(|e| invalid(format!("{}: {}", name, e)))?,
+            _ => {
+                return Err(invalid(format!(
+                    "{} uses unsupported compression method {}",
+                    name, method
+                )))
+            }
+        };
+
+        if rom.len() != size || crc32(&rom) != crc {
+            return Err(invalid(format!("{} failed its CRC check", name)));
+        }
+
+        return Ok(Extracted {
+            name: file_name(&name).to_string(),
+            data: rom,
+        });
+    }
+
+    Err(invalid(match entry {
+        Some(entry) => format!("no {} in the archive", entry),
+        None => String::from("no .gb or .gbc file in the archive"),
+    }))
+}
+
+fn extract_gzip(data: &[u8], archive_name: &str) -> io::Result<Extracted> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if data.len() < 18 || data[2] != 8 {
+        return Err(invalid(String::from("unsupported gzip file")));
+    }
+
+    let flags = data[3];
+    let mut offset = 10;
+
+    if flags & FEXTRA != 0 {
+        offset += 2 + read_u16(data, offset)? as usize;
+    }
+
+    let truncated = || invalid(String::from("archive is truncated"));
+
+    let skip_string = |offset: &mut usize| -> io::Result<String> {
+        let end = data
+            .get(*offset..)
+            .ok_or_else(truncated)?
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(truncated)?;
+        let string = String::from_utf8_lossy(&data[*offset..*offset + end]).into_owned();
+        *offset += end + 1;
+        Ok(string)
+    };
+
+    let stored_name = if flags & FNAME != 0 {
+        Some(skip_string(&mut offset)?)
+    } else {
+        None
+    };
+    if flags & FCOMMENT != 0 {
+        skip_string(&mut offset)?;
+    }
+    if flags & FHCRC != 0 {
+        offset += 2;
+    }
+
+    // Every field above may claim more than there is
+    let trailer = data.len() - 8;
+    if offset > trailer {
+        return Err(truncated());
+    }
+    let compressed = &data[offset..trailer];
+    let rom = inflate(compressed, MAX_ROM_SIZE).map_err(|e| invalid(e.to_string()))?;
+
+    if crc32(&rom) != read_u32(data, trailer)? || rom.len() as u32 != read_u32(data, trailer + 4)? {
+        return Err(invalid(String::from("gzip data failed its CRC check")));
+    }
+
+    let name = match stored_name {
+        Some(name) => file_name(&name).to_string(),
+        None => archive_name
+            .strip_suffix(".gz")
+            .unwrap_or(archive_name)
+            .to_string(),
+    };
+
+    Ok(Extracted { name, data: rom })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::deflate;
+
+    const ROM: &[u8] = b"Not much of a ROM, but a ROM all the same";
+
+    fn zip(name: &str, method: u16) -> Vec<u8> {
+        let compressed = match method {
+            DEFLATED => deflate(ROM),
+            _ => ROM.to_vec(),
+        };
+        let u16 = |value: usize| (value as u16).to_le_bytes();
+        let u32 = |value: usize| (value as u32).to_le_bytes();
+
+        let mut local = ZIP_LOCAL_HEADER.to_le_bytes().to_vec();
+        local.extend([0; 4]);
+        local.extend(method.to_le_bytes());
+        local.extend([0; 4]);
+        local.extend(crc32(ROM).to_le_bytes());
+        local.extend(u32(compressed.len()));
+        local.extend(u32(ROM.len()));
+        local.extend(u16(name.len()));
+        local.extend(u16(0));
+        local.extend(name.as_bytes());
+        local.extend(&compressed);
+
+        let mut central = ZIP_CENTRAL_HEADER.to_le_bytes().to_vec();
+        central.extend([0; 6]);
+        central.extend(method.to_le_bytes());
+        central.extend([0; 4]);
+        central.extend(crc32(ROM).to_le_bytes());
+        central.extend(u32(compressed.len()));
+        central.extend(u32(ROM.len()));
+        central.extend(u16(name.len()));
+        central.extend([0; 12]);
+        central.extend(u32(0));
+        central.extend(name.as_bytes());
+
+        let mut end = ZIP_END_OF_DIRECTORY.to_le_bytes().to_vec();
+        end.extend([0; 4]);
+        end.extend(u16(1));
+        end.extend(u16(1));
+        end.extend(u32(central.len()));
+        end.extend(u32(local.len()));
+        end.extend(u16(0));
+
+        [local, central, end].concat()
+    }
+
+    fn gzip(flags: u8, fields: &[u8]) -> Vec<u8> {
+        let mut gzip = vec![0x1F, 0x8B, 8, flags, 0, 0, 0, 0, 0, 0xFF];
+        gzip.extend(fields);
+        gzip.extend(deflate(ROM));
+        gzip.extend(crc32(ROM).to_le_bytes());
+        gzip.extend((ROM.len() as u32).to_le_bytes());
+        gzip
+    }
+
+    #[test]
+    pub fn test_zip() {
+        for method in [STORED, DEFLATED] {
+            let extracted = extract(&zip("roms/game.gb", method), "game.zip", None)
+                .unwrap()
+                .unwrap();
+            assert_eq!("game.gb", extracted.name);
+            assert_eq!(ROM, extracted.data);
+        }
+
+        assert!(extract(&zip("readme.txt", STORED), "game.zip", None).is_err());
+        assert!(extract(b"Not an archive", "game.gb", None)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    pub fn test_gzip() {
+        let extracted = extract(&gzip(0x08, b"game.gbc\0"), "archive.gz", None)
+            .unwrap()
+            .unwrap();
+        assert_eq!("game.gbc", extracted.name);
+        assert_eq!(ROM, extracted.data);
+
+        let extracted = extract(&gzip(0, &[]), "game.gb.gz", None).unwrap().unwrap();
+        assert_eq!("game.gb", extracted.name);
+    }
+
+    #[test]
+    pub fn test_corrupted_headers() {
+        // Extra field longer than the file, then a name to look for past it
+        assert!(extract(&gzip(0x0C, &[0xFF, 0xFF]), "game.gb.gz", None).is_err());
+        // Name never ending
+        let mut unterminated = vec![0x1F, 0x8B, 8, 0x08, 0, 0, 0, 0, 0, 0xFF];
+        unterminated.extend(b"game.gb game.gb");
+        assert!(extract(&unterminated, "game.gb.gz", None).is_err());
+        // Header CRC past the end
+        let short = gzip(0x02, &[]);
+        assert!(extract(&short[..18], "game.gb.gz", None).is_err());
+
+        let zip = zip("game.gb", DEFLATED);
+        assert!(extract(&zip[..zip.len() - 10], "game.zip", None).is_err());
+        // Compressed data no longer matching the CRC
+        let mut corrupted = zip.clone();
+        corrupted[40] ^= 0xFF;
+        assert!(extract(&corrupted, "game.zip", None).is_err());
+
+        // Inflating stops at the declared size
+        let central = zip
+            .windows(4)
+            .position(|window| window == ZIP_CENTRAL_HEADER.to_le_bytes())
+            .unwrap();
+        let mut understated = zip.clone();
+        understated[central + 24..central + 28].copy_from_slice(&4u32.to_le_bytes());
+        let error = extract(&understated, "game.zip", None).err().unwrap();
+        assert!(error.to_string().contains("output too large"));
+    }
+}
@@ -1,48 +1,166 @@
-use std::{fs::File, io::Read};
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use super::address_space::AddressSpace;
+use super::archive;
+use super::mbc::{self, ImageSource, MapperKind, Mbc};
+use super::patch::{self, PatchError};
+
+#[derive(Default)]
+pub struct LoadOptions {
+    /// Mapper to use instead of the detected one
+    pub mapper: Option<MapperKind>,
+    /// Patch to apply, otherwise one next to the ROM is looked for
+    pub patch: Option<PathBuf>,
+    /// File to pick in a zip archive, otherwise the first `.gb`/`.gbc` one
+    pub entry: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Patch(PathBuf, PatchError),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Patch(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
 
 pub struct Cartridge {
-    data: Vec<u8>,
+    mbc: Box<dyn Mbc>,
+    rom_path: PathBuf,
 }
 
 impl AddressSpace for Cartridge {
     fn get(&self, address: u16) -> u8 {
-        self.data[address as usize]
+        self.mbc.get(address)
     }
 
     fn set(&mut self, address: u16, value: u8) {
-        self.data[address as usize] = value;
+        self.mbc.set(address, value);
     }
+}
 
-    fn get_word(&self, address: u16) -> u16 {
-        let h = (self.data[(address + 1) as usize] as u16) << 8;
-        let l = self.data[address as usize] as u16;
+impl Cartridge {
+    #[allow(dead_code)]
+    pub fn new(rom_path: String) -> Self {
+        Self::load(Path::new(&rom_path), &LoadOptions::default()).unwrap()
+    }
 
-        h | l
+    /// Cartridge for a ROM already in memory, with its detected mapper
+    #[cfg(test)]
+    pub fn from_rom(data: Vec<u8>) -> Self {
+        Self {
+            mbc: mbc::create(mbc::detect(&data), data),
+            rom_path: PathBuf::new(),
+        }
     }
 
-    fn set_word(&mut self, address: u16, value: u16) {
-        let val = value as u8;
-        let next_val = (value >> 8) as u8;
+    /// Reads the ROM, extracting it from an archive, patches it in memory
+    /// then creates its mapper
+    pub fn load(path: &Path, options: &LoadOptions) -> Result<Self, LoadError> {
+        let file = fs::read(path)?;
+        let archive_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        // A ROM from an archive is named as if it were next to the archive
+        let (rom_path, mut data) =
+            match archive::extract(&file, &archive_name, options.entry.as_deref())? {
+                Some(extracted) => {
+                    println!("Extracted {} from {}", extracted.name, archive_name);
+                    (path.with_file_name(&extracted.name), extracted.data)
+                }
+                None => (path.to_path_buf(), file),
+            };
 
-        self.data[address as usize] = val;
-        self.data[(address + 1) as usize] = next_val;
+        let patch_path = options
+            .patch
+            .clone()
+            .or_else(|| patch::find_sibling(&rom_path))
+            .or_else(|| patch::find_sibling(path));
+
+        if let Some(patch_path) = patch_path {
+            data = patch::apply_file(&data, &patch_path)
+                .map_err(|e| LoadError::Patch(patch_path.clone(), e))?;
+            println!("Applied patch {}", patch_path.display());
+        }
+
+        let mapper = options.mapper.unwrap_or_else(|| mbc::detect(&data));
+
+        Ok(Self {
+            mbc: mbc::create(mapper, data),
+            rom_path,
+        })
     }
-}
 
-impl Cartridge {
-    pub fn new(rom_path: String) -> Self {
-        let mut rom_file = File::open(rom_path).unwrap();
-        let mut data: Vec<u8> = Vec::new();
-        rom_file.read_to_end(&mut data).unwrap();
+    /// Path of the ROM, used to name the files stored next to it
+    ///
+    /// For archives this is the inner ROM's name in the archive's directory.
+    pub fn rom_path(&self) -> &Path {
+        &self.rom_path
+    }
 
-        Self { data }
+    /// ROM data as mapped, after extraction and patching
+    pub fn rom(&self) -> &[u8] {
+        self.mbc.rom()
     }
 
     pub fn get_rom_title(&self) -> &str {
-        std::str::from_utf8(&self.data[0x134..0x143])
+        std::str::from_utf8(&self.mbc.rom()[0x134..0x143])
             .unwrap()
             .trim_end()
     }
+
+    pub fn step(&mut self, cycles: u8) {
+        self.mbc.step(cycles);
+    }
+
+    /// Replaces the frames seen by the Pocket Camera sensor
+    pub fn set_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.mbc.set_image_source(source);
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        self.mbc.ram()
+    }
+
+    pub fn load_ram(&mut self, data: &[u8]) {
+        self.mbc.load_ram(data);
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.mbc.has_battery()
+    }
+
+    pub fn flash(&self) -> Option<Vec<u8>> {
+        self.mbc.flash()
+    }
+
+    pub fn load_flash(&mut self, data: &[u8]) {
+        self.mbc.load_flash(data);
+    }
+
+    pub fn take_flash_dirty(&mut self) -> bool {
+        self.mbc.take_flash_dirty()
+    }
+
+    pub fn take_reset_request(&mut self) -> bool {
+        self.mbc.take_reset_request()
+    }
 }
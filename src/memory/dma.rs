@@ -0,0 +1,270 @@
+// One byte is copied per M-cycle
+const OAM_DMA_LENGTH: u8 = 0xA0;
+
+// M-cycles between the write to FF46 and the first byte being copied
+const OAM_DMA_START_DELAY: u8 = 2;
+
+/// Bus the CPU shares with the DMA, depending on the address
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Bus {
+    External,
+    Video,
+}
+
+impl Bus {
+    /// OAM, I/O and HRAM aren't behind either bus
+    pub fn of(address: u16) -> Option<Self> {
+        match address {
+            0x8000..=0x9FFF => Some(Bus::Video),
+            0x0000..=0x7FFF | 0xA000..=0xFDFF => Some(Bus::External),
+            _ => None,
+        }
+    }
+}
+
+struct Transfer {
+    source: u16,
+    index: u8,
+}
+
+/// OAM DMA, started by writing the source page to FF46
+pub struct OamDma {
+    register: u8,
+    active: Option<Transfer>,
+    // Source and remaining delay of a transfer about to start
+    pending: Option<(u16, u8)>,
+    // Last byte copied, which is what the CPU sees on a conflicting bus
+    last_value: u8,
+}
+
+impl OamDma {
+    pub fn new() -> Self {
+        Self {
+            register: 0xFF,
+            active: None,
+            pending: None,
+            last_value: 0xFF,
+        }
+    }
+
+    /// Value of FF46
+    pub fn register(&self) -> u8 {
+        self.register
+    }
+
+    pub fn start(&mut self, value: u8) {
+        self.register = value;
+
+        // Above 0xDF00 the source wraps into WRAM like the echo area
+        let mut source = (value as u16) << 8;
+        if source >= 0xE000 {
+            source &= !0x2000;
+        }
+
+        // A running transfer keeps going until the new one starts
+        self.pending = Some((source, OAM_DMA_START_DELAY));
+    }
+
+    /// Whether OAM is being written, blocking the CPU out of it
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Bus the running transfer is reading from
+    pub fn bus(&self) -> Option<Bus> {
+        self.active
+            .as_ref()
+            .and_then(|transfer| Bus::of(transfer.source))
+    }
+
+    pub fn last_value(&self) -> u8 {
+        self.last_value
+    }
+
+    /// Advances one M-cycle, returning the source address and OAM index to copy
+    pub fn tick(&mut self) -> Option<(u16, usize)> {
+        if let Some((source, delay)) = &mut self.pending {
+            *delay -= 1;
+            if *delay == 0 {
+                self.active = Some(Transfer {
+                    source: *source,
+                    index: 0,
+                });
+                self.pending = None;
+            }
+        }
+
+        let transfer = self.active.as_mut()?;
+        let copy = (
+            transfer.source + transfer.index as u16,
+            transfer.index as usize,
+        );
+
+        transfer.index += 1;
+        if transfer.index == OAM_DMA_LENGTH {
+            self.active = None;
+        }
+
+        Some(copy)
+    }
+
+    /// Called with every byte copied
+    pub fn set_last_value(&mut self, value: u8) {
+        self.last_value = value;
+    }
+}
+
+/// Bytes copied per HDMA block
+pub const HDMA_BLOCK_SIZE: u16 = 0x10;
+
+// The CPU is stalled 8 M-cycles per block in normal speed
+pub const HDMA_BLOCK_CYCLES: u32 = 32;
+
+/// What a write to HDMA5 (FF55) asks for
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum HdmaRequest {
+    /// Copy that many blocks right away
+    General(u8),
+    /// Copy one block at the start of every HBlank
+    HBlank,
+    Cancel,
+}
+
+/// CGB VRAM DMA, configured through HDMA1-5 (FF51-FF55)
+pub struct Hdma {
+    source: u16,
+    destination: u16,
+    // Blocks left minus one, 0x7F once done
+    remaining: u8,
+    hblank_active: bool,
+}
+
+impl Hdma {
+    pub fn new() -> Self {
+        Self {
+            source: 0,
+            destination: 0x8000,
+            remaining: 0x7F,
+            hblank_active: false,
+        }
+    }
+
+    pub fn is_hblank_active(&self) -> bool {
+        self.hblank_active
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            // Bit 7 is clear while an HBlank transfer is pending
+            0xFF55 if self.hblank_active => self.remaining,
+            0xFF55 => 0x80 | self.remaining,
+            // The addresses are write only
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) -> Option<HdmaRequest> {
+        match address {
+            0xFF51 => self.source = (self.source & 0x00FF) | ((value as u16) << 8),
+            0xFF52 => self.source = (self.source & 0xFF00) | (value & 0xF0) as u16,
+            // The destination is always in VRAM
+            0xFF53 => {
+                self.destination =
+                    0x8000 | (self.destination & 0x00FF) | (((value & 0x1F) as u16) << 8)
+            }
+            0xFF54 => self.destination = (self.destination & 0xFF00) | (value & 0xF0) as u16,
+            0xFF55 => {
+                // Clearing bit 7 during an HBlank transfer stops it, keeping the length
+                if self.hblank_active && value & 0x80 == 0 {
+                    self.hblank_active = false;
+                    return Some(HdmaRequest::Cancel);
+                }
+
+                self.remaining = value & 0x7F;
+
+                if value & 0x80 != 0 {
+                    self.hblank_active = true;
+                    return Some(HdmaRequest::HBlank);
+                }
+
+                return Some(HdmaRequest::General(self.remaining + 1));
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    /// Source and destination of the next block, moving past it
+    pub fn next_block(&mut self) -> (u16, u16) {
+        let block = (self.source, self.destination);
+
+        self.source = self.source.wrapping_add(HDMA_BLOCK_SIZE);
+        self.destination = 0x8000 | (self.destination.wrapping_add(HDMA_BLOCK_SIZE) & 0x1FFF);
+
+        self.remaining = self.remaining.wrapping_sub(1) & 0x7F;
+        if self.remaining == 0x7F {
+            self.hblank_active = false;
+        }
+
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_oam_dma_timing() {
+        let mut dma = OamDma::new();
+        dma.start(0xC1);
+
+        // Start delay
+        assert_eq!(None, dma.tick());
+        assert!(!dma.is_active());
+
+        assert_eq!(Some((0xC100, 0)), dma.tick());
+        assert!(dma.is_active());
+        assert_eq!(Some(Bus::External), dma.bus());
+
+        for index in 1..0xA0 {
+            assert_eq!(Some((0xC100 + index, index as usize)), dma.tick());
+        }
+
+        // 160 M-cycles, 640 T-cycles
+        assert!(!dma.is_active());
+        assert_eq!(None, dma.tick());
+    }
+
+    #[test]
+    pub fn test_oam_dma_from_echo() {
+        let mut dma = OamDma::new();
+        dma.start(0xFE);
+
+        dma.tick();
+        assert_eq!(Some((0xDE00, 0)), dma.tick());
+        assert_eq!(0xFE, dma.register());
+    }
+
+    #[test]
+    pub fn test_hdma_remaining_length() {
+        let mut hdma = Hdma::new();
+        hdma.write(0xFF51, 0xC0);
+        hdma.write(0xFF53, 0x81);
+
+        assert_eq!(Some(HdmaRequest::HBlank), hdma.write(0xFF55, 0x82));
+        assert_eq!(0x02, hdma.read(0xFF55));
+
+        assert_eq!((0xC000, 0x8100), hdma.next_block());
+        assert_eq!(0x01, hdma.read(0xFF55));
+
+        // Stopped halfway, the length stays readable
+        assert_eq!(Some(HdmaRequest::Cancel), hdma.write(0xFF55, 0x00));
+        assert_eq!(0x81, hdma.read(0xFF55));
+
+        assert_eq!(Some(HdmaRequest::General(1)), hdma.write(0xFF55, 0x00));
+        assert_eq!((0xC010, 0x8110), hdma.next_block());
+        assert_eq!(0xFF, hdma.read(0xFF55));
+    }
+}
@@ -0,0 +1,277 @@
+use crate::boot_rom::GAMEBOY_CLASSIC;
+
+/// Logo the boot ROM compares against 0x0104-0x0133
+pub fn nintendo_logo() -> &'static [u8] {
+    &GAMEBOY_CLASSIC[0xA8..0xD8]
+}
+
+/// Cartridge header, found at 0x0100-0x014F of the first bank
+pub struct Header {
+    pub entry_point: [u8; 4],
+    pub logo: [u8; 0x30],
+    /// Raw 0x0134-0x0143, newer games reuse the end for the codes below
+    pub title: [u8; 0x10],
+    pub manufacturer_code: [u8; 4],
+    pub cgb_flag: u8,
+    pub new_licensee_code: [u8; 2],
+    pub sgb_flag: u8,
+    pub cartridge_type: u8,
+    pub rom_size: u8,
+    pub ram_size: u8,
+    pub destination_code: u8,
+    pub old_licensee_code: u8,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl Header {
+    pub fn parse(rom: &[u8]) -> Option<Self> {
+        let header = rom.get(0x100..0x150)?;
+
+        let mut entry_point = [0; 4];
+        entry_point.copy_from_slice(&header[0x00..0x04]);
+        let mut logo = [0; 0x30];
+        logo.copy_from_slice(&header[0x04..0x34]);
+        let mut title = [0; 0x10];
+        title.copy_from_slice(&header[0x34..0x44]);
+        let mut manufacturer_code = [0; 4];
+        manufacturer_code.copy_from_slice(&header[0x3F..0x43]);
+
+        Some(Self {
+            entry_point,
+            logo,
+            title,
+            manufacturer_code,
+            cgb_flag: header[0x43],
+            new_licensee_code: [header[0x44], header[0x45]],
+            sgb_flag: header[0x46],
+            cartridge_type: header[0x47],
+            rom_size: header[0x48],
+            ram_size: header[0x49],
+            destination_code: header[0x4A],
+            old_licensee_code: header[0x4B],
+            version: header[0x4C],
+            header_checksum: header[0x4D],
+            global_checksum: ((header[0x4E] as u16) << 8) | header[0x4F] as u16,
+        })
+    }
+
+    pub fn is_logo_valid(&self) -> bool {
+        self.logo == nintendo_logo()
+    }
+
+    /// Whether 0x0143 is the CGB flag rather than the last title character
+    pub fn is_cgb_aware(&self) -> bool {
+        self.cgb_flag & 0x80 != 0
+    }
+
+    /// Title as text, without the CGB flag and the padding
+    pub fn title(&self) -> String {
+        let title = if self.is_cgb_aware() {
+            &self.title[..0x0F]
+        } else {
+            &self.title[..]
+        };
+
+        title
+            .iter()
+            .take_while(|byte| **byte != 0)
+            .map(|byte| *byte as char)
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    /// ROM size in bytes from the header code (0x0148)
+    pub fn rom_size_bytes(&self) -> Option<usize> {
+        match self.rom_size {
+            0x00..=0x08 => Some(0x8000 << self.rom_size),
+            _ => None,
+        }
+    }
+
+    /// External RAM size in bytes from the header code (0x0149)
+    pub fn ram_size_bytes(&self) -> Option<usize> {
+        match self.ram_size {
+            0x00 => Some(0),
+            0x01 => Some(0x800),
+            0x02 => Some(0x2000),
+            0x03 => Some(0x8000),
+            0x04 => Some(0x20000),
+            0x05 => Some(0x10000),
+            _ => None,
+        }
+    }
+
+    /// Licensee, the new code only being used when the old one is 0x33
+    pub fn licensee(&self) -> String {
+        if self.old_licensee_code == 0x33 {
+            String::from_utf8_lossy(&self.new_licensee_code).into_owned()
+        } else {
+            format!("{:02X}", self.old_licensee_code)
+        }
+    }
+}
+
+/// Name of a cartridge type (0x0147)
+pub fn cartridge_type_name(cartridge_type: u8) -> &'static str {
+    match cartridge_type {
+        0x00 => "ROM ONLY",
+        0x01 => "MBC1",
+        0x02 => "MBC1+RAM",
+        0x03 => "MBC1+RAM+BATTERY",
+        0x05 => "MBC2",
+        0x06 => "MBC2+BATTERY",
+        0x08 => "ROM+RAM",
+        0x09 => "ROM+RAM+BATTERY",
+        0x0B => "MMM01",
+        0x0C => "MMM01+RAM",
+        0x0D => "MMM01+RAM+BATTERY",
+        0x0F => "MBC3+TIMER+BATTERY",
+        0x10 => "MBC3+TIMER+RAM+BATTERY",
+        0x11 => "MBC3",
+        0x12 => "MBC3+RAM",
+        0x13 => "MBC3+RAM+BATTERY",
+        0x19 => "MBC5",
+        0x1A => "MBC5+RAM",
+        0x1B => "MBC5+RAM+BATTERY",
+        0x1C => "MBC5+RUMBLE",
+        0x1D => "MBC5+RUMBLE+RAM",
+        0x1E => "MBC5+RUMBLE+RAM+BATTERY",
+        0x20 => "MBC6",
+        0x22 => "MBC7+SENSOR+RUMBLE+RAM+BATTERY",
+        0xFC => "POCKET CAMERA",
+        0xFD => "BANDAI TAMA5",
+        0xFE => "HuC3",
+        0xFF => "HuC1+RAM+BATTERY",
+        _ => "unknown",
+    }
+}
+
+/// Checksum over 0x0134-0x014C, as verified by the boot ROM
+pub fn compute_header_checksum(rom: &[u8]) -> u8 {
+    rom[0x134..0x14D].iter().fold(0u8, |checksum, byte| {
+        checksum.wrapping_sub(*byte).wrapping_sub(1)
+    })
+}
+
+/// Sum of every byte of the ROM but the global checksum itself (0x014E-0x014F)
+pub fn compute_global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(address, _)| *address != 0x14E && *address != 0x14F)
+        .fold(0u16, |checksum, (_, byte)| {
+            checksum.wrapping_add(*byte as u16)
+        })
+}
+
+/// Header fields to rewrite, the same set as rgbfix offers
+#[derive(Default)]
+pub struct HeaderFix {
+    pub logo: bool,
+    pub header_checksum: bool,
+    pub global_checksum: bool,
+    pub title: Option<String>,
+    pub cartridge_type: Option<u8>,
+    pub rom_size: Option<u8>,
+    pub ram_size: Option<u8>,
+}
+
+impl HeaderFix {
+    pub fn is_empty(&self) -> bool {
+        !self.logo
+            && !self.header_checksum
+            && !self.global_checksum
+            && self.title.is_none()
+            && self.cartridge_type.is_none()
+            && self.rom_size.is_none()
+            && self.ram_size.is_none()
+    }
+
+    /// Rewrites the header, checksums last so they cover the other changes
+    pub fn apply(&self, rom: &mut [u8]) -> Result<(), String> {
+        if rom.len() < 0x150 {
+            return Err(format!(
+                "file too short to have a header ({} bytes)",
+                rom.len()
+            ));
+        }
+
+        if self.logo {
+            rom[0x104..0x134].copy_from_slice(nintendo_logo());
+        }
+
+        if let Some(title) = &self.title {
+            // Like rgbfix, the title is padded with zeros and leaves the CGB flag alone
+            let length = if rom[0x143] & 0x80 != 0 { 0x0F } else { 0x10 };
+            let field = &mut rom[0x134..0x134 + length];
+            field.fill(0);
+
+            for (byte, character) in field.iter_mut().zip(title.bytes()) {
+                *byte = character;
+            }
+        }
+
+        if let Some(cartridge_type) = self.cartridge_type {
+            rom[0x147] = cartridge_type;
+        }
+        if let Some(rom_size) = self.rom_size {
+            rom[0x148] = rom_size;
+        }
+        if let Some(ram_size) = self.ram_size {
+            rom[0x149] = ram_size;
+        }
+
+        if self.header_checksum {
+            rom[0x14D] = compute_header_checksum(rom);
+        }
+
+        if self.global_checksum {
+            let checksum = compute_global_checksum(rom);
+            rom[0x14E] = (checksum >> 8) as u8;
+            rom[0x14F] = checksum as u8;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_fix() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x143] = 0x80;
+
+        let fix = HeaderFix {
+            logo: true,
+            header_checksum: true,
+            global_checksum: true,
+            title: Some(String::from("HOMEBREW")),
+            ..Default::default()
+        };
+        fix.apply(&mut rom).unwrap();
+
+        let header = Header::parse(&rom).unwrap();
+        assert!(header.is_logo_valid());
+        assert_eq!("HOMEBREW", header.title());
+        assert!(header.is_cgb_aware());
+        assert_eq!(compute_header_checksum(&rom), header.header_checksum);
+        assert_eq!(compute_global_checksum(&rom), header.global_checksum);
+    }
+
+    #[test]
+    pub fn test_fix_short_file() {
+        let mut rom = vec![0; 0x14F];
+        let fix = HeaderFix {
+            header_checksum: true,
+            ..Default::default()
+        };
+
+        assert!(fix.apply(&mut rom).is_err());
+        assert!(rom.iter().all(|byte| *byte == 0));
+    }
+}
@@ -0,0 +1,139 @@
+use std::{fmt::Display, str::FromStr};
+
+/// One of the eight buttons, in the order of their P1 lines
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+const NAMES: [(Button, &str); 8] = [
+    (Button::Right, "right"),
+    (Button::Left, "left"),
+    (Button::Up, "up"),
+    (Button::Down, "down"),
+    (Button::A, "a"),
+    (Button::B, "b"),
+    (Button::Select, "select"),
+    (Button::Start, "start"),
+];
+
+impl Button {
+    /// Bit in the pressed buttons, directions in the low nibble
+    fn mask(self) -> u8 {
+        1 << (self as u8)
+    }
+}
+
+impl FromStr for Button {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NAMES
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(s))
+            .map(|(button, _)| *button)
+            .ok_or_else(|| {
+                let names: Vec<&str> = NAMES.iter().map(|(_, name)| *name).collect();
+                format!(
+                    "Unknown button {}, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl Display for Button {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (_, name) = NAMES.iter().find(|(button, _)| button == self).unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+/// P1 (FF00), where bits 4 and 5 select the directions or the other buttons
+/// and bits 0-3 read 0 for the pressed ones
+pub struct Joypad {
+    pressed: u8,
+    select: u8,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self {
+            pressed: 0,
+            select: 0x30,
+        }
+    }
+
+    /// Low nibble of P1, with both groups ANDed when both are selected
+    fn lines(&self) -> u8 {
+        let mut lines = 0x0F;
+        if self.select & 0x10 == 0 {
+            lines &= !self.pressed & 0x0F;
+        }
+        if self.select & 0x20 == 0 {
+            lines &= !(self.pressed >> 4) & 0x0F;
+        }
+        lines
+    }
+
+    pub fn read(&self) -> u8 {
+        0xC0 | self.select | self.lines()
+    }
+
+    /// Returns whether the joypad interrupt is requested
+    pub fn write(&mut self, value: u8) -> bool {
+        let before = self.lines();
+        self.select = value & 0x30;
+        self.falling_edge(before)
+    }
+
+    /// Returns whether the joypad interrupt is requested
+    pub fn set_pressed(&mut self, button: Button, pressed: bool) -> bool {
+        let before = self.lines();
+        if pressed {
+            self.pressed |= button.mask();
+        } else {
+            self.pressed &= !button.mask();
+        }
+        self.falling_edge(before)
+    }
+
+    // The interrupt fires when a selected line goes from high to low
+    fn falling_edge(&self, before: u8) -> bool {
+        before & !self.lines() != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_joypad() {
+        let mut joypad = Joypad::new();
+        assert_eq!(0xFF, joypad.read());
+
+        // Nothing selected, no interrupt
+        assert!(!joypad.set_pressed(Button::Start, true));
+        assert_eq!(0xFF, joypad.read());
+
+        // Selecting the buttons shows Start, and counts as an edge
+        assert!(joypad.write(0x10));
+        assert_eq!(0xD7, joypad.read());
+        assert!(!joypad.set_pressed(Button::Up, true));
+        assert!(joypad.set_pressed(Button::A, true));
+        assert_eq!(0xD6, joypad.read());
+
+        joypad.write(0x20);
+        assert_eq!(0xEB, joypad.read());
+        assert_eq!(Ok(Button::Select), "Select".parse());
+    }
+}
@@ -0,0 +1,181 @@
+use crate::memory::address_space::AddressSpace;
+
+use super::{mbc5::Mbc5, Mbc};
+
+type Reordering = [[u8; 8]; 8];
+
+// For each mode, the source bit of every destination bit
+const BBD_DATA: Reordering = [
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 5, 1, 3, 4, 2, 6, 7],
+    [0, 4, 2, 3, 1, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 5, 3, 4, 2, 6, 7],
+];
+
+const BBD_BANK: Reordering = [
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [3, 4, 2, 0, 1, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [1, 2, 3, 4, 0, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 1, 2, 3, 4, 5, 6, 7],
+];
+
+const HITEK_DATA: Reordering = [
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [0, 6, 5, 3, 4, 1, 2, 7],
+    [0, 5, 6, 3, 4, 2, 1, 7],
+    [0, 6, 2, 3, 4, 5, 1, 7],
+    [0, 6, 1, 3, 4, 5, 2, 7],
+    [0, 1, 6, 3, 4, 5, 2, 7],
+    [0, 2, 6, 3, 4, 1, 5, 7],
+    [0, 6, 2, 3, 4, 1, 5, 7],
+];
+
+const HITEK_BANK: Reordering = [
+    [0, 1, 2, 3, 4, 5, 6, 7],
+    [3, 2, 1, 0, 4, 5, 6, 7],
+    [2, 1, 0, 3, 4, 5, 6, 7],
+    [1, 0, 3, 2, 4, 5, 6, 7],
+    [0, 3, 2, 1, 4, 5, 6, 7],
+    [2, 3, 0, 1, 4, 5, 6, 7],
+    [3, 0, 1, 2, 4, 5, 6, 7],
+    [2, 0, 3, 1, 4, 5, 6, 7],
+];
+
+/// BBD and Hitek boards: an MBC5 with scrambled data and bank number lines
+///
+/// 0x2001 selects how the data bits of the switchable bank are swapped,
+/// 0x2080 how the bits of the bank number written to 0x2000 are.
+pub struct Bbd {
+    mbc5: Mbc5,
+    data_reordering: &'static Reordering,
+    bank_reordering: &'static Reordering,
+    data_mode: usize,
+    bank_mode: usize,
+}
+
+impl Bbd {
+    pub fn standard(rom: Vec<u8>) -> Self {
+        Self::new(rom, &BBD_DATA, &BBD_BANK)
+    }
+
+    pub fn hitek(rom: Vec<u8>) -> Self {
+        Self::new(rom, &HITEK_DATA, &HITEK_BANK)
+    }
+
+    fn new(rom: Vec<u8>, data: &'static Reordering, bank: &'static Reordering) -> Self {
+        Self {
+            mbc5: Mbc5::new(rom),
+            data_reordering: data,
+            bank_reordering: bank,
+            data_mode: 0,
+            bank_mode: 0,
+        }
+    }
+}
+
+fn reorder(value: u8, reordering: &[u8; 8]) -> u8 {
+    reordering
+        .iter()
+        .enumerate()
+        .fold(0, |result, (bit, source)| {
+            result | (((value >> source) & 1) << bit)
+        })
+}
+
+impl AddressSpace for Bbd {
+    fn get(&self, address: u16) -> u8 {
+        let value = self.mbc5.get(address);
+
+        match address {
+            0x4000..=0x7FFF => reorder(value, &self.data_reordering[self.data_mode]),
+            _ => value,
+        }
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        let mut value = value;
+
+        match address & 0xF0FF {
+            0x2000 => value = reorder(value, &self.bank_reordering[self.bank_mode]),
+            0x2001 => self.data_mode = (value & 0x07) as usize,
+            0x2080 => self.bank_mode = (value & 0x07) as usize,
+            _ => {}
+        }
+
+        // The MBC5 still sees every write, including the mode ones
+        self.mbc5.set(address, value);
+    }
+}
+
+impl Mbc for Bbd {
+    fn rom(&self) -> &[u8] {
+        self.mbc5.rom()
+    }
+
+    fn ram(&self) -> &[u8] {
+        self.mbc5.ram()
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        self.mbc5.load_ram(data);
+    }
+
+    fn has_battery(&self) -> bool {
+        self.mbc5.has_battery()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom() -> Vec<u8> {
+        // 512 KiB, every bank starting with its own number then 0x02
+        let mut rom = vec![0; 0x80000];
+        for (bank, chunk) in rom.chunks_mut(0x4000).enumerate() {
+            chunk[0] = bank as u8;
+            chunk[1] = 0x02;
+        }
+        rom
+    }
+
+    #[test]
+    pub fn test_bbd() {
+        let mut bbd = Bbd::standard(rom());
+
+        // Bank bit 0 goes to bit 4
+        bbd.set(0x2080, 0x05);
+        bbd.set(0x2000, 0x01);
+        assert_eq!(0x10, bbd.get(0x4000));
+
+        // Data bit 1 goes to bit 2, in the switchable bank only
+        bbd.set(0x2001, 0x04);
+        bbd.set(0x2080, 0x00);
+        bbd.set(0x2000, 0x01);
+        assert_eq!(0x04, bbd.get(0x4001));
+        assert_eq!(0x02, bbd.get(0x0001));
+    }
+
+    #[test]
+    pub fn test_hitek() {
+        let mut hitek = Bbd::hitek(rom());
+
+        // The low 4 bank bits are reversed
+        hitek.set(0x2080, 0x01);
+        hitek.set(0x2000, 0x01);
+        assert_eq!(0x08, hitek.get(0x4000));
+
+        hitek.set(0x2001, 0x01);
+        hitek.set(0x2080, 0x00);
+        hitek.set(0x2000, 0x01);
+        assert_eq!(0x20, hitek.get(0x4001));
+    }
+}
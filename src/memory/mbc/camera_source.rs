@@ -0,0 +1,264 @@
+use std::{fs, io, path::PathBuf};
+
+pub const SENSOR_WIDTH: usize = 128;
+pub const SENSOR_HEIGHT: usize = 112;
+
+// Plenty for a photo to be scaled down, anything larger is more likely a broken file
+const MAX_DIMENSION: usize = 4096;
+
+/// Provides the frames seen by the Pocket Camera sensor
+pub trait ImageSource {
+    /// Returns a 128x112 greyscale frame, one byte per pixel, 0 being black
+    fn capture(&mut self) -> Vec<u8>;
+}
+
+/// Generated pattern, moving by one pixel every capture
+pub struct TestPattern {
+    frame: usize,
+}
+
+impl TestPattern {
+    pub fn new() -> Self {
+        Self { frame: 0 }
+    }
+}
+
+impl Default for TestPattern {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImageSource for TestPattern {
+    fn capture(&mut self) -> Vec<u8> {
+        let mut pixels = vec![0; SENSOR_WIDTH * SENSOR_HEIGHT];
+
+        for y in 0..SENSOR_HEIGHT {
+            for x in 0..SENSOR_WIDTH {
+                // Horizontal gradient with a checkerboard in the middle band
+                let gradient = ((x + self.frame) % SENSOR_WIDTH) * 2;
+                let value = if (40..72).contains(&y) {
+                    if ((x + self.frame) / 8 + y / 8).is_multiple_of(2) {
+                        0xFF
+                    } else {
+                        0x00
+                    }
+                } else {
+                    gradient
+                };
+
+                pixels[y * SENSOR_WIDTH + x] = value as u8;
+            }
+        }
+
+        self.frame = self.frame.wrapping_add(1);
+        pixels
+    }
+}
+
+/// PGM/PPM file, read again on every capture so it can be updated from outside
+pub struct PnmImage {
+    path: PathBuf,
+    last_frame: Vec<u8>,
+    // The last read failed, so the error isn't repeated on every capture
+    failing: bool,
+}
+
+impl PnmImage {
+    pub fn new(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let last_frame = load_pnm(&path)?;
+
+        Ok(Self {
+            path,
+            last_frame,
+            failing: false,
+        })
+    }
+}
+
+impl ImageSource for PnmImage {
+    fn capture(&mut self) -> Vec<u8> {
+        // The previous frame is kept until the file can be read again
+        match load_pnm(&self.path) {
+            Ok(frame) => {
+                self.last_frame = frame;
+                self.failing = false;
+            }
+            Err(e) => {
+                if !self.failing {
+                    println!("Could not read {}: {}", self.path.display(), e);
+                }
+                self.failing = true;
+            }
+        }
+
+        self.last_frame.clone()
+    }
+}
+
+fn load_pnm(path: &PathBuf) -> io::Result<Vec<u8>> {
+    let data = fs::read(path)?;
+    let (width, height, grey) = parse_pnm(&data)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a valid PGM/PPM file"))?;
+
+    Ok(resize(&grey, width, height))
+}
+
+/// Decodes P2, P3, P5 and P6 files into greyscale pixels
+pub fn parse_pnm(data: &[u8]) -> Option<(usize, usize, Vec<u8>)> {
+    let mut pos = 0;
+    let magic = next_token(data, &mut pos)?;
+
+    let (channels, binary) = match magic {
+        b"P2" => (1, false),
+        b"P5" => (1, true),
+        b"P3" => (3, false),
+        b"P6" => (3, true),
+        _ => return None,
+    };
+
+    let width = parse_number(next_token(data, &mut pos)?)?;
+    let height = parse_number(next_token(data, &mut pos)?)?;
+    let max_value = parse_number(next_token(data, &mut pos)?)?;
+
+    if !(1..=MAX_DIMENSION).contains(&width)
+        || !(1..=MAX_DIMENSION).contains(&height)
+        || max_value == 0
+        || max_value > 0xFFFF
+    {
+        return None;
+    }
+
+    let sample_count = width.checked_mul(height)?.checked_mul(channels)?;
+    let mut samples;
+
+    if binary {
+        // A single whitespace separates the header from the raster
+        pos += 1;
+        let wide = max_value > 0xFF;
+        let sample_size = if wide { 2 } else { 1 };
+        let end = pos.checked_add(sample_count.checked_mul(sample_size)?)?;
+        let raster = data.get(pos..end)?;
+
+        samples = Vec::with_capacity(sample_count);
+        for sample in raster.chunks(sample_size) {
+            let value = if wide {
+                ((sample[0] as usize) << 8) | sample[1] as usize
+            } else {
+                sample[0] as usize
+            };
+            samples.push(value);
+        }
+    } else {
+        // Each sample takes at least a digit and a separator
+        if sample_count > data.len().saturating_sub(pos) / 2 + 1 {
+            return None;
+        }
+
+        samples = Vec::with_capacity(sample_count);
+        for _ in 0..sample_count {
+            samples.push(parse_number(next_token(data, &mut pos)?)?);
+        }
+    }
+
+    let grey = samples
+        .chunks(channels)
+        .map(|pixel| {
+            let value = if channels == 3 {
+                // ITU-R BT.601 luma
+                (pixel[0] * 299 + pixel[1] * 587 + pixel[2] * 114) / 1000
+            } else {
+                pixel[0]
+            };
+            (value.min(max_value) * 0xFF / max_value) as u8
+        })
+        .collect();
+
+    Some((width, height, grey))
+}
+
+fn next_token<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    loop {
+        match data.get(*pos)? {
+            b'#' => {
+                while *data.get(*pos)? != b'\n' {
+                    *pos += 1;
+                }
+            }
+            c if c.is_ascii_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+
+    let start = *pos;
+    while data.get(*pos).is_some_and(|c| !c.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+
+    Some(&data[start..*pos])
+}
+
+fn parse_number(token: &[u8]) -> Option<usize> {
+    std::str::from_utf8(token).ok()?.parse().ok()
+}
+
+/// Nearest-neighbour resize to the sensor resolution
+fn resize(pixels: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut frame = vec![0; SENSOR_WIDTH * SENSOR_HEIGHT];
+
+    for y in 0..SENSOR_HEIGHT {
+        for x in 0..SENSOR_WIDTH {
+            let src_x = x * width / SENSOR_WIDTH;
+            let src_y = y * height / SENSOR_HEIGHT;
+            frame[y * SENSOR_WIDTH + x] = pixels[src_y * width + src_x];
+        }
+    }
+
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_ascii() {
+        let pgm = b"P2\n# A comment\n2 1 # Another\n15\n0 15\n";
+        assert_eq!(Some((2, 1, vec![0x00, 0xFF])), parse_pnm(pgm));
+
+        // Pure red, green and blue, as luma
+        let ppm = b"P3 3 1 255 255 0 0 0 255 0 0 0 255";
+        assert_eq!(Some((3, 1, vec![76, 149, 29])), parse_pnm(ppm));
+    }
+
+    #[test]
+    pub fn test_binary() {
+        let mut pgm = b"P5 2 1 255\n".to_vec();
+        pgm.extend([0x12, 0x34]);
+        assert_eq!(Some((2, 1, vec![0x12, 0x34])), parse_pnm(&pgm));
+
+        let mut ppm = b"P6 1 1 255\n".to_vec();
+        ppm.extend([0xFF, 0xFF, 0xFF]);
+        assert_eq!(Some((1, 1, vec![0xFF])), parse_pnm(&ppm));
+
+        // Two bytes per sample past a maximum of 255, big endian
+        let mut wide = b"P5 2 1 65535\n".to_vec();
+        wide.extend([0xFF, 0xFF, 0x80, 0x00]);
+        assert_eq!(Some((2, 1, vec![0xFF, 0x7F])), parse_pnm(&wide));
+    }
+
+    #[test]
+    pub fn test_invalid() {
+        assert_eq!(None, parse_pnm(b"P5 2 2 255\n\x00\x00"));
+        assert_eq!(None, parse_pnm(b"P2 2 2 255 0 0 0"));
+        assert_eq!(None, parse_pnm(b"P4 1 1\n\x00"));
+        assert_eq!(None, parse_pnm(b"P2 0 1 255"));
+
+        // Huge dimensions don't get allocated, or overflow
+        assert_eq!(None, parse_pnm(b"P6 100000 100000 255\n\x00"));
+        let huge = format!("P6 {} {} 255\n", usize::MAX, usize::MAX);
+        assert_eq!(None, parse_pnm(huge.as_bytes()));
+        assert_eq!(None, parse_pnm(b"P2 4096 4096 255 0"));
+    }
+}
@@ -0,0 +1,221 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::memory::header::{compute_header_checksum, nintendo_logo, Header};
+
+use super::{gb_memory::GbMemory, sachen::unscramble};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MapperKind {
+    RomOnly,
+    Mbc5,
+    Mmm01,
+    GbMemory,
+    PocketCamera,
+    WisdomTree,
+    SachenMmc1,
+    SachenMmc2,
+    Bbd,
+    Hitek,
+}
+
+const NAMES: [(MapperKind, &str); 10] = [
+    (MapperKind::RomOnly, "rom-only"),
+    (MapperKind::Mbc5, "mbc5"),
+    (MapperKind::Mmm01, "mmm01"),
+    (MapperKind::GbMemory, "gb-memory"),
+    (MapperKind::PocketCamera, "pocket-camera"),
+    (MapperKind::WisdomTree, "wisdom-tree"),
+    (MapperKind::SachenMmc1, "sachen-mmc1"),
+    (MapperKind::SachenMmc2, "sachen-mmc2"),
+    (MapperKind::Bbd, "bbd"),
+    (MapperKind::Hitek, "hitek"),
+];
+
+impl FromStr for MapperKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NAMES
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(kind, _)| *kind)
+            .ok_or_else(|| {
+                let names: Vec<&str> = NAMES.iter().map(|(_, name)| *name).collect();
+                format!(
+                    "Unknown mapper {}, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl Display for MapperKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = NAMES.iter().find(|(kind, _)| kind == self).unwrap().1;
+        write!(f, "{}", name)
+    }
+}
+
+/// Picks the mapper of a ROM
+///
+/// The declared cartridge type is trusted unless the header looks wrong: bad
+/// logo or header checksum, or a size that doesn't match the declared type.
+/// Unlicensed boards are then recognised by their signatures, and as a last
+/// resort the ROM size decides.
+pub fn detect(rom: &[u8]) -> MapperKind {
+    if GbMemory::is_gb_memory(rom) {
+        return MapperKind::GbMemory;
+    }
+
+    let declared = declared(rom);
+
+    if !is_suspicious(rom, declared) {
+        if let Some(kind) = declared {
+            return kind;
+        }
+    }
+
+    if let Some(kind) = signature(rom) {
+        println!("Header looks wrong, detected an unlicensed {} board", kind);
+        return kind;
+    }
+
+    declared.unwrap_or_else(|| {
+        let kind = if rom.len() > 0x8000 {
+            MapperKind::Mbc5
+        } else {
+            MapperKind::RomOnly
+        };
+
+        // TODO Implement the other mappers
+        println!(
+            "Unsupported cartridge type {:#04X}, falling back to {}",
+            rom.get(0x147).copied().unwrap_or(0x00),
+            kind
+        );
+        kind
+    })
+}
+
+/// Mapper from the cartridge type (0x0147), if it is supported
+fn declared(rom: &[u8]) -> Option<MapperKind> {
+    let cartridge_type = rom.get(0x147).copied().unwrap_or(0x00);
+
+    // MMM01 dumps start with the first game, the menu header is in the last bank
+    let menu_header = rom.len().saturating_sub(0x8000) + 0x147;
+    let menu_type = rom.get(menu_header).copied().unwrap_or(0x00);
+
+    match cartridge_type {
+        0x00 if (0x0B..=0x0D).contains(&menu_type) => Some(MapperKind::Mmm01),
+        0x00 => Some(MapperKind::RomOnly),
+        0x0B..=0x0D => Some(MapperKind::Mmm01),
+        0x19..=0x1E => Some(MapperKind::Mbc5),
+        0xFC => Some(MapperKind::PocketCamera),
+        _ => None,
+    }
+}
+
+fn is_suspicious(rom: &[u8], declared: Option<MapperKind>) -> bool {
+    let header = match Header::parse(rom) {
+        Some(header) => header,
+        None => return false,
+    };
+
+    let size_mismatch = match header.rom_size_bytes() {
+        Some(size) => size != rom.len(),
+        None => true,
+    };
+
+    !header.is_logo_valid()
+        || compute_header_checksum(rom) != header.header_checksum
+        || size_mismatch
+        || (declared == Some(MapperKind::RomOnly) && rom.len() > 0x8000)
+}
+
+fn signature(rom: &[u8]) -> Option<MapperKind> {
+    let bank0 = &rom[..rom.len().min(0x4000)];
+
+    // Sachen hides the real logo behind its address scrambling
+    let sachen_logo = (0..0x30u16).all(|i| {
+        let address = unscramble((0x0104 + i) | 0x80) as usize;
+        rom.get(address) == Some(&nintendo_logo()[i as usize])
+    });
+    if sachen_logo {
+        let cgb_flag = rom.get(unscramble(0x0143) as usize).copied().unwrap_or(0);
+        return Some(if cgb_flag & 0x80 != 0 {
+            MapperKind::SachenMmc2
+        } else {
+            MapperKind::SachenMmc1
+        });
+    }
+
+    if contains(bank0, b"WISDOM TREE") || contains(bank0, b"WISDOM\0TREE") {
+        return Some(MapperKind::WisdomTree);
+    }
+
+    // ld (0x2001), a and ld (0x2080), a set the scrambling modes
+    if contains(rom, &[0xEA, 0x01, 0x20]) && contains(rom, &[0xEA, 0x80, 0x20]) {
+        if contains(rom, b"HITEK") || contains(rom, b"Hitek") {
+            return Some(MapperKind::Hitek);
+        }
+        return Some(MapperKind::Bbd);
+    }
+
+    None
+}
+
+fn contains(data: &[u8], needle: &[u8]) -> bool {
+    data.windows(needle.len()).any(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(size: usize, cartridge_type: u8) -> Vec<u8> {
+        let mut rom = vec![0; size];
+        rom[0x104..0x134].copy_from_slice(nintendo_logo());
+        rom[0x147] = cartridge_type;
+        rom[0x148] = (size / 0x8000).trailing_zeros() as u8;
+        rom[0x14D] = compute_header_checksum(&rom);
+        rom
+    }
+
+    #[test]
+    pub fn test_declared_type_is_trusted() {
+        assert_eq!(MapperKind::Mbc5, detect(&rom(0x20000, 0x19)));
+        assert_eq!(MapperKind::RomOnly, detect(&rom(0x8000, 0x00)));
+    }
+
+    #[test]
+    pub fn test_mmm01_menu_header() {
+        let mut mmm01 = rom(0x20000, 0x00);
+        mmm01[0x20000 - 0x8000 + 0x147] = 0x0B;
+        assert_eq!(MapperKind::Mmm01, detect(&mmm01));
+
+        // Only a ROM declaring no mapper is looked at, the byte may be code
+        let mut mbc5 = rom(0x20000, 0x19);
+        mbc5[0x20000 - 0x8000 + 0x147] = 0x0B;
+        assert_eq!(MapperKind::Mbc5, detect(&mbc5));
+    }
+
+    #[test]
+    pub fn test_wisdom_tree() {
+        let mut rom = rom(0x40000, 0x00);
+        rom[0x150..0x15B].copy_from_slice(b"WISDOM TREE");
+
+        assert_eq!(MapperKind::WisdomTree, detect(&rom));
+    }
+
+    #[test]
+    pub fn test_sachen_logo() {
+        let mut rom = vec![0; 0x20000];
+        for i in 0..0x30u16 {
+            let address = unscramble((0x0104 + i) | 0x80) as usize;
+            rom[address] = nintendo_logo()[i as usize];
+        }
+
+        assert_eq!(MapperKind::SachenMmc1, detect(&rom));
+    }
+}
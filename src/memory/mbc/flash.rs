@@ -0,0 +1,211 @@
+// AMD-style command set, as found on the MX29F008 of the GB Memory cartridge
+const MANUFACTURER_ID: u8 = 0xC2;
+const DEVICE_ID: u8 = 0x81;
+const SECTOR_SIZE: usize = 0x10000;
+// Extra sector outside of the array, where the GB Memory keeps its mapping
+pub const HIDDEN_SIZE: usize = 0x80;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum State {
+    Read,
+    Unlock1,
+    Unlock2,
+    Program,
+    EraseSetup,
+    EraseUnlock1,
+    EraseUnlock2,
+    Autoselect,
+    HiddenRead,
+    HiddenSetup,
+    HiddenUnlock1,
+    HiddenUnlock2,
+    HiddenProgram,
+}
+
+/// Flash array with a small hidden sector
+///
+/// After the unlock cycles, 0x77 makes reads return the hidden sector until a
+/// reset, and 0x60 followed by a second unlock selects what to do with it:
+/// 0x04 erases it, 0xE0 programs the byte written next.
+pub struct Flash {
+    data: Vec<u8>,
+    hidden: [u8; HIDDEN_SIZE],
+    state: State,
+    dirty: bool,
+}
+
+impl Flash {
+    pub fn new(mut data: Vec<u8>, size: usize) -> Self {
+        // Erased flash reads as 0xFF
+        data.resize(size, 0xFF);
+
+        Self {
+            data,
+            hidden: [0xFF; HIDDEN_SIZE],
+            state: State::Read,
+            dirty: false,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn load(&mut self, data: &[u8]) {
+        let len = data.len().min(self.data.len());
+        self.data[..len].copy_from_slice(&data[..len]);
+    }
+
+    pub fn hidden(&self) -> &[u8] {
+        &self.hidden
+    }
+
+    pub fn load_hidden(&mut self, data: &[u8]) {
+        let len = data.len().min(HIDDEN_SIZE);
+        self.hidden[..len].copy_from_slice(&data[..len]);
+    }
+
+    /// Returns whether the contents changed since the last call
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    pub fn read(&self, address: usize) -> u8 {
+        if self.state == State::Autoselect {
+            return match address & 0xFF {
+                0x00 => MANUFACTURER_ID,
+                0x01 => DEVICE_ID,
+                // No sector is write protected
+                _ => 0x00,
+            };
+        }
+
+        if self.state == State::HiddenRead {
+            return self.hidden[address % HIDDEN_SIZE];
+        }
+
+        self.data[address % self.data.len()]
+    }
+
+    pub fn write(&mut self, address: usize, value: u8) {
+        let address = address % self.data.len();
+        // Only A0-A14 are decoded for the unlock cycles
+        let command_address = address & 0x7FFF;
+
+        // Reset is accepted at any point of a sequence
+        if value == 0xF0 && !matches!(self.state, State::Program | State::HiddenProgram) {
+            self.state = State::Read;
+            return;
+        }
+
+        self.state = match (self.state, command_address, value) {
+            (State::Read, 0x5555, 0xAA) => State::Unlock1,
+            (State::Unlock1, 0x2AAA, 0x55) => State::Unlock2,
+            (State::Unlock2, 0x5555, 0xA0) => State::Program,
+            (State::Unlock2, 0x5555, 0x80) => State::EraseSetup,
+            (State::Unlock2, 0x5555, 0x90) => State::Autoselect,
+            (State::Unlock2, 0x5555, 0x77) => State::HiddenRead,
+            (State::Unlock2, 0x5555, 0x60) => State::HiddenSetup,
+            (State::Program, _, _) => {
+                // Programming can only clear bits
+                self.data[address] &= value;
+                self.dirty = true;
+                State::Read
+            }
+            (State::EraseSetup, 0x5555, 0xAA) => State::EraseUnlock1,
+            (State::EraseUnlock1, 0x2AAA, 0x55) => State::EraseUnlock2,
+            (State::EraseUnlock2, 0x5555, 0x10) => {
+                self.data.fill(0xFF);
+                self.dirty = true;
+                State::Read
+            }
+            (State::EraseUnlock2, _, 0x30) => {
+                let start = address & !(SECTOR_SIZE - 1);
+                let end = (start + SECTOR_SIZE).min(self.data.len());
+                self.data[start..end].fill(0xFF);
+                self.dirty = true;
+                State::Read
+            }
+            (State::Autoselect, _, _) => State::Autoselect,
+            (State::HiddenRead, _, _) => State::HiddenRead,
+            (State::HiddenSetup, 0x5555, 0xAA) => State::HiddenUnlock1,
+            (State::HiddenUnlock1, 0x2AAA, 0x55) => State::HiddenUnlock2,
+            (State::HiddenUnlock2, _, 0x04) => {
+                self.hidden.fill(0xFF);
+                self.dirty = true;
+                State::Read
+            }
+            (State::HiddenUnlock2, 0x5555, 0xE0) => State::HiddenProgram,
+            (State::HiddenProgram, _, _) => {
+                self.hidden[address % HIDDEN_SIZE] &= value;
+                self.dirty = true;
+                State::Read
+            }
+            _ => State::Read,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(flash: &mut Flash, value: u8) {
+        flash.write(0x5555, 0xAA);
+        flash.write(0x2AAA, 0x55);
+        flash.write(0x5555, value);
+    }
+
+    #[test]
+    pub fn test_program_and_erase() {
+        let mut flash = Flash::new(vec![], 0x20000);
+
+        command(&mut flash, 0xA0);
+        flash.write(0x12345, 0x5A);
+        assert_eq!(0x5A, flash.read(0x12345));
+        assert!(flash.take_dirty());
+
+        // Writes outside of a sequence don't touch the array
+        flash.write(0x12345, 0x00);
+        assert_eq!(0x5A, flash.read(0x12345));
+
+        command(&mut flash, 0x80);
+        flash.write(0x5555, 0xAA);
+        flash.write(0x2AAA, 0x55);
+        flash.write(0x10000, 0x30);
+        assert_eq!(0xFF, flash.read(0x12345));
+    }
+
+    #[test]
+    pub fn test_autoselect() {
+        let mut flash = Flash::new(vec![], 0x20000);
+
+        command(&mut flash, 0x90);
+        assert_eq!(MANUFACTURER_ID, flash.read(0x00));
+        assert_eq!(DEVICE_ID, flash.read(0x01));
+
+        flash.write(0x0000, 0xF0);
+        assert_eq!(0xFF, flash.read(0x00));
+    }
+
+    #[test]
+    pub fn test_hidden_sector() {
+        let mut flash = Flash::new(vec![], 0x20000);
+
+        command(&mut flash, 0x60);
+        command(&mut flash, 0xE0);
+        flash.write(0x0002, 0x12);
+        assert_eq!(0x12, flash.hidden()[2]);
+        assert_eq!(0xFF, flash.read(0x02));
+
+        command(&mut flash, 0x77);
+        assert_eq!(0x12, flash.read(0x02));
+        flash.write(0x0000, 0xF0);
+
+        command(&mut flash, 0x60);
+        flash.write(0x5555, 0xAA);
+        flash.write(0x2AAA, 0x55);
+        flash.write(0x0000, 0x04);
+        assert_eq!(0xFF, flash.hidden()[2]);
+    }
+}
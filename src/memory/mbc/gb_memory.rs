@@ -0,0 +1,378 @@
+use crate::memory::address_space::AddressSpace;
+
+use super::{
+    copy_save,
+    flash::{Flash, HIDDEN_SIZE},
+    Mbc, RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+
+const FLASH_SIZE: usize = 0x100000;
+const SRAM_SIZE: usize = 0x20000;
+
+// Writing this value to 0x013F executes the command in 0x0120
+const EXECUTE: u8 = 0xA5;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum GameMapper {
+    RomOnly,
+    Mbc1,
+    Mbc2,
+    Mbc3,
+    Mbc5,
+}
+
+/// Part of the flash and SRAM a game sees once it has been mapped
+#[derive(Copy, Clone, Debug)]
+struct Window {
+    mapper: GameMapper,
+    rom_start: usize,
+    rom_size: usize,
+    ram_start: usize,
+    ram_size: usize,
+}
+
+impl Window {
+    /// The menu sees the whole flash and SRAM through an MBC5
+    fn menu() -> Self {
+        Self {
+            mapper: GameMapper::Mbc5,
+            rom_start: 0,
+            rom_size: FLASH_SIZE,
+            ram_start: 0,
+            ram_size: SRAM_SIZE,
+        }
+    }
+
+    /// Decodes a 3 bytes entry of the hidden mapping sector
+    ///
+    /// Byte 0 holds the mapper (bits 5-7), the ROM size as 32 KiB << n
+    /// (bits 2-4) and the SRAM size (bits 0-1: none, 2, 8 or 32 KiB).
+    /// Byte 1 is the ROM offset in 32 KiB units, byte 2 the SRAM offset in 2 KiB units.
+    fn from_entry(entry: &[u8]) -> Option<Self> {
+        let mapper = match entry[0] >> 5 {
+            0 => GameMapper::RomOnly,
+            1 => GameMapper::Mbc1,
+            2 => GameMapper::Mbc2,
+            3 => GameMapper::Mbc3,
+            5 => GameMapper::Mbc5,
+            _ => return None,
+        };
+
+        let rom_size = 0x8000 << ((entry[0] >> 2) & 0x07);
+        let ram_size = match entry[0] & 0x03 {
+            0 => 0,
+            1 => 0x800,
+            2 => 0x2000,
+            _ => 0x8000,
+        };
+        let rom_start = (entry[1] & 0x1F) as usize * 0x8000;
+        let ram_start = (entry[2] & 0x3F) as usize * 0x800;
+
+        if rom_start + rom_size > FLASH_SIZE || ram_start + ram_size > SRAM_SIZE {
+            return None;
+        }
+
+        Some(Self {
+            mapper,
+            rom_start,
+            rom_size,
+            ram_start,
+            ram_size,
+        })
+    }
+}
+
+/// Nintendo Power GB Memory cartridge: 1 MiB flash, 128 KiB SRAM and a menu
+///
+/// The mapping of each game comes from the hidden sector of the flash, which is
+/// dumped separately as a 128 bytes `.map` file. The menu can rewrite it like
+/// the rest of the flash, and it is saved along with it.
+pub struct GbMemory {
+    flash: Flash,
+    sram: Vec<u8>,
+    window: Window,
+    rom_bank: usize,
+    ram_bank: usize,
+    ram_enabled: bool,
+    registers: [u8; 0x20],
+    unlocked: bool,
+    flash_writable: bool,
+    reset_requested: bool,
+}
+
+impl GbMemory {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Self {
+            flash: Flash::new(rom, FLASH_SIZE),
+            sram: vec![0; SRAM_SIZE],
+            window: Window::menu(),
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            registers: [0; 0x20],
+            unlocked: false,
+            flash_writable: false,
+            reset_requested: false,
+        }
+    }
+
+    pub fn is_gb_memory(rom: &[u8]) -> bool {
+        rom.get(0x134..0x143)
+            .is_some_and(|title| title.starts_with(b"NP M-MENU"))
+    }
+
+    fn rom_address(&self, address: u16) -> usize {
+        let offset = match address {
+            0x0000..=0x3FFF => address as usize,
+            _ => self.rom_bank * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1)),
+        };
+
+        self.window.rom_start + offset % self.window.rom_size
+    }
+
+    fn ram_address(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.window.ram_size == 0 {
+            return None;
+        }
+
+        let offset = self.ram_bank * RAM_BANK_SIZE + (address as usize & (RAM_BANK_SIZE - 1));
+        Some(self.window.ram_start + offset % self.window.ram_size)
+    }
+
+    fn write_register(&mut self, index: usize, value: u8) {
+        self.registers[index] = value;
+
+        if index != 0x1F || value != EXECUTE {
+            return;
+        }
+
+        let command = self.registers[0x00];
+
+        if command == 0x09 && self.registers[0x01] == 0xAA && self.registers[0x02] == 0x55 {
+            self.unlocked = true;
+            return;
+        }
+
+        if !self.unlocked {
+            return;
+        }
+
+        match command {
+            // Relock
+            0x08 => {
+                self.unlocked = false;
+                self.flash_writable = false;
+            }
+            // Enable flash writes through the ROM area
+            0x0A => self.flash_writable = true,
+            // Map the whole flash back, as at power on
+            0x04 => self.map_window(Window::menu()),
+            // Write a byte to the flash, bypassing the mapping
+            0x0F => {
+                let address =
+                    ((self.registers[0x05] as usize) << 8) | self.registers[0x06] as usize;
+                let value = self.registers[0x07];
+                self.flash.write(self.window.rom_start + address, value);
+            }
+            // Map one of the games then reset the console
+            0xC0..=0xC7 => {
+                let entry = (command & 0x07) as usize;
+                let window = Window::from_entry(&self.flash.hidden()[entry * 3..entry * 3 + 3]);
+
+                // An empty entry does nothing
+                if let Some(window) = window {
+                    self.map_window(window);
+                    self.reset_requested = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn map_window(&mut self, window: Window) {
+        self.window = window;
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.ram_enabled = false;
+    }
+
+    fn write_mapper(&mut self, address: u16, value: u8) {
+        match (self.window.mapper, address) {
+            (GameMapper::RomOnly, _) => {}
+
+            // The MBC2 uses A8 to tell RAM enable and ROM bank apart
+            (GameMapper::Mbc2, 0x0000..=0x3FFF) if address & 0x0100 != 0 => {
+                self.rom_bank = ((value & 0x0F) as usize).max(1);
+            }
+            (GameMapper::Mbc2, 0x0000..=0x3FFF) => self.ram_enabled = value & 0x0F == 0x0A,
+            (GameMapper::Mbc2, _) => {}
+
+            (_, 0x0000..=0x1FFF) => self.ram_enabled = value & 0x0F == 0x0A,
+
+            (GameMapper::Mbc1, 0x2000..=0x3FFF) => {
+                self.rom_bank = ((value & 0x1F) as usize).max(1);
+            }
+            (GameMapper::Mbc3, 0x2000..=0x3FFF) => {
+                self.rom_bank = ((value & 0x7F) as usize).max(1);
+            }
+            (GameMapper::Mbc5, 0x2000..=0x2FFF) => {
+                self.rom_bank = (self.rom_bank & 0x100) | value as usize;
+            }
+            (GameMapper::Mbc5, 0x3000..=0x3FFF) => {
+                self.rom_bank = (self.rom_bank & 0xFF) | (((value & 0x01) as usize) << 8);
+            }
+
+            (GameMapper::Mbc1 | GameMapper::Mbc3, 0x4000..=0x5FFF) => {
+                self.ram_bank = (value & 0x03) as usize;
+            }
+            (GameMapper::Mbc5, 0x4000..=0x5FFF) => self.ram_bank = (value & 0x0F) as usize,
+
+            _ => {}
+        }
+    }
+}
+
+impl AddressSpace for GbMemory {
+    fn get(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x7FFF => self.flash.read(self.rom_address(address)),
+            0xA000..=0xBFFF => match self.ram_address(address) {
+                Some(offset) => self.sram[offset],
+                None => 0xFF,
+            },
+            _ => 0xFF,
+        }
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        match address {
+            0x0120..=0x013F => self.write_register(address as usize - 0x0120, value),
+            0x0000..=0x7FFF => {
+                // The flash sees the write as well as the mapper
+                if self.flash_writable {
+                    let flash_address = self.rom_address(address);
+                    self.flash.write(flash_address, value);
+                }
+
+                self.write_mapper(address, value);
+            }
+            0xA000..=0xBFFF => {
+                if let Some(offset) = self.ram_address(address) {
+                    self.sram[offset] = value;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for GbMemory {
+    fn rom(&self) -> &[u8] {
+        self.flash.data()
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.sram
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        copy_save(&mut self.sram, data);
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn flash(&self) -> Option<Vec<u8>> {
+        let mut image = self.flash.data().to_vec();
+        image.extend_from_slice(self.flash.hidden());
+        Some(image)
+    }
+
+    fn load_flash(&mut self, data: &[u8]) {
+        // Either a `.map` dump alone, or a previously saved flash image
+        if data.len() == HIDDEN_SIZE {
+            self.flash.load_hidden(data);
+            return;
+        }
+
+        self.flash.load(data);
+
+        if let Some(map) = data.get(FLASH_SIZE..FLASH_SIZE + HIDDEN_SIZE) {
+            self.flash.load_hidden(map);
+        }
+    }
+
+    fn take_flash_dirty(&mut self) -> bool {
+        self.flash.take_dirty()
+    }
+
+    fn take_reset_request(&mut self) -> bool {
+        std::mem::take(&mut self.reset_requested)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x20000];
+        rom[0x134..0x13D].copy_from_slice(b"NP M-MENU");
+        // First byte of the game at 64 KiB
+        rom[0x10000] = 0x42;
+        rom
+    }
+
+    fn execute(cartridge: &mut GbMemory, command: &[u8]) {
+        for (i, value) in command.iter().enumerate() {
+            cartridge.set(0x0120 + i as u16, *value);
+        }
+        cartridge.set(0x013F, EXECUTE);
+    }
+
+    fn flash_command(cartridge: &mut GbMemory, value: u8) {
+        cartridge.set(0x5555, 0xAA);
+        cartridge.set(0x2AAA, 0x55);
+        cartridge.set(0x5555, value);
+    }
+
+    /// Writes the first mapping entry as the menu does: an MBC5 game of
+    /// 64 KiB, 64 KiB into the flash, without SRAM
+    fn program_entry(cartridge: &mut GbMemory) {
+        execute(cartridge, &[0x09, 0xAA, 0x55]);
+        execute(cartridge, &[0x0A]);
+
+        for (offset, value) in [0xA4, 0x02, 0x00].iter().enumerate() {
+            flash_command(cartridge, 0x60);
+            flash_command(cartridge, 0xE0);
+            cartridge.set(offset as u16, *value);
+        }
+    }
+
+    #[test]
+    pub fn test_menu_maps_game() {
+        let mut cartridge = GbMemory::new(menu_rom());
+        program_entry(&mut cartridge);
+        assert!(cartridge.take_flash_dirty());
+
+        execute(&mut cartridge, &[0xC0]);
+        assert!(cartridge.take_reset_request());
+        assert_eq!(0x42, cartridge.get(0x0000));
+    }
+
+    #[test]
+    pub fn test_flash_save_round_trip() {
+        let mut cartridge = GbMemory::new(menu_rom());
+        program_entry(&mut cartridge);
+        let image = cartridge.flash().unwrap();
+        assert_eq!(FLASH_SIZE + HIDDEN_SIZE, image.len());
+
+        // The mapping written by the menu is still there after a reload
+        let mut reloaded = GbMemory::new(menu_rom());
+        reloaded.load_flash(&image);
+        execute(&mut reloaded, &[0x09, 0xAA, 0x55]);
+        execute(&mut reloaded, &[0xC0]);
+        assert_eq!(0x42, reloaded.get(0x0000));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::memory::address_space::AddressSpace;
+
+use super::{copy_save, ram_offset, ram_size, read_rom_bank, Mbc};
+
+/// MBC5 (0x19-0x1E), up to 8 MiB of ROM and 128 KiB of RAM
+pub struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank: usize,
+    ram_bank: usize,
+    ram_enabled: bool,
+    battery: bool,
+}
+
+impl Mbc5 {
+    pub fn new(rom: Vec<u8>) -> Self {
+        let ram_code = rom.get(0x149).copied().unwrap_or(0);
+        let cartridge_type = rom.get(0x147).copied().unwrap_or(0);
+
+        Self {
+            rom,
+            ram: vec![0; ram_size(ram_code)],
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            battery: cartridge_type == 0x1B || cartridge_type == 0x1E,
+        }
+    }
+}
+
+impl AddressSpace for Mbc5 {
+    fn get(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => read_rom_bank(&self.rom, 0, address),
+            0x4000..=0x7FFF => read_rom_bank(&self.rom, self.rom_bank, address),
+            0xA000..=0xBFFF if self.ram_enabled => {
+                match ram_offset(&self.ram, self.ram_bank, address) {
+                    Some(offset) => self.ram[offset],
+                    None => 0xFF,
+                }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+
+            // Lower 8 bits of the ROM bank, bank 0 can be mapped
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | value as usize,
+
+            // 9th bit of the ROM bank
+            0x3000..=0x3FFF => {
+                self.rom_bank = (self.rom_bank & 0xFF) | (((value & 0x01) as usize) << 8)
+            }
+
+            0x4000..=0x5FFF => self.ram_bank = (value & 0x0F) as usize,
+
+            0xA000..=0xBFFF if self.ram_enabled => {
+                if let Some(offset) = ram_offset(&self.ram, self.ram_bank, address) {
+                    self.ram[offset] = value;
+                }
+            }
+
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for Mbc5 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        copy_save(&mut self.ram, data);
+    }
+
+    fn has_battery(&self) -> bool {
+        self.battery
+    }
+}
@@ -0,0 +1,233 @@
+use crate::memory::address_space::AddressSpace;
+
+use super::{copy_save, ram_offset, ram_size, read_rom_bank, Mbc};
+
+/// MMM01 multi-game mapper (0x0B-0x0D)
+///
+/// Boots in unmapped mode with the last 32 KiB of the ROM (the menu) visible.
+/// The menu configures the outer bank bits and masks, then sets the map enable
+/// bit, after which the mapper behaves as an MBC1 restricted to the game's window.
+pub struct Mmm01 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    mapped: bool,
+    ram_enabled: bool,
+    rom_bank_low: u8,
+    rom_bank_mid: u8,
+    rom_bank_high: u8,
+    ram_bank_low: u8,
+    ram_bank_high: u8,
+    // ROM bank bits 1-4 that the game can't change anymore
+    rom_bank_mask: u8,
+    // RAM bank bits 0-1 that the game can't change anymore
+    ram_bank_mask: u8,
+    mode: bool,
+    mode_locked: bool,
+    multiplex: bool,
+    battery: bool,
+}
+
+impl Mmm01 {
+    pub fn new(rom: Vec<u8>) -> Self {
+        // The relevant header is the menu's one, in the last bank
+        let header_offset = rom.len().saturating_sub(0x8000);
+        let ram_code = rom.get(header_offset + 0x149).copied().unwrap_or(0);
+        let cartridge_type = rom.get(header_offset + 0x147).copied().unwrap_or(0);
+
+        Self {
+            rom,
+            ram: vec![0; ram_size(ram_code)],
+            mapped: false,
+            ram_enabled: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_mask: 0,
+            mode: false,
+            mode_locked: false,
+            multiplex: false,
+            battery: cartridge_type == 0x0D,
+        }
+    }
+
+    fn writable_rom_bits(&self) -> u8 {
+        if self.mapped {
+            0x1F & !(self.rom_bank_mask << 1)
+        } else {
+            0x1F
+        }
+    }
+
+    fn outer_bits(&self) -> usize {
+        let mid = if self.multiplex && self.mode {
+            self.ram_bank_low
+        } else {
+            self.rom_bank_mid
+        };
+
+        ((self.rom_bank_high as usize) << 7) | ((mid as usize) << 5)
+    }
+
+    fn low_rom_bank(&self) -> usize {
+        if !self.mapped {
+            return 0x1FE;
+        }
+
+        self.outer_bits() | (self.rom_bank_low & !self.writable_rom_bits()) as usize
+    }
+
+    fn high_rom_bank(&self) -> usize {
+        if !self.mapped {
+            return 0x1FF;
+        }
+
+        // Same zero bank quirk as the MBC1, limited to the game's bits
+        let mut low = self.rom_bank_low;
+        if low & self.writable_rom_bits() == 0 {
+            low |= 1;
+        }
+
+        self.outer_bits() | low as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        let low = if self.multiplex { 0 } else { self.ram_bank_low };
+        ((self.ram_bank_high as usize) << 2) | low as usize
+    }
+}
+
+impl AddressSpace for Mmm01 {
+    fn get(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => read_rom_bank(&self.rom, self.low_rom_bank(), address),
+            0x4000..=0x7FFF => read_rom_bank(&self.rom, self.high_rom_bank(), address),
+            0xA000..=0xBFFF if self.ram_enabled => {
+                match ram_offset(&self.ram, self.ram_bank(), address) {
+                    Some(offset) => self.ram[offset],
+                    None => 0xFF,
+                }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        match address {
+            // RAM enable, RAM bank mask and map enable
+            0x0000..=0x1FFF => {
+                self.ram_enabled = value & 0x0F == 0x0A;
+
+                if !self.mapped {
+                    self.ram_bank_mask = (value >> 4) & 0x03;
+                    self.mapped = value & 0x40 != 0;
+                }
+            }
+
+            // ROM bank low and mid bits
+            0x2000..=0x3FFF => {
+                let writable = self.writable_rom_bits();
+                self.rom_bank_low = (self.rom_bank_low & !writable) | (value & writable);
+
+                if !self.mapped {
+                    self.rom_bank_mid = (value >> 5) & 0x03;
+                }
+            }
+
+            // RAM bank and outer ROM bank bits
+            0x4000..=0x5FFF => {
+                let writable = if self.mapped {
+                    0x03 & !self.ram_bank_mask
+                } else {
+                    0x03
+                };
+                self.ram_bank_low = (self.ram_bank_low & !writable) | (value & writable);
+
+                if !self.mapped {
+                    self.ram_bank_high = (value >> 2) & 0x03;
+                    self.rom_bank_high = (value >> 4) & 0x03;
+                    self.mode_locked = value & 0x40 != 0;
+                }
+            }
+
+            // Banking mode, ROM bank mask and multiplexing
+            0x6000..=0x7FFF => {
+                if !self.mode_locked {
+                    self.mode = value & 0x01 != 0;
+                }
+
+                if !self.mapped {
+                    self.rom_bank_mask = (value >> 2) & 0x0F;
+                    self.multiplex = value & 0x40 != 0;
+                }
+            }
+
+            0xA000..=0xBFFF if self.ram_enabled => {
+                if let Some(offset) = ram_offset(&self.ram, self.ram_bank(), address) {
+                    self.ram[offset] = value;
+                }
+            }
+
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for Mmm01 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        copy_save(&mut self.ram, data);
+    }
+
+    fn has_battery(&self) -> bool {
+        self.battery
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom() -> Vec<u8> {
+        // 512 KiB, every bank starting with its own number
+        let mut rom = vec![0; 0x80000];
+        for (bank, chunk) in rom.chunks_mut(0x4000).enumerate() {
+            chunk[0] = bank as u8;
+        }
+        rom
+    }
+
+    #[test]
+    pub fn test_menu_mapped_at_boot() {
+        let mmm01 = Mmm01::new(rom());
+
+        assert_eq!(0x1E, mmm01.get(0x0000));
+        assert_eq!(0x1F, mmm01.get(0x4000));
+    }
+
+    #[test]
+    pub fn test_game_window_is_locked() {
+        let mut mmm01 = Mmm01::new(rom());
+
+        // Game at banks 0x10-0x17: bank 0x10, only bits 0-2 left to the game
+        mmm01.set(0x2000, 0x10);
+        mmm01.set(0x6000, 0x0C << 2);
+        mmm01.set(0x0000, 0x40);
+
+        assert_eq!(0x10, mmm01.get(0x0000));
+        assert_eq!(0x11, mmm01.get(0x4000));
+
+        mmm01.set(0x2000, 0x1F);
+        assert_eq!(0x17, mmm01.get(0x4000));
+        assert_eq!(0x10, mmm01.get(0x0000));
+    }
+}
@@ -0,0 +1,124 @@
+mod bbd;
+mod camera_source;
+mod detect;
+mod flash;
+mod gb_memory;
+mod mbc5;
+mod mmm01;
+mod pocket_camera;
+mod rom_only;
+mod sachen;
+mod wisdom_tree;
+
+use super::address_space::AddressSpace;
+
+pub use bbd::Bbd;
+pub use camera_source::{ImageSource, PnmImage, TestPattern};
+pub use detect::{detect, MapperKind};
+pub use gb_memory::GbMemory;
+pub use mbc5::Mbc5;
+pub use mmm01::Mmm01;
+pub use pocket_camera::PocketCamera;
+pub use rom_only::RomOnly;
+pub use sachen::Sachen;
+pub use wisdom_tree::WisdomTree;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+/// Memory bank controller living on the cartridge
+///
+/// Mappers see the ROM area (0x0000-0x7FFF) and the external RAM area
+/// (0xA000-0xBFFF) through `AddressSpace`, with the CPU addresses untouched.
+pub trait Mbc: AddressSpace {
+    fn rom(&self) -> &[u8];
+
+    /// Advances time-dependent hardware on the cartridge
+    fn step(&mut self, _cycles: u8) {}
+
+    /// External RAM contents
+    fn ram(&self) -> &[u8] {
+        &[]
+    }
+
+    fn load_ram(&mut self, _data: &[u8]) {}
+
+    /// Whether the external RAM survives power off
+    fn has_battery(&self) -> bool {
+        false
+    }
+
+    /// Plugs a new image source in, only meaningful for the Pocket Camera
+    fn set_image_source(&mut self, _source: Box<dyn ImageSource>) {}
+
+    /// Image of the rewritable ROM, for cartridges built around a flash chip
+    fn flash(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn load_flash(&mut self, _data: &[u8]) {}
+
+    /// Returns whether the flash was written since the last call
+    fn take_flash_dirty(&mut self) -> bool {
+        false
+    }
+
+    /// Returns whether the cartridge asked for the console to be reset
+    fn take_reset_request(&mut self) -> bool {
+        false
+    }
+}
+
+/// Creates the given mapper around the ROM
+pub fn create(kind: MapperKind, rom: Vec<u8>) -> Box<dyn Mbc> {
+    match kind {
+        MapperKind::RomOnly => Box::new(RomOnly::new(rom)),
+        MapperKind::Mbc5 => Box::new(Mbc5::new(rom)),
+        MapperKind::Mmm01 => Box::new(Mmm01::new(rom)),
+        MapperKind::GbMemory => Box::new(GbMemory::new(rom)),
+        MapperKind::PocketCamera => Box::new(PocketCamera::new(rom)),
+        MapperKind::WisdomTree => Box::new(WisdomTree::new(rom)),
+        MapperKind::SachenMmc1 => Box::new(Sachen::mmc1(rom)),
+        MapperKind::SachenMmc2 => Box::new(Sachen::mmc2(rom)),
+        MapperKind::Bbd => Box::new(Bbd::standard(rom)),
+        MapperKind::Hitek => Box::new(Bbd::hitek(rom)),
+    }
+}
+
+/// External RAM size from the header code (0x0149)
+pub fn ram_size(code: u8) -> usize {
+    match code {
+        0x01 => 0x800,
+        0x02 => 0x2000,
+        0x03 => 0x8000,
+        0x04 => 0x20000,
+        0x05 => 0x10000,
+        _ => 0,
+    }
+}
+
+/// Copies a save into RAM, ignoring anything past the RAM size
+pub fn copy_save(ram: &mut [u8], data: &[u8]) {
+    let len = ram.len().min(data.len());
+    ram[..len].copy_from_slice(&data[..len]);
+}
+
+/// Reads a byte from the given ROM bank, wrapping around small ROMs
+pub fn read_rom_bank(rom: &[u8], bank: usize, address: u16) -> u8 {
+    if rom.is_empty() {
+        return 0xFF;
+    }
+
+    let offset = bank * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1));
+    rom[offset % rom.len()]
+}
+
+/// Byte offset inside external RAM for the given bank, wrapping around small RAMs
+pub fn ram_offset(ram: &[u8], bank: usize, address: u16) -> Option<usize> {
+    if ram.is_empty() {
+        return None;
+    }
+
+    let offset = bank * RAM_BANK_SIZE + (address as usize & (RAM_BANK_SIZE - 1));
+    Some(offset % ram.len())
+}
@@ -0,0 +1,303 @@
+use crate::memory::address_space::AddressSpace;
+
+use super::{
+    camera_source::{SENSOR_HEIGHT, SENSOR_WIDTH},
+    copy_save, ram_offset, read_rom_bank, ImageSource, Mbc, TestPattern,
+};
+
+const RAM_SIZE: usize = 0x20000;
+const REGISTER_COUNT: usize = 0x36;
+
+// Register indices, mirrored every 0x80 bytes from 0xA000
+const CONTROL: usize = 0x00;
+const GAIN: usize = 0x01;
+const EXPOSURE_HIGH: usize = 0x02;
+const EXPOSURE_LOW: usize = 0x03;
+const EDGE_INVERT: usize = 0x04;
+const DITHER_MATRIX: usize = 0x06;
+
+// The processed picture lands in RAM bank 0 as 16x14 tiles
+const IMAGE_OFFSET: usize = 0x0100;
+
+/// MAC-GBD mapper with the Mitsubishi M64282FP sensor (0xFC)
+pub struct PocketCamera {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank: usize,
+    ram_bank: usize,
+    registers_mapped: bool,
+    ram_write_enabled: bool,
+    registers: [u8; REGISTER_COUNT],
+    capture_cycles: u32,
+    source: Box<dyn ImageSource>,
+}
+
+impl PocketCamera {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Self {
+            rom,
+            ram: vec![0; RAM_SIZE],
+            rom_bank: 1,
+            ram_bank: 0,
+            registers_mapped: false,
+            ram_write_enabled: false,
+            registers: [0; REGISTER_COUNT],
+            capture_cycles: 0,
+            source: Box::new(TestPattern::new()),
+        }
+    }
+
+    fn exposure(&self) -> u32 {
+        ((self.registers[EXPOSURE_HIGH] as u32) << 8) | self.registers[EXPOSURE_LOW] as u32
+    }
+
+    fn start_capture(&mut self) {
+        let n_flag = self.registers[GAIN] & 0x80 != 0;
+        let m_cycles = 32446 + if n_flag { 0 } else { 512 } + 16 * self.exposure();
+
+        self.capture_cycles = m_cycles * 4;
+    }
+
+    fn finish_capture(&mut self) {
+        let frame = self.source.capture();
+        let image = self.process(&frame);
+
+        for (i, pixel) in image.iter().enumerate() {
+            let (x, y) = (i % SENSOR_WIDTH, i / SENSOR_WIDTH);
+            let tile = (y / 8) * (SENSOR_WIDTH / 8) + x / 8;
+            let address = IMAGE_OFFSET + tile * 16 + (y % 8) * 2;
+            let bit = 7 - (x % 8);
+
+            self.ram[address] &= !(1 << bit);
+            self.ram[address + 1] &= !(1 << bit);
+            self.ram[address] |= (pixel & 1) << bit;
+            self.ram[address + 1] |= ((pixel >> 1) & 1) << bit;
+        }
+
+        self.registers[CONTROL] &= !0x01;
+    }
+
+    /// Runs the sensor pipeline, returning one shade (0-3) per pixel
+    fn process(&self, frame: &[u8]) -> Vec<u8> {
+        // Gain follows the sensor's logarithmic curve, roughly 0.6 dB per step
+        let gain = 0.88 * 1.0717f32.powi((self.registers[GAIN] & 0x1F) as i32);
+        let exposure = self.exposure() as f32 / 0x1000 as f32;
+
+        let exposed: Vec<f32> = frame
+            .iter()
+            .map(|&pixel| pixel as f32 * gain * exposure)
+            .collect();
+
+        let at = |x: isize, y: isize| {
+            let x = x.clamp(0, SENSOR_WIDTH as isize - 1) as usize;
+            let y = y.clamp(0, SENSOR_HEIGHT as isize - 1) as usize;
+            exposed[y * SENSOR_WIDTH + x]
+        };
+
+        const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+        let ratio = EDGE_RATIOS[((self.registers[EDGE_INVERT] >> 4) & 0x07) as usize];
+        let edge_enabled = self.registers[GAIN] & 0x80 != 0;
+        let horizontal = self.registers[GAIN] & 0x20 != 0;
+        let vertical = self.registers[GAIN] & 0x40 != 0;
+        let invert = self.registers[EDGE_INVERT] & 0x08 != 0;
+
+        let mut image = Vec::with_capacity(frame.len());
+
+        for y in 0..SENSOR_HEIGHT as isize {
+            for x in 0..SENSOR_WIDTH as isize {
+                let mut color = at(x, y);
+
+                if edge_enabled {
+                    if horizontal {
+                        color += (2.0 * at(x, y) - at(x - 1, y) - at(x + 1, y)) * ratio;
+                    }
+                    if vertical {
+                        color += (2.0 * at(x, y) - at(x, y - 1) - at(x, y + 1)) * ratio;
+                    }
+                }
+
+                let mut color = color.clamp(0.0, 255.0) as u8;
+                if invert {
+                    color = 0xFF - color;
+                }
+
+                let base = DITHER_MATRIX + ((y as usize & 3) * 4 + (x as usize & 3)) * 3;
+                let thresholds = &self.registers[base..base + 3];
+
+                let shade = if color < thresholds[0] {
+                    3
+                } else if color < thresholds[1] {
+                    2
+                } else if color < thresholds[2] {
+                    1
+                } else {
+                    0
+                };
+
+                image.push(shade);
+            }
+        }
+
+        image
+    }
+}
+
+impl AddressSpace for PocketCamera {
+    fn get(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => read_rom_bank(&self.rom, 0, address),
+            0x4000..=0x7FFF => read_rom_bank(&self.rom, self.rom_bank, address),
+            0xA000..=0xBFFF if self.registers_mapped => {
+                // Only the control register can be read back
+                if address as usize & 0x7F == CONTROL {
+                    self.registers[CONTROL] & 0x07
+                } else {
+                    0x00
+                }
+            }
+            0xA000..=0xBFFF => match ram_offset(&self.ram, self.ram_bank, address) {
+                // The RAM can't be read while a capture is writing to it
+                Some(_) if self.capture_cycles > 0 => 0x00,
+                Some(offset) => self.ram[offset],
+                None => 0xFF,
+            },
+            _ => 0xFF,
+        }
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        match address {
+            // RAM write enable
+            0x0000..=0x1FFF => self.ram_write_enabled = value & 0x0F == 0x0A,
+
+            // ROM bank number, bank 0 can be mapped
+            0x2000..=0x3FFF => self.rom_bank = (value & 0x3F) as usize,
+
+            // RAM bank number, bit 4 selects the camera registers
+            0x4000..=0x5FFF => {
+                self.registers_mapped = value & 0x10 != 0;
+                self.ram_bank = (value & 0x0F) as usize;
+            }
+
+            0x6000..=0x7FFF => {}
+
+            0xA000..=0xBFFF if self.registers_mapped => {
+                let index = address as usize & 0x7F;
+
+                if index == CONTROL {
+                    let was_busy = self.registers[CONTROL] & 0x01 != 0;
+                    self.registers[CONTROL] = value & 0x07;
+
+                    if value & 0x01 != 0 && !was_busy {
+                        self.start_capture();
+                    } else if was_busy {
+                        // Writing while busy can't stop the capture
+                        self.registers[CONTROL] |= 0x01;
+                    }
+                } else if index < REGISTER_COUNT {
+                    self.registers[index] = value;
+                }
+            }
+
+            0xA000..=0xBFFF if self.ram_write_enabled && self.capture_cycles == 0 => {
+                if let Some(offset) = ram_offset(&self.ram, self.ram_bank, address) {
+                    self.ram[offset] = value;
+                }
+            }
+
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for PocketCamera {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        copy_save(&mut self.ram, data);
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn step(&mut self, cycles: u8) {
+        if self.capture_cycles == 0 {
+            return;
+        }
+
+        self.capture_cycles = self.capture_cycles.saturating_sub(cycles as u32);
+
+        if self.capture_cycles == 0 {
+            self.finish_capture();
+        }
+    }
+
+    fn set_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.source = source;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Flat(u8);
+
+    impl ImageSource for Flat {
+        fn capture(&mut self) -> Vec<u8> {
+            vec![self.0; SENSOR_WIDTH * SENSOR_HEIGHT]
+        }
+    }
+
+    fn camera(level: u8) -> PocketCamera {
+        let mut camera = PocketCamera::new(vec![0; 0x8000]);
+        camera.set_image_source(Box::new(Flat(level)));
+
+        camera.set(0x4000, 0x10);
+        camera.set(0xA002, 0x10);
+        camera.set(0xA003, 0x00);
+        for i in 0..16 {
+            camera.set(0xA006 + i * 3, 0x40);
+            camera.set(0xA007 + i * 3, 0x80);
+            camera.set(0xA008 + i * 3, 0xC0);
+        }
+
+        camera
+    }
+
+    fn capture(camera: &mut PocketCamera) {
+        camera.set(0xA000, 0x01);
+        assert_eq!(0x01, camera.get(0xA000) & 0x01);
+
+        while camera.get(0xA000) & 0x01 != 0 {
+            camera.step(255);
+        }
+
+        camera.set(0x4000, 0x00);
+    }
+
+    #[test]
+    pub fn test_capture_black_frame() {
+        let mut camera = camera(0x00);
+        capture(&mut camera);
+
+        assert_eq!(0xFF, camera.get(0xA100));
+        assert_eq!(0xFF, camera.get(0xA101));
+    }
+
+    #[test]
+    pub fn test_capture_white_frame() {
+        let mut camera = camera(0xFF);
+        capture(&mut camera);
+
+        assert_eq!(0x00, camera.get(0xA100));
+        assert_eq!(0x00, camera.get(0xA101));
+    }
+}
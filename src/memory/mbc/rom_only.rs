@@ -0,0 +1,32 @@
+use crate::memory::address_space::AddressSpace;
+
+use super::{read_rom_bank, Mbc};
+
+/// 32 KiB cartridges without any mapper
+pub struct RomOnly {
+    rom: Vec<u8>,
+}
+
+impl RomOnly {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Self { rom }
+    }
+}
+
+impl AddressSpace for RomOnly {
+    fn get(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => read_rom_bank(&self.rom, 0, address),
+            0x4000..=0x7FFF => read_rom_bank(&self.rom, 1, address),
+            _ => 0xFF,
+        }
+    }
+
+    fn set(&mut self, _address: u16, _value: u8) {}
+}
+
+impl Mbc for RomOnly {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+}
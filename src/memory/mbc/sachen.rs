@@ -0,0 +1,187 @@
+use std::cell::Cell;
+
+use crate::memory::address_space::AddressSpace;
+
+use super::{read_rom_bank, Mbc};
+
+// Reads of the 0x01xx page needed by the boot ROM before the mapper unlocks
+const UNLOCK_READS: u8 = 0x31;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Lock {
+    // MMC2 only, waiting for the DMG boot ROM
+    Dmg,
+    Locked,
+    Unlocked,
+}
+
+/// Sachen MMC1 and MMC2
+///
+/// While locked, reads of the 0x01xx page get A7 forced high so the boot ROM
+/// finds the real Nintendo logo at 0x0184 instead of Sachen's own one. The
+/// header page is also stored with A0/A6 and A1/A4 swapped.
+pub struct Sachen {
+    rom: Vec<u8>,
+    base_bank: usize,
+    rom_bank: usize,
+    mask: usize,
+    lock: Cell<Lock>,
+    header_reads: Cell<u8>,
+}
+
+impl Sachen {
+    pub fn mmc1(rom: Vec<u8>) -> Self {
+        Self::new(rom, Lock::Locked)
+    }
+
+    /// The MMC2 unlocks in two steps, once for the DMG and once for the CGB boot ROM
+    ///
+    /// Real hardware counts A15 edges to leave the first stage, which the
+    /// cartridge can't see here, so both stages count header reads.
+    pub fn mmc2(rom: Vec<u8>) -> Self {
+        Self::new(rom, Lock::Dmg)
+    }
+
+    fn new(rom: Vec<u8>, lock: Lock) -> Self {
+        Self {
+            rom,
+            base_bank: 0,
+            rom_bank: 1,
+            mask: 0,
+            lock: Cell::new(lock),
+            header_reads: Cell::new(0),
+        }
+    }
+
+    fn low_bank(&self) -> usize {
+        self.base_bank & self.mask
+    }
+
+    fn high_bank(&self) -> usize {
+        (self.rom_bank & !self.mask) | (self.base_bank & self.mask)
+    }
+
+    /// Base bank and mask are frozen once the ROM bank bits 4-5 are cleared
+    fn outer_writable(&self) -> bool {
+        self.rom_bank & 0x30 == 0x30
+    }
+
+    fn count_header_read(&self) -> bool {
+        let reads = self.header_reads.get() + 1;
+        self.header_reads.set(reads);
+
+        if reads < UNLOCK_READS {
+            return false;
+        }
+
+        self.header_reads.set(0);
+        self.lock.set(match self.lock.get() {
+            Lock::Dmg => Lock::Locked,
+            _ => Lock::Unlocked,
+        });
+
+        true
+    }
+}
+
+/// Swaps A0 with A6 and A1 with A4
+pub fn unscramble(address: u16) -> u16 {
+    let mut unscrambled = address & 0xFFAC;
+    unscrambled |= (address & 0x40) >> 6;
+    unscrambled |= (address & 0x10) >> 3;
+    unscrambled |= (address & 0x02) << 3;
+    unscrambled |= (address & 0x01) << 6;
+    unscrambled
+}
+
+impl AddressSpace for Sachen {
+    fn get(&self, address: u16) -> u8 {
+        match address {
+            0x0100..=0x01FF => {
+                let mut address = address;
+
+                if self.lock.get() != Lock::Unlocked && !self.count_header_read() {
+                    address |= 0x80;
+                }
+
+                read_rom_bank(&self.rom, self.low_bank(), unscramble(address))
+            }
+            0x0000..=0x3FFF => read_rom_bank(&self.rom, self.low_bank(), address),
+            0x4000..=0x7FFF => read_rom_bank(&self.rom, self.high_bank(), address),
+            _ => 0xFF,
+        }
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF if self.outer_writable() => self.base_bank = value as usize,
+            0x2000..=0x3FFF => self.rom_bank = (value as usize).max(1),
+            0x4000..=0x5FFF if self.outer_writable() => self.mask = value as usize,
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for Sachen {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom() -> Vec<u8> {
+        // 512 KiB, every bank starting with its own number
+        let mut rom = vec![0; 0x80000];
+        for (bank, chunk) in rom.chunks_mut(0x4000).enumerate() {
+            chunk[0] = bank as u8;
+        }
+        rom[unscramble(0x0184) as usize] = 0xAA;
+        rom[unscramble(0x0104) as usize] = 0xBB;
+        rom
+    }
+
+    #[test]
+    pub fn test_logo_unlock() {
+        let sachen = Sachen::mmc1(rom());
+
+        // The boot ROM sees the logo at 0x0184 until it read the header enough
+        for _ in 0..UNLOCK_READS - 1 {
+            assert_eq!(0xAA, sachen.get(0x0104));
+        }
+        assert_eq!(0xBB, sachen.get(0x0104));
+        assert_eq!(0xBB, sachen.get(0x0104));
+    }
+
+    #[test]
+    pub fn test_mmc2_unlocks_twice() {
+        let sachen = Sachen::mmc2(rom());
+
+        // Each stage lets the last of its reads through
+        for _ in 0..2 {
+            for _ in 0..UNLOCK_READS - 1 {
+                assert_eq!(0xAA, sachen.get(0x0104));
+            }
+            assert_eq!(0xBB, sachen.get(0x0104));
+        }
+        assert_eq!(0xBB, sachen.get(0x0104));
+    }
+
+    #[test]
+    pub fn test_outer_banks() {
+        let mut sachen = Sachen::mmc1(rom());
+
+        // The base bank and mask can only be set while bits 4-5 of the bank are
+        sachen.set(0x2000, 0x30);
+        sachen.set(0x0000, 0x10);
+        sachen.set(0x4000, 0x30);
+        sachen.set(0x2000, 0x02);
+        assert_eq!(0x10, sachen.get(0x0000));
+        assert_eq!(0x12, sachen.get(0x4000));
+
+        sachen.set(0x0000, 0x00);
+        assert_eq!(0x10, sachen.get(0x0000));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::memory::address_space::AddressSpace;
+
+use super::Mbc;
+
+const BANK_SIZE: usize = 0x8000;
+
+/// Wisdom Tree mapper, switching the whole 32 KiB window at once
+///
+/// The bank number is taken from the low bits of the written address, the
+/// written value is ignored.
+pub struct WisdomTree {
+    rom: Vec<u8>,
+    bank: usize,
+}
+
+impl WisdomTree {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Self { rom, bank: 0 }
+    }
+}
+
+impl AddressSpace for WisdomTree {
+    fn get(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x7FFF if !self.rom.is_empty() => {
+                self.rom[(self.bank * BANK_SIZE + address as usize) % self.rom.len()]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn set(&mut self, address: u16, _value: u8) {
+        if let 0x0000..=0x3FFF = address {
+            self.bank = (address & 0x3F) as usize;
+        }
+    }
+}
+
+impl Mbc for WisdomTree {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_32_kib_banks() {
+        // 128 KiB, every 16 KiB starting with its own number
+        let mut rom = vec![0; 0x20000];
+        for (i, chunk) in rom.chunks_mut(0x4000).enumerate() {
+            chunk[0] = i as u8;
+        }
+        let mut wisdom_tree = WisdomTree::new(rom);
+
+        assert_eq!(0x00, wisdom_tree.get(0x0000));
+        assert_eq!(0x01, wisdom_tree.get(0x4000));
+
+        // The address picks the bank, not the value
+        wisdom_tree.set(0x0002, 0x00);
+        assert_eq!(0x04, wisdom_tree.get(0x0000));
+        assert_eq!(0x05, wisdom_tree.get(0x4000));
+
+        // Past the end of the ROM, it wraps
+        wisdom_tree.set(0x0005, 0xFF);
+        assert_eq!(0x02, wisdom_tree.get(0x0000));
+
+        // Writes above 0x3FFF don't switch
+        wisdom_tree.set(0x4000, 0x00);
+        assert_eq!(0x02, wisdom_tree.get(0x0000));
+    }
+}
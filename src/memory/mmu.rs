@@ -1,25 +1,94 @@
+use crate::apu::Apu;
 use crate::boot_rom::GAMEBOY_CLASSIC;
+use crate::ppu::{CompatibilityPalettes, Mode, Ppu, PpuEvents};
 
 use super::address_space::AddressSpace;
 use super::cartridge::Cartridge;
-use super::{bytes_to_word, word_to_bytes};
+use super::dma::{Bus, Hdma, HdmaRequest, OamDma, HDMA_BLOCK_CYCLES, HDMA_BLOCK_SIZE};
+use super::header::Header;
+use super::joypad::{Button, Joypad};
+use super::model::Model;
+use super::timer::Timer;
+use super::{bytes_to_word, word_to_bytes, INTERRUPT_JOYPAD, INTERRUPT_TIMER};
+
+// The CPU is stopped for 2050 M-cycles while switching speed
+const SPEED_SWITCH_CYCLES: u32 = 2050 * 4;
 
 pub struct Mmu {
     cartridge: Cartridge,
-    vram: [u8; 0x2000],
+    model: Model,
+    // Two banks on CGB, selected by VBK (FF4F)
+    vram: [u8; 0x4000],
+    vram_bank: usize,
+    // Eight banks on CGB, D000-DFFF selected by SVBK (FF70), fixed to 1 on DMG
+    wram: [u8; 0x8000],
+    wram_bank: usize,
+    oam: [u8; 0xA0],
+    io: [u8; 0x80],
+    hram: [u8; 0x7F],
+    interrupt_enable: u8,
+    joypad: Joypad,
+    timer: Timer,
+    ppu: Ppu,
+    apu: Apu,
+    oam_dma: OamDma,
+    hdma: Hdma,
+    // CPU cycles the CPU has to wait for, during a VRAM DMA or a speed switch
+    stall_cycles: u32,
+    // CGB double speed, armed by KEY1 (FF4D) and switched on STOP
+    double_speed: bool,
+    speed_switch_armed: bool,
     is_booting: bool,
 }
 
 impl Mmu {
-    pub fn new(cartridge: Cartridge) -> Self {
+    pub fn new(cartridge: Cartridge, model: Model) -> Self {
+        let mut ppu = Ppu::new(model);
+        // The CGB boot ROM colorizes games that don't know about it
+        if model == Model::Cgb {
+            if let Some(header) = Header::parse(cartridge.rom()).filter(|h| !h.is_cgb_aware()) {
+                ppu.set_compatibility_palettes(CompatibilityPalettes::for_header(&header));
+            }
+        }
+
         Self {
             cartridge,
-            vram: [0; 0x2000],
+            model,
+            vram: [0; 0x4000],
+            vram_bank: 0,
+            wram: [0; 0x8000],
+            wram_bank: 1,
+            oam: [0; 0xA0],
+            io: [0; 0x80],
+            hram: [0; 0x7F],
+            interrupt_enable: 0,
+            joypad: Joypad::new(),
+            timer: Timer::new(),
+            ppu,
+            apu: Apu::new(model),
+            oam_dma: OamDma::new(),
+            hdma: Hdma::new(),
+            stall_cycles: 0,
+            double_speed: false,
+            speed_switch_armed: false,
             is_booting: true,
         }
     }
 
     pub fn power_up(&mut self) {
+        self.is_booting = true;
+        self.timer = Timer::new();
+        self.ppu.reset();
+        let sample_rate = self.apu.sample_rate();
+        self.apu = Apu::new(self.model);
+        self.apu.set_sample_rate(sample_rate);
+        self.oam_dma = OamDma::new();
+        self.hdma = Hdma::new();
+        self.stall_cycles = 0;
+        self.double_speed = false;
+        self.speed_switch_armed = false;
+        self.vram_bank = 0;
+        self.wram_bank = 1;
         self.init_memory();
     }
 
@@ -57,88 +126,355 @@ impl Mmu {
         self.set(0xFFFF, 0x00);
     }
 
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
     pub fn cartridge(&self) -> &Cartridge {
         &self.cartridge
     }
-}
 
-impl AddressSpace for Mmu {
-    fn get(&self, address: u16) -> u8 {
-        match address {
-            // ROM Bank
-            // Fixed until 0x3FFF
-            0x0000..=0x7FFF => {
-                // TODO Improve this
-                if self.is_booting {
-                    GAMEBOY_CLASSIC[address as usize]
-                } else {
-                    self.cartridge.get(address)
+    pub fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    /// Advances the hardware outside of the CPU by the given amount of CPU cycles
+    ///
+    /// Returns the time elapsed in 4 MHz cycles, which is what the PPU and APU
+    /// run at whatever the CPU speed.
+    pub fn step(&mut self, cycles: u8) -> u32 {
+        let mut elapsed = self.advance(cycles);
+
+        // The CPU doesn't run during a VRAM DMA or a speed switch, everything else does
+        while self.stall_cycles > 0 {
+            let stall = self.stall_cycles.min(0xFC);
+            self.stall_cycles -= stall;
+            elapsed += self.advance(stall as u8);
+        }
+
+        elapsed
+    }
+
+    fn advance(&mut self, cycles: u8) -> u32 {
+        self.cartridge.step(cycles);
+
+        // The timer and OAM DMA follow the CPU clock
+        for _ in 0..cycles / 4 {
+            let signal = self.frame_sequencer_signal();
+            if self.timer.tick() {
+                self.request_interrupt(INTERRUPT_TIMER);
+            }
+            self.clock_frame_sequencer(signal);
+            self.step_oam_dma();
+        }
+
+        // The PPU keeps its speed in double speed mode
+        let dots = if self.double_speed {
+            cycles / 2
+        } else {
+            cycles
+        };
+
+        for _ in 0..dots {
+            let events = self.ppu.tick(&self.vram, &self.oam);
+            self.handle_ppu_events(events);
+            self.apu.tick();
+        }
+
+        dots as u32
+    }
+
+    /// DIV bit whose falling edge clocks the APU's frame sequencer, bit 4 or
+    /// bit 5 in double speed so it stays at 512 Hz
+    fn frame_sequencer_signal(&self) -> bool {
+        let bit = if self.double_speed { 5 } else { 4 };
+        self.timer.read(0xFF04) & (1 << bit) != 0
+    }
+
+    /// Clocks the frame sequencer if the DIV bit fell since it was high
+    fn clock_frame_sequencer(&mut self, signal: bool) {
+        if signal && !self.frame_sequencer_signal() {
+            self.apu.clock_frame_sequencer();
+        }
+    }
+
+    fn handle_ppu_events(&mut self, events: PpuEvents) {
+        self.request_interrupt(events.interrupts);
+
+        // Start of an HBlank, where a pending HBlank DMA copies one block
+        if events.hblank && self.hdma.is_hblank_active() {
+            self.copy_hdma_block();
+        }
+    }
+
+    pub fn ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    /// Both banks on CGB, for the VRAM viewers
+    pub fn vram(&self) -> &[u8] {
+        &self.vram
+    }
+
+    pub fn oam(&self) -> &[u8] {
+        &self.oam
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut Ppu {
+        &mut self.ppu
+    }
+
+    pub fn apu(&self) -> &Apu {
+        &self.apu
+    }
+
+    pub fn apu_mut(&mut self) -> &mut Apu {
+        &mut self.apu
+    }
+
+    /// Presses or releases a button, from the frontend
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        if self.joypad.set_pressed(button, pressed) {
+            self.request_interrupt(INTERRUPT_JOYPAD);
+        }
+    }
+
+    /// Sets a bit of IF (FF0F)
+    fn request_interrupt(&mut self, bit: u8) {
+        self.io[0x0F] |= bit;
+    }
+
+    /// Called on STOP, returns whether KEY1 armed a speed switch
+    pub fn stop(&mut self) -> bool {
+        let signal = self.frame_sequencer_signal();
+        self.timer.reset_div();
+        self.clock_frame_sequencer(signal);
+
+        if !self.speed_switch_armed {
+            return false;
+        }
+
+        self.speed_switch_armed = false;
+        self.double_speed = !self.double_speed;
+        self.stall_cycles += SPEED_SWITCH_CYCLES;
+
+        true
+    }
+
+    fn copy_hdma_block(&mut self) {
+        let (source, destination) = self.hdma.next_block();
+
+        for i in 0..HDMA_BLOCK_SIZE {
+            let value = self.read(source.wrapping_add(i));
+            let offset = self.vram_offset(destination + i);
+            self.vram[offset] = value;
+        }
+
+        // The block takes the same time at both speeds, twice as many CPU cycles
+        self.stall_cycles += if self.double_speed {
+            HDMA_BLOCK_CYCLES * 2
+        } else {
+            HDMA_BLOCK_CYCLES
+        };
+    }
+
+    fn write_hdma(&mut self, address: u16, value: u8) {
+        match self.hdma.write(address, value) {
+            Some(HdmaRequest::General(blocks)) => {
+                for _ in 0..blocks {
+                    self.copy_hdma_block();
                 }
             }
+            Some(HdmaRequest::HBlank) | Some(HdmaRequest::Cancel) | None => {}
+        }
+    }
+
+    fn is_cgb(&self) -> bool {
+        self.model == Model::Cgb
+    }
+
+    fn vram_offset(&self, address: u16) -> usize {
+        self.vram_bank * 0x2000 + (address as usize & 0x1FFF)
+    }
+
+    fn wram_offset(&self, address: u16) -> usize {
+        match address & 0x1FFF {
+            offset @ 0x0000..=0x0FFF => offset as usize,
+            // Bank 0 selects bank 1
+            offset => self.wram_bank.max(1) * 0x1000 + (offset as usize & 0x0FFF),
+        }
+    }
+
+    fn step_oam_dma(&mut self) {
+        if let Some((source, index)) = self.oam_dma.tick() {
+            let value = self.read(source);
+            self.oam[index] = value;
+            self.oam_dma.set_last_value(value);
+        }
+    }
+
+    /// Whether the CPU is locked out of an address by a running OAM DMA
+    ///
+    /// OAM can't be accessed at all, and the bus the DMA reads from returns
+    /// whatever byte is being copied. HRAM and I/O stay usable, which is why
+    /// games run their DMA routine from HRAM.
+    fn dma_conflict(&self, address: u16) -> Option<u8> {
+        if !self.oam_dma.is_active() {
+            return None;
+        }
+
+        match address {
+            0xFE00..=0xFEFF => Some(0xFF),
+            _ if Bus::of(address).is_some() && Bus::of(address) == self.oam_dma.bus() => {
+                Some(self.oam_dma.last_value())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the CPU is locked out of an address by the PPU
+    ///
+    /// OAM is in use during OAM scan and drawing, VRAM during drawing.
+    fn ppu_conflict(&self, address: u16) -> Option<u8> {
+        match (address, self.ppu.mode()) {
+            (0x8000..=0x9FFF, Mode::Drawing) => Some(0xFF),
+            (0xFE00..=0xFE9F, Mode::OamScan | Mode::Drawing) => Some(0xFF),
+            _ => None,
+        }
+    }
+
+    /// Reads memory as the DMA sees it, without any conflict
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            // ROM Bank
+            // Fixed until 0x3FFF, the boot ROM overlays the first 256 bytes
+            0x0000..=0x00FF if self.is_booting => GAMEBOY_CLASSIC[address as usize],
+            0x0000..=0x7FFF => self.cartridge.get(address),
 
             // Video RAM
-            0x8000..=0x9FFF => 0x00,
+            0x8000..=0x9FFF => self.vram[self.vram_offset(address)],
 
             // Switchable RAM Bank
-            0xA000..=0xBFFF => 0x00,
+            0xA000..=0xBFFF => self.cartridge.get(address),
 
             // Internal RAM (WRAM)
-            0xC000..=0xDFFF => 0x00,
+            0xC000..=0xDFFF => self.wram[self.wram_offset(address)],
 
             // Echo of 8kB Internal RAM
-            0xE000..=0xFDFF => 0x00,
+            0xE000..=0xFDFF => self.wram[self.wram_offset(address)],
 
             // Sprite Attrib Memory (OAM)
-            0xFE00..=0xFE9F => 0x00,
+            0xFE00..=0xFE9F => self.oam[address as usize - 0xFE00],
 
             // Empty but unusable for I/O
             0xFEA0..=0xFEFF => 0x00,
 
             // I/O Registers
             // Unusable from 0xFF4C
-            0xFF00..=0xFF7F => 0x00,
+            0xFF00 => self.joypad.read(),
+            0xFF04..=0xFF07 => self.timer.read(address),
+            // The upper bits of IF don't exist
+            0xFF0F => 0xE0 | self.io[0x0F],
+            0xFF10..=0xFF3F => self.apu.read(address),
+            0xFF46 => self.oam_dma.register(),
+            0xFF40..=0xFF4B => self.ppu.read(address),
+            0xFF68..=0xFF6C if self.is_cgb() => self.ppu.read(address),
+            0xFF4D | 0xFF4F | 0xFF51..=0xFF55 | 0xFF70 if !self.is_cgb() => 0xFF,
+            0xFF4D => ((self.double_speed as u8) << 7) | 0x7E | self.speed_switch_armed as u8,
+            0xFF4F => 0xFE | self.vram_bank as u8,
+            0xFF51..=0xFF55 => self.hdma.read(address),
+            0xFF70 => 0xF8 | self.wram_bank as u8,
+            0xFF01..=0xFF7F => self.io[address as usize - 0xFF00],
 
             // High RAM
-            0xFF80..=0xFFFE => 0x00,
+            0xFF80..=0xFFFE => self.hram[address as usize - 0xFF80],
 
             // Interrupt Enable Register
-            0xFFFF => 0x00,
+            0xFFFF => self.interrupt_enable,
         }
     }
 
-    fn set(&mut self, address: u16, value: u8) {
+    fn write(&mut self, address: u16, value: u8) {
         match address {
             // ROM Bank
             // Fixed until 0x3FFF
             0x0000..=0x7FFF => self.cartridge.set(address, value),
 
             // Video RAM
-            0x8000..=0x9FFF => {}
+            0x8000..=0x9FFF => {
+                let offset = self.vram_offset(address);
+                self.vram[offset] = value;
+            }
 
             // Switchable RAM Bank
-            0xA000..=0xBFFF => {}
+            0xA000..=0xBFFF => self.cartridge.set(address, value),
 
-            // Internal RAM (WRAM)
-            0xC000..=0xDFFF => {}
-
-            // Echo of 8kB Internal RAM
-            0xE000..=0xFDFF => {}
+            // Internal RAM (WRAM) and its echo
+            0xC000..=0xFDFF => {
+                let offset = self.wram_offset(address);
+                self.wram[offset] = value;
+            }
 
             // Sprite Attrib Memory (OAM)
-            0xFE00..=0xFE9F => {}
+            0xFE00..=0xFE9F => self.oam[address as usize - 0xFE00] = value,
 
             // Empty but unusable for I/O
             0xFEA0..=0xFEFF => {}
 
             // I/O Registers
             // Unusable from 0xFF4C
-            0xFF00..=0xFF7F => {}
+            0xFF00 => {
+                if self.joypad.write(value) {
+                    self.request_interrupt(INTERRUPT_JOYPAD);
+                }
+            }
+            // Resetting DIV can clock the frame sequencer
+            0xFF04..=0xFF07 => {
+                let signal = self.frame_sequencer_signal();
+                self.timer.write(address, value);
+                self.clock_frame_sequencer(signal);
+            }
+            0xFF0F => self.io[0x0F] = value & 0x1F,
+            0xFF10..=0xFF3F => self.apu.write(address, value),
+            0xFF46 => self.oam_dma.start(value),
+            0xFF40..=0xFF4B | 0xFF68..=0xFF6C => {
+                if address < 0xFF68 || self.is_cgb() {
+                    let events = self.ppu.write(address, value);
+                    self.handle_ppu_events(events);
+                }
+            }
+            // Any write unmaps the boot ROM for good
+            0xFF50 => {
+                if value != 0 {
+                    self.is_booting = false;
+                }
+            }
+            0xFF4D | 0xFF4F | 0xFF51..=0xFF55 | 0xFF70 if !self.is_cgb() => {}
+            0xFF4D => self.speed_switch_armed = value & 0x01 != 0,
+            0xFF4F => self.vram_bank = (value & 0x01) as usize,
+            0xFF51..=0xFF55 => self.write_hdma(address, value),
+            0xFF70 => self.wram_bank = (value & 0x07) as usize,
+            0xFF01..=0xFF7F => self.io[address as usize - 0xFF00] = value,
 
             // High RAM
-            0xFF80..=0xFFFE => {}
+            0xFF80..=0xFFFE => self.hram[address as usize - 0xFF80] = value,
 
             // Interrupt Enable Register
-            0xFFFF => {}
+            0xFFFF => self.interrupt_enable = value,
+        }
+    }
+}
+
+impl AddressSpace for Mmu {
+    fn get(&self, address: u16) -> u8 {
+        self.dma_conflict(address)
+            .or_else(|| self.ppu_conflict(address))
+            .unwrap_or_else(|| self.read(address))
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        if self.dma_conflict(address).is_none() && self.ppu_conflict(address).is_none() {
+            self.write(address, value);
         }
     }
 
@@ -153,3 +489,117 @@ impl AddressSpace for Mmu {
         self.set(address, l);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mmu(model: Model) -> Mmu {
+        let mut mmu = Mmu::new(Cartridge::from_rom(vec![0; 0x8000]), model);
+        mmu.power_up();
+        mmu
+    }
+
+    #[test]
+    pub fn test_vram_bank() {
+        let mut mmu = mmu(Model::Cgb);
+        assert_eq!(0xFE, mmu.read(0xFF4F));
+
+        mmu.write(0xFF4F, 0x01);
+        assert_eq!(0xFF, mmu.read(0xFF4F));
+        mmu.write(0x8000, 0x12);
+        assert_eq!(0x12, mmu.vram()[0x2000]);
+
+        // Only bit 0 counts
+        mmu.write(0xFF4F, 0xFE);
+        assert_eq!(0xFE, mmu.read(0xFF4F));
+        assert_eq!(0x00, mmu.read(0x8000));
+        mmu.write(0xFF4F, 0x01);
+        assert_eq!(0x12, mmu.read(0x8000));
+    }
+
+    #[test]
+    pub fn test_wram_bank() {
+        let mut mmu = mmu(Model::Cgb);
+        assert_eq!(0xF9, mmu.read(0xFF70));
+
+        mmu.write(0xD000, 0x11);
+        // Bank 0 maps bank 1
+        mmu.write(0xFF70, 0x00);
+        assert_eq!(0xF8, mmu.read(0xFF70));
+        assert_eq!(0x11, mmu.read(0xD000));
+
+        mmu.write(0xFF70, 0x07);
+        assert_eq!(0x00, mmu.read(0xD000));
+        // The echo follows the bank too, C000-CFFF stays
+        mmu.write(0xDDFF, 0x77);
+        assert_eq!(0x77, mmu.read(0xFDFF));
+        mmu.write(0xF000, 0x70);
+        assert_eq!(0x70, mmu.read(0xD000));
+        mmu.write(0xC000, 0x0C);
+
+        mmu.write(0xFF70, 0x01);
+        assert_eq!(0x11, mmu.read(0xF000));
+        assert_eq!(0x0C, mmu.read(0xE000));
+    }
+
+    #[test]
+    pub fn test_banks_on_dmg() {
+        let mut mmu = mmu(Model::Dmg);
+
+        mmu.write(0x8000, 0x12);
+        mmu.write(0xD000, 0x11);
+        mmu.write(0xFF4F, 0x01);
+        mmu.write(0xFF70, 0x02);
+
+        assert_eq!(0xFF, mmu.read(0xFF4F));
+        assert_eq!(0xFF, mmu.read(0xFF70));
+        assert_eq!(0x12, mmu.read(0x8000));
+        assert_eq!(0x11, mmu.read(0xD000));
+        assert_eq!(0x11, mmu.read(0xF000));
+    }
+
+    #[test]
+    pub fn test_speed_switch() {
+        let mut dmg = mmu(Model::Dmg);
+        dmg.write(0xFF4D, 0x01);
+        assert_eq!(0xFF, dmg.read(0xFF4D));
+        assert!(!dmg.stop());
+
+        let mut mmu = mmu(Model::Cgb);
+        assert_eq!(0x7E, mmu.read(0xFF4D));
+
+        // Nothing happens without arming the switch first
+        assert!(!mmu.stop());
+        mmu.write(0xFF4D, 0x01);
+        assert_eq!(0x7F, mmu.read(0xFF4D));
+
+        assert!(mmu.stop());
+        assert_eq!(0xFE, mmu.read(0xFF4D));
+        // The CPU is held while the clock settles
+        assert_eq!(2 + SPEED_SWITCH_CYCLES / 2, mmu.step(4));
+
+        mmu.write(0xFF4D, 0x01);
+        assert_eq!(0xFF, mmu.read(0xFF4D));
+        assert!(mmu.stop());
+        assert_eq!(0x7E, mmu.read(0xFF4D));
+    }
+
+    #[test]
+    pub fn test_double_speed_rates() {
+        // DIV goes up every 256 CPU cycles, the PPU gets half as many in double speed
+        for (double_speed, dots) in [(false, 256), (true, 128)] {
+            let mut mmu = mmu(Model::Cgb);
+            if double_speed {
+                mmu.write(0xFF4D, 0x01);
+                mmu.stop();
+                mmu.step(4);
+            }
+            mmu.write(0xFF04, 0x00);
+
+            let elapsed: u32 = (0..64).map(|_| mmu.step(4)).sum();
+            assert_eq!(0x01, mmu.read(0xFF04));
+            assert_eq!(dots, elapsed);
+        }
+    }
+}
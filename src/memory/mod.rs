@@ -1,10 +1,35 @@
 mod address_space;
+mod archive;
 mod cartridge;
+mod dma;
+mod header;
+mod joypad;
+mod mbc;
 mod mmu;
+mod model;
+mod patch;
+mod save;
+mod timer;
 
 pub use address_space::AddressSpace;
-pub use cartridge::Cartridge;
+pub use cartridge::{Cartridge, LoadOptions};
+pub use header::{
+    cartridge_type_name, compute_global_checksum, compute_header_checksum, Header, HeaderFix,
+};
+pub use joypad::Button;
+pub use mbc::{ImageSource, PnmImage, TestPattern};
 pub use mmu::Mmu;
+pub use model::Model;
+pub use save::{SaveFile, SaveManager};
+
+// IF and IE bits
+pub const INTERRUPT_VBLANK: u8 = 0x01;
+pub const INTERRUPT_STAT: u8 = 0x02;
+pub const INTERRUPT_TIMER: u8 = 0x04;
+pub const INTERRUPT_JOYPAD: u8 = 0x10;
+
+// Well over the largest cartridge, a patch or archive producing more is broken
+const MAX_ROM_SIZE: usize = 0x400_0000;
 
 /// Converts two bytes to a single word
 pub fn bytes_to_word(h: u8, l: u8) -> u16 {
@@ -19,8 +44,9 @@ pub fn word_to_bytes(w: u16) -> (u8, u8) {
 }
 
 // TODO Move tests somewhere else
+#[cfg(test)]
 mod tests {
-    
+    use super::*;
 
     #[test]
     pub fn test_bytes_to_word() {
@@ -0,0 +1,41 @@
+use std::{fmt::Display, str::FromStr};
+
+use super::{cartridge::Cartridge, header::Header};
+
+/// Console being emulated
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Model {
+    Dmg,
+    Cgb,
+}
+
+impl Model {
+    /// CGB for games flagged as enhanced or CGB only, DMG otherwise
+    pub fn detect(cartridge: &Cartridge) -> Self {
+        match Header::parse(cartridge.rom()) {
+            Some(header) if header.is_cgb_aware() => Model::Cgb,
+            _ => Model::Dmg,
+        }
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dmg" => Ok(Model::Dmg),
+            "cgb" => Ok(Model::Cgb),
+            _ => Err(format!("Unknown model {}, expected one of: dmg, cgb", s)),
+        }
+    }
+}
+
+impl Display for Model {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Model::Dmg => write!(f, "dmg"),
+            Model::Cgb => write!(f, "cgb"),
+        }
+    }
+}
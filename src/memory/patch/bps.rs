@@ -0,0 +1,195 @@
+use crate::hash::crc32;
+
+use super::{checked_add, read_footer, read_number, read_size, verify_patch, PatchError};
+
+pub const MAGIC: &[u8] = b"BPS1";
+
+const SOURCE_READ: usize = 0;
+const TARGET_READ: usize = 1;
+const SOURCE_COPY: usize = 2;
+const TARGET_COPY: usize = 3;
+
+/// BPS: a stream of copy actions from the source, the patch or the output itself
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let (source_crc, target_crc, patch_crc) = read_footer(patch)?;
+    verify_patch(patch, patch_crc)?;
+
+    let actual = crc32(rom);
+    if actual != source_crc {
+        return Err(PatchError::SourceMismatch {
+            expected: source_crc,
+            actual,
+        });
+    }
+
+    let mut pos = MAGIC.len();
+    let source_size = read_size(patch, &mut pos)?;
+    let target_size = read_size(patch, &mut pos)?;
+    let metadata_size = read_number(patch, &mut pos)?;
+    pos = checked_add(pos, metadata_size)?;
+
+    if rom.len() != source_size {
+        return Err(PatchError::Invalid("ROM size doesn't match the patch"));
+    }
+
+    let end = patch.len() - 12;
+    let mut output: Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_offset: isize = 0;
+    let mut target_offset: isize = 0;
+
+    while pos < end {
+        let action = read_number(patch, &mut pos)?;
+        let length = (action >> 2) + 1;
+
+        if checked_add(output.len(), length)? > target_size {
+            return Err(PatchError::Invalid("output larger than the target size"));
+        }
+
+        match action & 0x03 {
+            SOURCE_READ => {
+                let start = output.len();
+                let bytes = rom
+                    .get(start..start + length)
+                    .ok_or(PatchError::Invalid("source read out of bounds"))?;
+                output.extend_from_slice(bytes);
+            }
+            TARGET_READ => {
+                let bytes = patch[..end]
+                    .get(pos..checked_add(pos, length)?)
+                    .ok_or(PatchError::Truncated)?;
+                output.extend_from_slice(bytes);
+                pos += length;
+            }
+            SOURCE_COPY => {
+                source_offset = move_offset(source_offset, patch, &mut pos)?;
+                let start = usize::try_from(source_offset)
+                    .map_err(|_| PatchError::Invalid("source copy out of bounds"))?;
+                let bytes = rom
+                    .get(start..checked_add(start, length)?)
+                    .ok_or(PatchError::Invalid("source copy out of bounds"))?;
+                output.extend_from_slice(bytes);
+                source_offset += length as isize;
+            }
+            TARGET_COPY => {
+                target_offset = move_offset(target_offset, patch, &mut pos)?;
+
+                // Copied byte by byte, the ranges may overlap
+                for _ in 0..length {
+                    let byte = usize::try_from(target_offset)
+                        .ok()
+                        .and_then(|offset| output.get(offset).copied())
+                        .ok_or(PatchError::Invalid("target copy out of bounds"))?;
+                    output.push(byte);
+                    target_offset += 1;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if output.len() != target_size {
+        return Err(PatchError::Invalid("output smaller than the target size"));
+    }
+
+    let actual = crc32(&output);
+    if actual != target_crc {
+        return Err(PatchError::TargetMismatch {
+            expected: target_crc,
+            actual,
+        });
+    }
+
+    Ok(output)
+}
+
+/// Applies a signed relative offset, the sign being stored in the lowest bit
+fn move_offset(current: isize, patch: &[u8], pos: &mut usize) -> Result<isize, PatchError> {
+    let data = read_number(patch, pos)?;
+    let offset = (data >> 1) as isize;
+    let offset = if data & 1 != 0 { -offset } else { offset };
+
+    current
+        .checked_add(offset)
+        .ok_or(PatchError::Invalid("offset out of range"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(mut value: usize) -> Vec<u8> {
+        let mut bytes = vec![];
+        loop {
+            let x = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(0x80 | x);
+                return bytes;
+            }
+            bytes.push(x);
+            value -= 1;
+        }
+    }
+
+    fn patch(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+        let mut patch = MAGIC.to_vec();
+        patch.extend(number(source.len()));
+        patch.extend(number(target.len()));
+        patch.extend(number(0));
+        patch.extend_from_slice(actions);
+        patch.extend(crc32(source).to_le_bytes());
+        patch.extend(crc32(target).to_le_bytes());
+        patch.extend(crc32(&patch).to_le_bytes());
+        patch
+    }
+
+    #[test]
+    pub fn test_apply() {
+        let source = b"ABCDEFGH";
+        let target = b"ABCDxyxyxyGH";
+
+        let mut actions = vec![];
+        // Source read of 4 bytes
+        actions.extend(number((3 << 2) | SOURCE_READ));
+        // Target read of "xy"
+        actions.extend(number((1 << 2) | TARGET_READ));
+        actions.extend_from_slice(b"xy");
+        // Target copy of 4 bytes from offset 4, overlapping
+        actions.extend(number((3 << 2) | TARGET_COPY));
+        actions.extend(number(4 << 1));
+        // Source copy of "GH" from offset 6
+        actions.extend(number((1 << 2) | SOURCE_COPY));
+        actions.extend(number(6 << 1));
+
+        let patch = patch(source, target, &actions);
+
+        assert_eq!(target.to_vec(), apply(source, &patch).unwrap());
+        assert!(matches!(
+            apply(b"ABCDEFGX", &patch),
+            Err(PatchError::SourceMismatch { .. })
+        ));
+    }
+
+    #[test]
+    pub fn test_overlong_number() {
+        let source = b"ABCD";
+
+        // No byte ever ends the number
+        let mut actions = vec![0x7F; 16];
+        actions.push(0x80);
+        let unterminated = patch(source, source, &actions);
+        assert!(matches!(
+            apply(source, &unterminated),
+            Err(PatchError::Invalid(_))
+        ));
+
+        // Ends, but past what fits in a usize
+        let mut actions = vec![0x7F; 9];
+        actions.push(0xFF);
+        let too_large = patch(source, source, &actions);
+        assert!(matches!(
+            apply(source, &too_large),
+            Err(PatchError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,66 @@
+use super::PatchError;
+
+pub const MAGIC: &[u8] = b"PATCH";
+const END: &[u8] = b"EOF";
+
+/// IPS: records of 24-bit offset, 16-bit size and data, sizes of 0 being runs
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut output = rom.to_vec();
+    let mut pos = MAGIC.len();
+
+    let mut read = |len: usize| -> Result<&[u8], PatchError> {
+        let bytes = patch.get(pos..pos + len).ok_or(PatchError::Truncated)?;
+        pos += len;
+        Ok(bytes)
+    };
+
+    loop {
+        let offset = read(3)?;
+        if offset == END {
+            break;
+        }
+
+        let offset =
+            ((offset[0] as usize) << 16) | ((offset[1] as usize) << 8) | offset[2] as usize;
+        let size = read(2)?;
+        let size = ((size[0] as usize) << 8) | size[1] as usize;
+
+        let data = if size == 0 {
+            let run = read(3)?;
+            let count = ((run[0] as usize) << 8) | run[1] as usize;
+            vec![run[2]; count]
+        } else {
+            read(size)?.to_vec()
+        };
+
+        if output.len() < offset + data.len() {
+            output.resize(offset + data.len(), 0);
+        }
+        output[offset..offset + data.len()].copy_from_slice(&data);
+    }
+
+    // Optional truncation extension
+    if let Ok(size) = read(3) {
+        let size = ((size[0] as usize) << 16) | ((size[1] as usize) << 8) | size[2] as usize;
+        output.truncate(size);
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_records_and_runs() {
+        let mut patch = MAGIC.to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB]);
+        patch.extend_from_slice(&[0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x03, 0xCC]);
+        patch.extend_from_slice(END);
+
+        let output = apply(&[0; 4], &patch).unwrap();
+
+        assert_eq!(vec![0x00, 0xAA, 0xBB, 0x00, 0x00, 0xCC, 0xCC, 0xCC], output);
+    }
+}
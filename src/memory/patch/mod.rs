@@ -0,0 +1,155 @@
+mod bps;
+mod ips;
+mod ups;
+
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum PatchError {
+    Io(io::Error),
+    UnknownFormat,
+    Truncated,
+    Invalid(&'static str),
+    SourceMismatch { expected: u32, actual: u32 },
+    TargetMismatch { expected: u32, actual: u32 },
+    PatchMismatch { expected: u32, actual: u32 },
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchError::Io(e) => write!(f, "{}", e),
+            PatchError::UnknownFormat => write!(f, "not an IPS, BPS or UPS patch"),
+            PatchError::Truncated => write!(f, "patch is truncated"),
+            PatchError::Invalid(reason) => write!(f, "invalid patch: {}", reason),
+            PatchError::SourceMismatch { expected, actual } => write!(
+                f,
+                "patch expects a ROM with CRC32 {:08X}, got {:08X}",
+                expected, actual
+            ),
+            PatchError::TargetMismatch { expected, actual } => write!(
+                f,
+                "patched ROM has CRC32 {:08X} instead of {:08X}",
+                actual, expected
+            ),
+            PatchError::PatchMismatch { expected, actual } => write!(
+                f,
+                "patch file is corrupted, CRC32 {:08X} instead of {:08X}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for PatchError {
+    fn from(e: io::Error) -> Self {
+        PatchError::Io(e)
+    }
+}
+
+const EXTENSIONS: [&str; 3] = ["ips", "bps", "ups"];
+
+/// Patch with the ROM's name next to it, if any
+pub fn find_sibling(rom_path: &Path) -> Option<PathBuf> {
+    EXTENSIONS
+        .iter()
+        .map(|extension| rom_path.with_extension(extension))
+        .find(|path| path.exists())
+}
+
+/// Reads a patch file and applies it to the ROM in memory
+pub fn apply_file(rom: &[u8], patch_path: &Path) -> Result<Vec<u8>, PatchError> {
+    let patch = fs::read(patch_path)?;
+    apply(rom, &patch)
+}
+
+/// Applies a patch, its format being recognised from its magic
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.starts_with(ips::MAGIC) {
+        ips::apply(rom, patch)
+    } else if patch.starts_with(bps::MAGIC) {
+        bps::apply(rom, patch)
+    } else if patch.starts_with(ups::MAGIC) {
+        ups::apply(rom, patch)
+    } else {
+        Err(PatchError::UnknownFormat)
+    }
+}
+
+// Enough 7-bit groups for a 64-bit number
+const MAX_NUMBER_BYTES: usize = 10;
+
+/// Variable-length integer shared by BPS and UPS
+fn read_number(patch: &[u8], pos: &mut usize) -> Result<usize, PatchError> {
+    let mut value: usize = 0;
+    let mut shift: usize = 1;
+
+    for _ in 0..MAX_NUMBER_BYTES {
+        let byte = *patch.get(*pos).ok_or(PatchError::Truncated)?;
+        *pos += 1;
+
+        let term = ((byte & 0x7F) as usize)
+            .checked_mul(shift)
+            .ok_or(PatchError::Invalid("number too large"))?;
+        value = value
+            .checked_add(term)
+            .ok_or(PatchError::Invalid("number too large"))?;
+
+        if byte & 0x80 != 0 {
+            return Ok(value);
+        }
+
+        shift = shift
+            .checked_mul(0x80)
+            .ok_or(PatchError::Invalid("number too large"))?;
+        value = value
+            .checked_add(shift)
+            .ok_or(PatchError::Invalid("number too large"))?;
+    }
+
+    Err(PatchError::Invalid("number too large"))
+}
+
+/// Sum of two sizes or offsets read from a patch, which may be anything
+fn checked_add(a: usize, b: usize) -> Result<usize, PatchError> {
+    a.checked_add(b)
+        .ok_or(PatchError::Invalid("offset out of range"))
+}
+
+/// Source or target size, before allocating anything for it
+fn read_size(patch: &[u8], pos: &mut usize) -> Result<usize, PatchError> {
+    let size = read_number(patch, pos)?;
+
+    if size > super::MAX_ROM_SIZE {
+        return Err(PatchError::Invalid("ROM size too large"));
+    }
+
+    Ok(size)
+}
+
+/// Source, target and patch CRC32s ending BPS and UPS files
+fn read_footer(patch: &[u8]) -> Result<(u32, u32, u32), PatchError> {
+    if patch.len() < 12 {
+        return Err(PatchError::Truncated);
+    }
+
+    let footer = &patch[patch.len() - 12..];
+    let read = |i: usize| u32::from_le_bytes(footer[i..i + 4].try_into().unwrap());
+
+    Ok((read(0), read(4), read(8)))
+}
+
+/// Checks the CRC32 of the patch itself, which covers everything but its last field
+fn verify_patch(patch: &[u8], expected: u32) -> Result<(), PatchError> {
+    let actual = crate::hash::crc32(&patch[..patch.len() - 4]);
+
+    if actual != expected {
+        return Err(PatchError::PatchMismatch { expected, actual });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use crate::hash::crc32;
+
+use super::{checked_add, read_footer, read_number, read_size, verify_patch, PatchError};
+
+pub const MAGIC: &[u8] = b"UPS1";
+
+/// UPS: runs of bytes XORed with the source, separated by skipped lengths
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let (source_crc, target_crc, patch_crc) = read_footer(patch)?;
+    verify_patch(patch, patch_crc)?;
+
+    let mut pos = MAGIC.len();
+    let source_size = read_size(patch, &mut pos)?;
+    let target_size = read_size(patch, &mut pos)?;
+
+    // UPS patches apply both ways, the CRCs tell the direction
+    let actual = crc32(rom);
+    let (expected_size, output_size, expected_crc) = if actual == source_crc {
+        (source_size, target_size, target_crc)
+    } else if actual == target_crc {
+        (target_size, source_size, source_crc)
+    } else {
+        return Err(PatchError::SourceMismatch {
+            expected: source_crc,
+            actual,
+        });
+    };
+
+    if rom.len() != expected_size {
+        return Err(PatchError::Invalid("ROM size doesn't match the patch"));
+    }
+
+    let mut output = rom.to_vec();
+    output.resize(output_size, 0);
+
+    let end = patch.len() - 12;
+    let mut out = 0;
+
+    while pos < end {
+        out = checked_add(out, read_number(patch, &mut pos)?)?;
+
+        loop {
+            let byte = *patch[..end].get(pos).ok_or(PatchError::Truncated)?;
+            pos += 1;
+
+            if out < output.len() {
+                output[out] ^= byte;
+            }
+            out = checked_add(out, 1)?;
+
+            if byte == 0 {
+                break;
+            }
+        }
+    }
+
+    let actual = crc32(&output);
+    if actual != expected_crc {
+        return Err(PatchError::TargetMismatch {
+            expected: expected_crc,
+            actual,
+        });
+    }
+
+    Ok(output)
+}
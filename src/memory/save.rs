@@ -0,0 +1,224 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use super::cartridge::Cartridge;
+
+// Number of previous sessions kept next to a save, as `<save>.1` to `<save>.3`
+const BACKUPS: usize = 3;
+
+// Saves are written back to disk about once per emulated second
+const AUTOSAVE_INTERVAL: u32 = 4194304;
+
+/// File written atomically, rotating backups on the first write of a session
+pub struct SaveFile {
+    path: PathBuf,
+    last_written: Option<Vec<u8>>,
+    rotated: bool,
+}
+
+impl SaveFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            last_written: None,
+            rotated: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn backup_path(&self, index: usize) -> PathBuf {
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    /// Reads the file, falling back to the newest backup if it went missing
+    pub fn read(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let candidates = std::iter::once(self.path.clone())
+            .chain((1..=BACKUPS).map(|index| self.backup_path(index)));
+
+        for candidate in candidates {
+            match fs::read(&candidate) {
+                Ok(data) => {
+                    if candidate != self.path {
+                        println!("Save missing, restored {}", candidate.display());
+                    }
+                    self.last_written = Some(data.clone());
+                    return Ok(Some(data));
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Writes the data unless it is already on disk
+    ///
+    /// The data goes to a temporary file first, which is renamed over the save
+    /// once synced, so the previous save stays intact if anything fails.
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.last_written.as_deref() == Some(data) {
+            return Ok(());
+        }
+
+        let mut temp_name = OsString::from(self.path.as_os_str());
+        temp_name.push(".tmp");
+        let temp_path = PathBuf::from(temp_name);
+
+        let mut file = File::create(&temp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        drop(file);
+
+        if !self.rotated {
+            self.rotate_backups()?;
+            self.rotated = true;
+        }
+
+        fs::rename(&temp_path, &self.path)?;
+        self.last_written = Some(data.to_vec());
+
+        Ok(())
+    }
+
+    fn rotate_backups(&self) -> io::Result<()> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+
+        for index in (1..BACKUPS).rev() {
+            let from = self.backup_path(index);
+            if from.exists() {
+                fs::rename(&from, self.backup_path(index + 1))?;
+            }
+        }
+
+        // Copied rather than moved so a save is always present
+        fs::copy(&self.path, self.backup_path(1))?;
+
+        Ok(())
+    }
+}
+
+/// Keeps the battery-backed RAM and the flash of a cartridge on disk
+pub struct SaveManager {
+    ram_file: Option<SaveFile>,
+    flash_file: Option<SaveFile>,
+    map_path: PathBuf,
+    cycles_since_autosave: u32,
+}
+
+impl SaveManager {
+    /// Picks `<rom>.sav`, or `<rom>.srm` if only that one exists
+    pub fn new(rom_path: &Path, cartridge: &Cartridge) -> Self {
+        let sav_path = rom_path.with_extension("sav");
+        let srm_path = rom_path.with_extension("srm");
+
+        let ram_path = if !sav_path.exists() && srm_path.exists() {
+            srm_path
+        } else {
+            sav_path
+        };
+
+        let ram_file = cartridge.has_battery().then(|| SaveFile::new(ram_path));
+        let flash_file = cartridge
+            .flash()
+            .map(|_| SaveFile::new(rom_path.with_extension("flash")));
+
+        Self {
+            ram_file,
+            flash_file,
+            map_path: rom_path.with_extension("map"),
+            cycles_since_autosave: 0,
+        }
+    }
+
+    pub fn load(&mut self, cartridge: &mut Cartridge) -> io::Result<()> {
+        if let Some(file) = &mut self.ram_file {
+            if let Some(data) = file.read()? {
+                println!("Loaded save {}", file.path().display());
+                cartridge.load_ram(&data);
+            }
+        }
+
+        if let Some(file) = &mut self.flash_file {
+            // The hidden mapping sector of a GB Memory dump comes separately
+            if let Ok(map) = fs::read(&self.map_path) {
+                cartridge.load_flash(&map);
+            }
+            if let Some(image) = file.read()? {
+                cartridge.load_flash(&image);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes everything that changed since the last flush
+    pub fn flush(&mut self, cartridge: &mut Cartridge) -> io::Result<()> {
+        if let Some(file) = &mut self.ram_file {
+            file.write(cartridge.ram())?;
+        }
+
+        if let Some(file) = &mut self.flash_file {
+            if cartridge.take_flash_dirty() {
+                if let Some(image) = cartridge.flash() {
+                    file.write(&image)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flushes periodically, based on emulated time
+    ///
+    /// A failed autosave is tried again at the next interval.
+    pub fn step(&mut self, cartridge: &mut Cartridge, cycles: u32) -> io::Result<()> {
+        self.cycles_since_autosave += cycles;
+
+        if self.cycles_since_autosave >= AUTOSAVE_INTERVAL {
+            self.cycles_since_autosave = 0;
+            self.flush(cartridge)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_write_keeps_backup() {
+        let dir = std::env::temp_dir().join(format!("sabitaboy-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.sav");
+        fs::write(&path, [1, 2, 3]).unwrap();
+
+        let mut file = SaveFile::new(path.clone());
+        file.write(&[4, 5, 6]).unwrap();
+        file.write(&[7, 8, 9]).unwrap();
+
+        assert_eq!(vec![7, 8, 9], fs::read(&path).unwrap());
+        // Only the previous session is backed up
+        assert_eq!(vec![1, 2, 3], fs::read(dir.join("game.sav.1")).unwrap());
+        assert!(!dir.join("game.sav.tmp").exists());
+
+        fs::remove_file(&path).unwrap();
+        let mut file = SaveFile::new(path);
+        assert_eq!(Some(vec![1, 2, 3]), file.read().unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
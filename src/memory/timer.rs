@@ -0,0 +1,217 @@
+/// DIV, TIMA, TMA and TAC (FF04-FF07), driven by a 16-bit system counter
+///
+/// DIV is the upper byte of the counter. TIMA counts the falling edges of the
+/// counter bit selected by TAC, ANDed with the enable bit, which is why
+/// resetting DIV or changing TAC can increment it.
+pub struct Timer {
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    // TIMA overflowed, TMA gets loaded on the next M-cycle
+    overflow: bool,
+    // TMA is being loaded during this M-cycle
+    reloading: bool,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self {
+            counter: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+            overflow: false,
+            reloading: false,
+        }
+    }
+
+    fn signal(&self) -> bool {
+        let bit = match self.tac & 0x03 {
+            0x00 => 9,
+            0x01 => 3,
+            0x02 => 5,
+            _ => 7,
+        };
+
+        self.tac & 0x04 != 0 && self.counter & (1 << bit) != 0
+    }
+
+    fn increment_tima(&mut self) {
+        let (tima, overflow) = self.tima.overflowing_add(1);
+        self.tima = tima;
+
+        // TIMA reads 0x00 for one M-cycle before the reload
+        if overflow {
+            self.overflow = true;
+        }
+    }
+
+    /// Advances one M-cycle, returning whether the timer interrupt is requested
+    pub fn tick(&mut self) -> bool {
+        self.reloading = false;
+
+        let interrupt = self.overflow;
+        if self.overflow {
+            self.overflow = false;
+            self.tima = self.tma;
+            self.reloading = true;
+        }
+
+        let before = self.signal();
+        self.counter = self.counter.wrapping_add(4);
+        if before && !self.signal() {
+            self.increment_tima();
+        }
+
+        interrupt
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF04 => (self.counter >> 8) as u8,
+            0xFF05 => self.tima,
+            0xFF06 => self.tma,
+            _ => 0xF8 | self.tac,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        let before = self.signal();
+
+        match address {
+            0xFF04 => self.reset_div(),
+            0xFF05 => {
+                // The reload wins over a write in the same cycle
+                if !self.reloading {
+                    self.tima = value;
+                    // Writing during the overflow cycle cancels the reload and interrupt
+                    self.overflow = false;
+                }
+            }
+            0xFF06 => {
+                self.tma = value;
+                if self.reloading {
+                    self.tima = value;
+                }
+            }
+            _ => {
+                self.tac = value & 0x07;
+                if before && !self.signal() {
+                    self.increment_tima();
+                }
+            }
+        }
+    }
+
+    /// Clears the counter, as any DIV write or STOP does
+    pub fn reset_div(&mut self) {
+        let before = self.signal();
+        self.counter = 0;
+
+        if before {
+            self.increment_tima();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timer(tac: u8) -> Timer {
+        let mut timer = Timer::new();
+        timer.write(0xFF07, tac);
+        timer
+    }
+
+    #[test]
+    pub fn test_div_increments_every_64_cycles() {
+        let mut timer = Timer::new();
+
+        for _ in 0..63 {
+            timer.tick();
+        }
+        assert_eq!(0x00, timer.read(0xFF04));
+
+        timer.tick();
+        assert_eq!(0x01, timer.read(0xFF04));
+
+        timer.write(0xFF04, 0x42);
+        assert_eq!(0x00, timer.read(0xFF04));
+    }
+
+    #[test]
+    pub fn test_tima_rates() {
+        // M-cycles per increment for each TAC clock select
+        for (tac, period) in [(0x04, 256), (0x05, 4), (0x06, 16), (0x07, 64)] {
+            let mut timer = timer(tac);
+
+            for _ in 0..period - 1 {
+                timer.tick();
+            }
+            assert_eq!(0x00, timer.read(0xFF05));
+
+            timer.tick();
+            assert_eq!(0x01, timer.read(0xFF05));
+        }
+    }
+
+    #[test]
+    pub fn test_div_reset_increments_tima() {
+        let mut timer = timer(0x05);
+
+        // Bit 3 is set after 2 M-cycles, clearing it is a falling edge
+        timer.tick();
+        timer.tick();
+        timer.write(0xFF04, 0x00);
+        assert_eq!(0x01, timer.read(0xFF05));
+    }
+
+    #[test]
+    pub fn test_tac_change_increments_tima() {
+        let mut timer = timer(0x05);
+
+        timer.tick();
+        timer.tick();
+        timer.write(0xFF07, 0x00);
+        assert_eq!(0x01, timer.read(0xFF05));
+    }
+
+    #[test]
+    pub fn test_overflow_reload() {
+        let mut timer = timer(0x05);
+        timer.write(0xFF06, 0x23);
+        timer.write(0xFF05, 0xFF);
+
+        for _ in 0..4 {
+            assert!(!timer.tick());
+        }
+        // TIMA stays at 0x00 for one M-cycle
+        assert_eq!(0x00, timer.read(0xFF05));
+
+        assert!(timer.tick());
+        assert_eq!(0x23, timer.read(0xFF05));
+
+        // Writes to TIMA are ignored during the reload cycle, TMA writes go through
+        timer.write(0xFF05, 0x55);
+        assert_eq!(0x23, timer.read(0xFF05));
+        timer.write(0xFF06, 0x77);
+        assert_eq!(0x77, timer.read(0xFF05));
+    }
+
+    #[test]
+    pub fn test_tima_write_cancels_reload() {
+        let mut timer = timer(0x05);
+        timer.write(0xFF06, 0x23);
+        timer.write(0xFF05, 0xFF);
+
+        for _ in 0..4 {
+            timer.tick();
+        }
+        timer.write(0xFF05, 0x42);
+
+        assert!(!timer.tick());
+        assert_eq!(0x42, timer.read(0xFF05));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::memory::Model;
+
+use super::{
+    sprites::{BgPixel, ObjPixel},
+    Ppu,
+};
+
+// White to black, for the DMG until a palette is applied
+pub(super) const DMG_GREYS: [u16; 4] = [0x7FFF, 0x56B5, 0x294A, 0x0000];
+
+/// Converts a 24-bit color to the CGB's 15-bit BGR format
+pub const fn rgb555(rgb: u32) -> u16 {
+    let r = ((rgb >> 16) & 0xFF) >> 3;
+    let g = ((rgb >> 8) & 0xFF) >> 3;
+    let b = (rgb & 0xFF) >> 3;
+    ((b << 10) | (g << 5) | r) as u16
+}
+
+/// 8 palettes of 4 colors, accessed through BCPS/BCPD or OCPS/OCPD
+pub(super) struct PaletteRam {
+    data: [u8; 64],
+    // Index in bits 0-5, auto increment in bit 7
+    specification: u8,
+}
+
+impl PaletteRam {
+    pub fn new() -> Self {
+        Self {
+            data: [0xFF; 64],
+            specification: 0,
+        }
+    }
+
+    pub fn read_specification(&self) -> u8 {
+        0x40 | self.specification
+    }
+
+    pub fn write_specification(&mut self, value: u8) {
+        self.specification = value & 0xBF;
+    }
+
+    pub fn read_data(&self) -> u8 {
+        self.data[(self.specification & 0x3F) as usize]
+    }
+
+    pub fn write_data(&mut self, value: u8) {
+        self.data[(self.specification & 0x3F) as usize] = value;
+
+        if self.specification & 0x80 != 0 {
+            self.specification = 0x80 | (self.specification.wrapping_add(1) & 0x3F);
+        }
+    }
+
+    pub fn color(&self, palette: u8, color: u8) -> u16 {
+        let offset = palette as usize * 8 + color as usize * 2;
+        u16::from_le_bytes([self.data[offset], self.data[offset + 1]]) & 0x7FFF
+    }
+
+    pub fn set_palette(&mut self, palette: u8, colors: &[u16; 4]) {
+        for (color, value) in colors.iter().enumerate() {
+            let offset = palette as usize * 8 + color * 2;
+            self.data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+/// Colors the CGB boot ROM gives a DMG game, for BGP, OBP0 and OBP1
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct CompatibilityPalettes {
+    pub bg: [u16; 4],
+    pub obj0: [u16; 4],
+    pub obj1: [u16; 4],
+}
+
+impl Default for CompatibilityPalettes {
+    /// What games missing from the boot ROM's table get
+    fn default() -> Self {
+        Self {
+            bg: [
+                rgb555(0xFFFFFF),
+                rgb555(0x7BFF31),
+                rgb555(0x0063C5),
+                rgb555(0x000000),
+            ],
+            obj0: [
+                rgb555(0xFFFFFF),
+                rgb555(0xFF8484),
+                rgb555(0x943A3A),
+                rgb555(0x000000),
+            ],
+            obj1: [
+                rgb555(0xFFFFFF),
+                rgb555(0xFF8484),
+                rgb555(0x943A3A),
+                rgb555(0x000000),
+            ],
+        }
+    }
+}
+
+impl Ppu {
+    /// Final color of a pixel, given the background and the sprite on top
+    ///
+    /// Returns the color index that ended up on screen, the DMG shade or the
+    /// CGB color number, and its 15-bit RGB value.
+    pub(super) fn mix_pixel(&self, background: BgPixel, obj: ObjPixel) -> (u8, u16) {
+        if self.cgb_mode() {
+            return self.mix_cgb_pixel(background, obj);
+        }
+
+        // On DMG, LCDC bit 0 blanks both the background and the window to white
+        let background_enabled = self.lcdc & 0x01 != 0;
+        let background_color = if background_enabled {
+            background.color
+        } else {
+            0
+        };
+
+        let obj_visible = self.lcdc & 0x02 != 0
+            && obj.color != 0
+            && !(obj.behind_background && background_color != 0);
+
+        if obj_visible {
+            self.obj_color(obj.palette, obj.color)
+        } else if background_enabled {
+            self.background_color(BgPixel {
+                color: background_color,
+                ..background
+            })
+        } else {
+            (0, self.dmg_color(false, 0, 0))
+        }
+    }
+
+    /// Background pixel through BGP, or its CGB palette
+    pub(super) fn background_color(&self, pixel: BgPixel) -> (u8, u16) {
+        if self.cgb_mode() {
+            return (
+                pixel.color,
+                self.bg_palettes.color(pixel.palette, pixel.color),
+            );
+        }
+
+        let shade = (self.bgp >> (pixel.color * 2)) & 0x03;
+        (shade, self.dmg_color(false, 0, shade))
+    }
+
+    /// Sprite pixel through OBP0/OBP1, or its CGB palette
+    pub(super) fn obj_color(&self, palette: u8, color: u8) -> (u8, u16) {
+        if self.cgb_mode() {
+            return (color, self.obj_palettes.color(palette, color));
+        }
+
+        let register = if palette == 0 { self.obp0 } else { self.obp1 };
+        let shade = (register >> (color * 2)) & 0x03;
+        (shade, self.dmg_color(true, palette, shade))
+    }
+
+    /// RGB of a DMG shade, through the compatibility palettes on a CGB
+    fn dmg_color(&self, obj: bool, palette: u8, shade: u8) -> u16 {
+        if self.model != Model::Cgb {
+            return DMG_GREYS[shade as usize];
+        }
+
+        if obj {
+            self.obj_palettes.color(palette, shade)
+        } else {
+            self.bg_palettes.color(0, shade)
+        }
+    }
+
+    /// With LCDC bit 0 clear, sprites are always on top. Otherwise the
+    /// background wins over sprites when either asks for it, unless it is
+    /// color 0.
+    fn mix_cgb_pixel(&self, background: BgPixel, obj: ObjPixel) -> (u8, u16) {
+        let obj_visible = self.lcdc & 0x02 != 0
+            && obj.color != 0
+            && (background.color == 0
+                || self.lcdc & 0x01 == 0
+                || !(background.priority || obj.behind_background));
+
+        if obj_visible {
+            self.obj_color(obj.palette, obj.color)
+        } else {
+            self.background_color(background)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_palette_ram_auto_increment() {
+        let mut palettes = PaletteRam::new();
+        palettes.write_specification(0xBE);
+        palettes.write_data(0x1F);
+        palettes.write_data(0x00);
+
+        // Wrapped around to the first palette
+        assert_eq!(0xC0, palettes.read_specification());
+        assert_eq!(0x001F, palettes.color(7, 3));
+        assert_eq!(0x1F, rgb555(0xFF0000));
+    }
+}
@@ -0,0 +1,299 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::memory::Header;
+
+use super::CompatibilityPalettes;
+
+// Colors as stored by the CGB boot ROM, palettes of 4 that combinations can
+// also start in the middle of
+const COLORS: [u16; 120] = [
+    0x7FFF, 0x32BF, 0x00D0, 0x0000, // 0
+    0x639F, 0x4279, 0x15B0, 0x04CB, // 1
+    0x7FFF, 0x6E31, 0x454A, 0x0000, // 2
+    0x7FFF, 0x1BEF, 0x0200, 0x0000, // 3
+    0x7FFF, 0x421F, 0x1CF2, 0x0000, // 4
+    0x7FFF, 0x5294, 0x294A, 0x0000, // 5
+    0x7FFF, 0x03FF, 0x012F, 0x0000, // 6
+    0x7FFF, 0x03EF, 0x01D6, 0x0000, // 7
+    0x7FFF, 0x42B5, 0x3DC8, 0x0000, // 8
+    0x7E74, 0x03FF, 0x0180, 0x0000, // 9
+    0x67FF, 0x77AC, 0x1A13, 0x2D6B, // 10
+    0x7ED6, 0x4BFF, 0x2175, 0x0000, // 11
+    0x53FF, 0x4A5F, 0x7E52, 0x0000, // 12
+    0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0, // 13
+    0x03ED, 0x7FFF, 0x255F, 0x0000, // 14
+    0x036A, 0x021F, 0x03FF, 0x7FFF, // 15
+    0x7FFF, 0x01DF, 0x0112, 0x0000, // 16
+    0x231F, 0x035F, 0x00F2, 0x0009, // 17
+    0x7FFF, 0x03EA, 0x011F, 0x0000, // 18
+    0x299F, 0x001A, 0x000C, 0x0000, // 19
+    0x7FFF, 0x027F, 0x001F, 0x0000, // 20
+    0x7FFF, 0x03E0, 0x0206, 0x0120, // 21
+    0x7FFF, 0x7EEB, 0x001F, 0x7C00, // 22
+    0x7FFF, 0x3FFF, 0x7E00, 0x001F, // 23
+    0x7FFF, 0x03FF, 0x001F, 0x0000, // 24
+    0x03FF, 0x001F, 0x000C, 0x0000, // 25
+    0x7FFF, 0x033F, 0x0193, 0x0000, // 26
+    0x0000, 0x4200, 0x037F, 0x7FFF, // 27
+    0x7FFF, 0x7E8C, 0x7C00, 0x0000, // 28
+    0x7FFF, 0x1BEF, 0x6180, 0x0000, // 29
+];
+
+// Index of the first color of OBP0, OBP1 and BGP
+const fn palettes(obj0: usize, obj1: usize, bg: usize) -> [usize; 3] {
+    [obj0 * 4, obj1 * 4, bg * 4]
+}
+
+const COMBINATIONS: [[usize; 3]; 51] = [
+    palettes(4, 4, 29),
+    palettes(18, 18, 18),
+    palettes(20, 20, 20),
+    palettes(24, 24, 24),
+    palettes(9, 9, 9),
+    palettes(0, 0, 0),
+    palettes(27, 27, 27),
+    palettes(5, 5, 5),
+    palettes(12, 12, 12),
+    palettes(26, 26, 26),
+    palettes(16, 8, 8),
+    palettes(4, 28, 28),
+    palettes(4, 2, 2),
+    palettes(3, 4, 4),
+    palettes(4, 29, 29),
+    palettes(28, 4, 28),
+    palettes(2, 17, 2),
+    palettes(16, 16, 8),
+    palettes(4, 4, 7),
+    palettes(4, 4, 18),
+    palettes(4, 4, 20),
+    palettes(19, 19, 9),
+    // Sprites start on the last color of palette 3
+    [4 * 4 - 1, 4 * 4 - 1, 11 * 4],
+    palettes(17, 17, 2),
+    palettes(4, 4, 2),
+    palettes(4, 4, 3),
+    palettes(28, 28, 0),
+    palettes(3, 3, 0),
+    palettes(0, 0, 1),
+    palettes(18, 22, 18),
+    palettes(20, 22, 20),
+    palettes(24, 22, 24),
+    palettes(16, 22, 8),
+    palettes(17, 4, 13),
+    [28 * 4 - 1, 0, 14 * 4],
+    [28 * 4 - 1, 4 * 4, 15 * 4],
+    palettes(19, 22, 9),
+    palettes(16, 28, 10),
+    palettes(4, 23, 28),
+    palettes(17, 22, 2),
+    palettes(4, 0, 2),
+    palettes(4, 28, 3),
+    palettes(28, 3, 0),
+    palettes(3, 28, 4),
+    palettes(21, 28, 4),
+    palettes(3, 28, 0),
+    palettes(25, 3, 28),
+    palettes(0, 28, 8),
+    palettes(4, 3, 28),
+    palettes(28, 3, 6),
+    palettes(4, 28, 29),
+];
+
+// Sum of the title bytes of the games the boot ROM knows about. Past
+// FIRST_DUPLICATE, checksums shared by several games are told apart by the
+// 4th letter of the title.
+const TITLE_CHECKSUMS: [u8; 94] = [
+    0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, 0x3D, 0x5C, 0x58, 0xC9, 0x3E, 0x70,
+    0x1D, 0x59, 0x69, 0x19, 0x35, 0xA8, 0x14, 0xAA, 0x75, 0x95, 0x99, 0x34, 0x6F, 0x15, 0xFF, 0x97,
+    0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7, 0xF6, 0xA2, 0x49, 0x4E, 0x43, 0x68, 0xE0, 0x8B, 0xF0, 0xCE,
+    0x0C, 0x29, 0xE8, 0xB7, 0x86, 0x9A, 0x52, 0x01, 0x9D, 0x71, 0x9C, 0xBD, 0x5D, 0x6D, 0x67, 0x3F,
+    0x6B, 0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4, 0xB3,
+    0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4, 0xB3,
+];
+
+const FIRST_DUPLICATE: usize = 65;
+
+const FOURTH_LETTERS: &[u8; TITLE_CHECKSUMS.len() - FIRST_DUPLICATE] =
+    b"BEFAARBEKEK R-URAR INAILICE R";
+
+// Combination used by each checksum
+const CHECKSUM_COMBINATIONS: [u8; 94] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5, 19, 36, 7, 37, 30, 44, 21, 32, 31, 20, 5, 33, 13, 14, 5, 29,
+    5, 18, 9, 3, 2, 26, 25, 25, 41, 42, 26, 45, 42, 45, 36, 38, 26, 42, 30, 41, 34, 34, 5, 42, 6,
+    5, 33, 25, 42, 42, 40, 2, 16, 25, 42, 42, 5, 0, 39, 36, 22, 25, 6, 32, 12, 36, 11, 39, 18, 39,
+    24, 31, 50, 17, 46, 6, 27, 0, 47, 41, 41, 0, 0, 19, 34, 23, 18, 29,
+];
+
+/// Palette picked by holding buttons while the CGB logo shows
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ButtonCombo {
+    Up,
+    UpA,
+    UpB,
+    Left,
+    LeftA,
+    LeftB,
+    Down,
+    DownA,
+    DownB,
+    Right,
+    RightA,
+    RightB,
+}
+
+const BUTTON_COMBOS: [(ButtonCombo, &str, usize); 12] = [
+    (ButtonCombo::Up, "up", 5),
+    (ButtonCombo::UpA, "up+a", 43),
+    (ButtonCombo::UpB, "up+b", 28),
+    (ButtonCombo::Left, "left", 48),
+    (ButtonCombo::LeftA, "left+a", 40),
+    (ButtonCombo::LeftB, "left+b", 7),
+    (ButtonCombo::Down, "down", 8),
+    (ButtonCombo::DownA, "down+a", 3),
+    (ButtonCombo::DownB, "down+b", 49),
+    (ButtonCombo::Right, "right", 1),
+    (ButtonCombo::RightA, "right+a", 0),
+    (ButtonCombo::RightB, "right+b", 6),
+];
+
+impl FromStr for ButtonCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BUTTON_COMBOS
+            .iter()
+            .find(|(_, name, _)| name.eq_ignore_ascii_case(s))
+            .map(|(combo, _, _)| *combo)
+            .ok_or_else(|| {
+                let names: Vec<&str> = BUTTON_COMBOS.iter().map(|(_, name, _)| *name).collect();
+                format!(
+                    "Unknown button combination {}, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl Display for ButtonCombo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = BUTTON_COMBOS
+            .iter()
+            .find(|(combo, _, _)| combo == self)
+            .unwrap()
+            .1;
+        write!(f, "{}", name)
+    }
+}
+
+impl CompatibilityPalettes {
+    /// Palettes the CGB boot ROM picks for a DMG game
+    ///
+    /// Only Nintendo's games are looked up, by the sum of their title bytes
+    /// and, for the few sums shared by several games, the 4th letter of the
+    /// title. Anything else gets the default.
+    pub fn for_header(header: &Header) -> Self {
+        let nintendo = match header.old_licensee_code {
+            0x01 => true,
+            0x33 => &header.new_licensee_code == b"01",
+            _ => false,
+        };
+
+        if !nintendo {
+            return Self::default();
+        }
+
+        let checksum = title_checksum(header);
+        let index = TITLE_CHECKSUMS
+            .iter()
+            .enumerate()
+            .position(|(index, sum)| {
+                *sum == checksum
+                    && (index < FIRST_DUPLICATE
+                        || FOURTH_LETTERS[index - FIRST_DUPLICATE] == header.title[3])
+            })
+            .unwrap_or(0);
+
+        Self::combination(CHECKSUM_COMBINATIONS[index] as usize)
+    }
+
+    /// Palettes chosen manually, which override the lookup
+    pub fn for_buttons(combo: ButtonCombo) -> Self {
+        let index = BUTTON_COMBOS
+            .iter()
+            .find(|(other, _, _)| *other == combo)
+            .unwrap()
+            .2;
+        Self::combination(index)
+    }
+
+    fn combination(index: usize) -> Self {
+        let [obj0, obj1, bg] = COMBINATIONS[index];
+        let palette = |start: usize| {
+            let mut colors = [0; 4];
+            colors.copy_from_slice(&COLORS[start..start + 4]);
+            colors
+        };
+
+        Self {
+            bg: palette(bg),
+            obj0: palette(obj0),
+            obj1: palette(obj1),
+        }
+    }
+}
+
+/// Sum of the 16 title bytes, CGB flag included
+fn title_checksum(header: &Header) -> u8 {
+    header
+        .title
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(title: &[u8], licensee: u8) -> Header {
+        let mut rom = vec![0; 0x150];
+        rom[0x134..0x134 + title.len()].copy_from_slice(title);
+        rom[0x14B] = licensee;
+        Header::parse(&rom).unwrap()
+    }
+
+    #[test]
+    pub fn test_default_matches_table() {
+        assert_eq!(
+            CompatibilityPalettes::default(),
+            CompatibilityPalettes::combination(0)
+        );
+        assert_eq!(
+            CompatibilityPalettes::default(),
+            CompatibilityPalettes::for_buttons(ButtonCombo::RightA)
+        );
+    }
+
+    #[test]
+    pub fn test_title_lookup() {
+        // Red background, green sprites
+        let red = CompatibilityPalettes::for_header(&header(b"POKEMON RED", 0x01));
+        assert_eq!(0x421F, red.bg[1]);
+        assert_eq!(0x1BEF, red.obj0[1]);
+
+        // Shares its checksum with another game, told apart by the E
+        let blue = CompatibilityPalettes::for_header(&header(b"POKEMON BLUE", 0x01));
+        assert_eq!(0x7C00, blue.bg[2]);
+
+        // Same title from another publisher
+        let other = CompatibilityPalettes::for_header(&header(b"POKEMON RED", 0x08));
+        assert_eq!(CompatibilityPalettes::default(), other);
+    }
+
+    #[test]
+    pub fn test_button_combo() {
+        assert_eq!(ButtonCombo::LeftB, "Left+B".parse().unwrap());
+        let grey = CompatibilityPalettes::for_buttons(ButtonCombo::LeftB);
+        assert_eq!([0x7FFF, 0x5294, 0x294A, 0x0000], grey.bg);
+    }
+}
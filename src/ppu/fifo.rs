@@ -0,0 +1,286 @@
+use std::collections::VecDeque;
+
+use super::{
+    sprites::{BgPixel, ObjPixel},
+    Ppu, SCREEN_WIDTH,
+};
+
+// The first tile of a line is fetched twice, the first one being thrown away
+const STARTUP_DOTS: u8 = 6;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum FetchStep {
+    Tile,
+    Low,
+    High,
+    Push,
+}
+
+/// Mode 3 state of the pixel FIFO renderer
+pub(super) struct Fifo {
+    background: VecDeque<BgPixel>,
+    objects: VecDeque<ObjPixel>,
+    step: FetchStep,
+    step_dots: u8,
+    // Tile column fetched next, relative to SCX or to the window
+    tile_x: u8,
+    tile: u8,
+    attributes: u8,
+    row: usize,
+    low: u8,
+    high: u8,
+    // Pixels sent to the LCD so far
+    x: usize,
+    // Pixels dropped from the start of the line for fine scrolling
+    discard: u8,
+    startup: u8,
+    window: bool,
+    // Dots left until the sprite being fetched is ready
+    sprite_fetch: u8,
+    sprites_done: usize,
+    // Tile column that already paid for the background fetch wait
+    penalized_tile: Option<usize>,
+}
+
+impl Fifo {
+    pub fn new() -> Self {
+        Self {
+            background: VecDeque::with_capacity(16),
+            objects: VecDeque::with_capacity(8),
+            step: FetchStep::Tile,
+            step_dots: 0,
+            tile_x: 0,
+            tile: 0,
+            attributes: 0,
+            row: 0,
+            low: 0,
+            high: 0,
+            x: 0,
+            discard: 0,
+            startup: STARTUP_DOTS,
+            window: false,
+            sprite_fetch: 0,
+            sprites_done: 0,
+            penalized_tile: None,
+        }
+    }
+
+    fn restart_fetch(&mut self) {
+        self.background.clear();
+        self.step = FetchStep::Tile;
+        self.step_dots = 0;
+        self.tile_x = 0;
+    }
+}
+
+impl Ppu {
+    pub(super) fn start_fifo_line(&mut self) {
+        self.fifo = Fifo::new();
+        // The fine scroll is latched at the start of the line
+        self.fifo.discard = self.scx & 0x07;
+
+        // Sprites are fetched from left to right
+        self.line_sprites.sort_by_key(|sprite| sprite.x);
+    }
+
+    fn fetch_tile_row(&self, vram: &[u8]) -> (u8, u8) {
+        self.tile_row(vram, self.fifo.tile, self.fifo.row, self.fifo.attributes)
+    }
+
+    fn step_fetcher(&mut self, vram: &[u8]) {
+        if self.fifo.step == FetchStep::Push {
+            // Pixels only go in once the FIFO is empty
+            if self.fifo.background.is_empty() {
+                let attributes = self.fifo.attributes;
+
+                for i in 0..8 {
+                    let bit = if attributes & 0x20 != 0 { i } else { 7 - i };
+                    self.fifo.background.push_back(BgPixel {
+                        color: (((self.fifo.high >> bit) & 1) << 1) | ((self.fifo.low >> bit) & 1),
+                        palette: attributes & 0x07,
+                        priority: attributes & 0x80 != 0,
+                    });
+                }
+                self.fifo.tile_x = self.fifo.tile_x.wrapping_add(1);
+                self.fifo.step = FetchStep::Tile;
+            }
+            return;
+        }
+
+        // Each step of the fetch takes two dots
+        self.fifo.step_dots += 1;
+        if self.fifo.step_dots < 2 {
+            return;
+        }
+        self.fifo.step_dots = 0;
+
+        match self.fifo.step {
+            FetchStep::Tile => {
+                let (map, x, y) = if self.fifo.window {
+                    let map = if self.lcdc & 0x40 != 0 {
+                        0x1C00
+                    } else {
+                        0x1800
+                    };
+                    (map, self.fifo.tile_x as usize, self.window_line as usize)
+                } else {
+                    let map = if self.lcdc & 0x08 != 0 {
+                        0x1C00
+                    } else {
+                        0x1800
+                    };
+                    let x = ((self.scx >> 3) as usize + self.fifo.tile_x as usize) & 0x1F;
+                    (map, x, (self.ly as usize + self.scy as usize) & 0xFF)
+                };
+
+                let offset = map + (y / 8) * 32 + (x & 0x1F);
+                self.fifo.tile = vram[offset];
+                self.fifo.attributes = self.tile_attributes(vram, offset);
+                self.fifo.row = y % 8;
+                self.fifo.step = FetchStep::Low;
+            }
+            FetchStep::Low => {
+                self.fifo.low = self.fetch_tile_row(vram).0;
+                self.fifo.step = FetchStep::High;
+            }
+            FetchStep::High => {
+                self.fifo.high = self.fetch_tile_row(vram).1;
+                self.fifo.step = FetchStep::Push;
+            }
+            FetchStep::Push => {}
+        }
+    }
+
+    fn window_starts(&self) -> bool {
+        if self.fifo.window || self.lcdc & 0x20 == 0 || !self.window_triggered {
+            return false;
+        }
+
+        // Below 7, the window starts at the left edge with its first pixels cut off
+        self.fifo.x + 7 == self.wx as usize || (self.wx < 7 && self.fifo.x == 0)
+    }
+
+    /// Dots a sprite stalls the line for
+    ///
+    /// The fetch takes 6 dots, plus the time for the background fetcher to
+    /// finish the tile under the sprite, which only the first sprite on a
+    /// tile pays.
+    fn sprite_penalty(&mut self, x: u8) -> u8 {
+        if x == 0 {
+            return 11;
+        }
+
+        let position = x as usize + self.scx as usize;
+        if self.fifo.penalized_tile == Some(position / 8) {
+            return 6;
+        }
+
+        self.fifo.penalized_tile = Some(position / 8);
+        11 - ((position % 8) as u8).min(5)
+    }
+
+    fn next_sprite_hit(&self) -> bool {
+        if self.lcdc & 0x02 == 0 {
+            return false;
+        }
+
+        self.line_sprites
+            .get(self.fifo.sprites_done)
+            .is_some_and(|sprite| sprite.x as usize <= self.fifo.x + 8)
+    }
+
+    /// Puts the fetched sprite's pixels in the sprite FIFO
+    ///
+    /// Pixels already there keep their place, unless on CGB the new sprite
+    /// comes first in OAM. Pixels left of the screen are dropped.
+    fn merge_sprite(&mut self, vram: &[u8]) {
+        let sprite = self.line_sprites[self.fifo.sprites_done];
+        let colors = self.sprite_colors(vram, &sprite);
+        let index_priority = self.index_priority();
+        let palette = self.sprite_palette(&sprite);
+
+        while self.fifo.objects.len() < 8 {
+            self.fifo.objects.push_back(ObjPixel::default());
+        }
+
+        for (i, color) in colors.iter().enumerate() {
+            let x = sprite.x as usize + i;
+            if x < self.fifo.x + 8 || *color == 0 {
+                continue;
+            }
+
+            let slot = &mut self.fifo.objects[x - 8 - self.fifo.x];
+            if slot.color == 0 || (index_priority && sprite.index < slot.index) {
+                *slot = ObjPixel {
+                    color: *color,
+                    palette,
+                    behind_background: sprite.behind_background(),
+                    index: sprite.index,
+                };
+            }
+        }
+    }
+
+    /// Advances mode 3 by a dot, returning whether the line is complete
+    pub(super) fn fifo_tick(&mut self, vram: &[u8]) -> bool {
+        if self.fifo.startup > 0 {
+            self.fifo.startup -= 1;
+            return false;
+        }
+
+        if self.fifo.sprite_fetch > 0 {
+            self.fifo.sprite_fetch -= 1;
+            if self.fifo.sprite_fetch == 0 {
+                self.merge_sprite(vram);
+                self.fifo.sprites_done += 1;
+            }
+            return false;
+        }
+
+        // Sprites past X 168 are never reached
+        if self.next_sprite_hit() {
+            let x = self.line_sprites[self.fifo.sprites_done].x;
+            // This dot is the first of the penalty
+            self.fifo.sprite_fetch = self.sprite_penalty(x) - 1;
+            return false;
+        }
+
+        if self.window_starts() {
+            self.fifo.window = true;
+            self.fifo.restart_fetch();
+            if self.wx < 7 {
+                self.fifo.discard = 7 - self.wx;
+            }
+        }
+
+        self.step_fetcher(vram);
+
+        let pixel = match self.fifo.background.pop_front() {
+            Some(pixel) => pixel,
+            None => return false,
+        };
+
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return false;
+        }
+
+        // Palettes are applied as pixels leave, mid-line changes show up
+        let obj = self.fifo.objects.pop_front().unwrap_or_default();
+        let (color, rgb) = self.mix_pixel(pixel, obj);
+        let offset = self.ly as usize * SCREEN_WIDTH + self.fifo.x;
+        self.back_buffer[offset] = color;
+        self.back_rgb_buffer[offset] = rgb;
+        self.fifo.x += 1;
+
+        if self.fifo.x < SCREEN_WIDTH {
+            return false;
+        }
+
+        if self.fifo.window {
+            self.window_line += 1;
+        }
+
+        true
+    }
+}
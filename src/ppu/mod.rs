@@ -0,0 +1,588 @@
+mod color;
+mod colorize;
+mod fifo;
+mod scanline;
+mod sprites;
+mod viewer;
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::memory::{Model, INTERRUPT_STAT, INTERRUPT_VBLANK};
+
+use color::PaletteRam;
+use fifo::Fifo;
+use sprites::Sprite;
+
+pub use color::CompatibilityPalettes;
+pub use colorize::ButtonCombo;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+const DOTS_PER_LINE: u16 = 456;
+const OAM_SCAN_DOTS: u16 = 80;
+// Shortest mode 3, without scrolling, window or sprites
+const DRAWING_DOTS: u16 = 172;
+const LINES_PER_FRAME: u8 = 154;
+
+/// Mode reported in the lower bits of STAT
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+/// How mode 3 is emulated
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Renderer {
+    /// Whole line drawn at once with a fixed mode 3 length, cheap but blind
+    /// to registers changed during the line
+    Scanline,
+    /// Pixel by pixel through the fetchers and FIFO, mode 3 length varies
+    /// with scrolling, window and sprites like on hardware
+    Fifo,
+}
+
+impl FromStr for Renderer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scanline" => Ok(Renderer::Scanline),
+            "fifo" => Ok(Renderer::Fifo),
+            _ => Err(format!(
+                "Unknown renderer {}, expected one of: scanline, fifo",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Renderer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Renderer::Scanline => write!(f, "scanline"),
+            Renderer::Fifo => write!(f, "fifo"),
+        }
+    }
+}
+
+/// What happened during a dot, for the rest of the hardware
+#[derive(Default)]
+pub struct PpuEvents {
+    /// IF bits to set
+    pub interrupts: u8,
+    /// Mode 0 was just entered, where HBlank DMA copies a block
+    pub hblank: bool,
+}
+
+/// Picture processing unit
+pub struct Ppu {
+    model: Model,
+    renderer: Renderer,
+    lcdc: u8,
+    // Only the interrupt enable bits, the rest is computed
+    stat: u8,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+    mode: Mode,
+    dot: u16,
+    // Lines of the window drawn so far this frame
+    window_line: u8,
+    // WY matched LY at some point this frame
+    window_triggered: bool,
+    // STAT interrupts fire on the rising edge of all conditions ORed together
+    stat_line: bool,
+    // Sprites found by the OAM scan of the current line
+    line_sprites: Vec<Sprite>,
+    fifo: Fifo,
+    // CGB palettes, BCPS/BCPD (FF68-FF69) and OCPS/OCPD (FF6A-FF6B)
+    bg_palettes: PaletteRam,
+    obj_palettes: PaletteRam,
+    // OPRI (FF6C), bit 0 set for DMG style sprite priority
+    object_priority: u8,
+    // Set when a DMG game runs on a CGB
+    compatibility: Option<CompatibilityPalettes>,
+    back_buffer: Vec<u8>,
+    back_rgb_buffer: Vec<u16>,
+    frame: Vec<u8>,
+    rgb_frame: Vec<u16>,
+    frame_ready: bool,
+}
+
+impl Ppu {
+    pub fn new(model: Model) -> Self {
+        Self {
+            model,
+            renderer: Renderer::Scanline,
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            mode: Mode::HBlank,
+            dot: 0,
+            window_line: 0,
+            window_triggered: false,
+            stat_line: false,
+            line_sprites: Vec::with_capacity(10),
+            fifo: Fifo::new(),
+            bg_palettes: PaletteRam::new(),
+            obj_palettes: PaletteRam::new(),
+            object_priority: 0,
+            compatibility: None,
+            back_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            back_rgb_buffer: vec![0x7FFF; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            rgb_frame: vec![0x7FFF; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_ready: false,
+        }
+    }
+
+    /// Back to the power on state, keeping the renderer and compatibility palettes
+    pub fn reset(&mut self) {
+        let compatibility = self.compatibility;
+
+        *self = Self {
+            renderer: self.renderer,
+            ..Self::new(self.model)
+        };
+
+        if let Some(palettes) = compatibility {
+            self.set_compatibility_palettes(palettes);
+        }
+    }
+
+    /// Runs a DMG game on a CGB, as the boot ROM sets it up
+    ///
+    /// The PPU then works like a DMG, with BGP, OBP0 and OBP1 indexing the
+    /// given colors instead of greys.
+    pub fn set_compatibility_palettes(&mut self, palettes: CompatibilityPalettes) {
+        self.compatibility = Some(palettes);
+        self.object_priority = 0x01;
+
+        self.bg_palettes.set_palette(0, &palettes.bg);
+        self.obj_palettes.set_palette(0, &palettes.obj0);
+        self.obj_palettes.set_palette(1, &palettes.obj1);
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Colors given to a DMG game running on a CGB, if that's the case
+    pub fn compatibility_palettes(&self) -> Option<CompatibilityPalettes> {
+        self.compatibility
+    }
+
+    /// Whether the CGB features are in use, a CGB running a CGB game
+    fn cgb_mode(&self) -> bool {
+        self.model == Model::Cgb && self.compatibility.is_none()
+    }
+
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer;
+    }
+
+    /// Whether sprites overlap by OAM order rather than by X
+    fn index_priority(&self) -> bool {
+        self.model == Model::Cgb && self.object_priority & 0x01 == 0
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Whether the LCD is on, LCDC bit 7
+    pub fn is_enabled(&self) -> bool {
+        self.lcdc & 0x80 != 0
+    }
+
+    /// Last complete frame, one shade (0-3) per pixel
+    ///
+    /// On CGB, this is the color number within the pixel's palette.
+    pub fn frame(&self) -> &[u8] {
+        &self.frame
+    }
+
+    /// Last complete frame in 15-bit RGB, as the CGB outputs it
+    pub fn rgb_frame(&self) -> &[u16] {
+        &self.rgb_frame
+    }
+
+    /// Returns whether a new frame was completed since the last call
+    pub fn take_frame_ready(&mut self) -> bool {
+        std::mem::take(&mut self.frame_ready)
+    }
+
+    fn stat_condition(&self) -> bool {
+        (self.stat & 0x40 != 0 && self.ly == self.lyc)
+            || (self.stat & 0x20 != 0 && self.mode == Mode::OamScan)
+            || (self.stat & 0x10 != 0 && self.mode == Mode::VBlank)
+            || (self.stat & 0x08 != 0 && self.mode == Mode::HBlank)
+    }
+
+    fn update_stat_line(&mut self, events: &mut PpuEvents) {
+        let line = self.is_enabled() && self.stat_condition();
+        if line && !self.stat_line {
+            events.interrupts |= INTERRUPT_STAT;
+        }
+        self.stat_line = line;
+    }
+
+    fn start_line(&mut self, events: &mut PpuEvents) {
+        if self.ly == self.wy {
+            self.window_triggered = true;
+        }
+
+        if (self.ly as usize) < SCREEN_HEIGHT {
+            self.mode = Mode::OamScan;
+        } else if self.ly as usize == SCREEN_HEIGHT {
+            self.mode = Mode::VBlank;
+            events.interrupts |= INTERRUPT_VBLANK;
+
+            std::mem::swap(&mut self.frame, &mut self.back_buffer);
+            std::mem::swap(&mut self.rgb_frame, &mut self.back_rgb_buffer);
+            self.frame_ready = true;
+        }
+    }
+
+    /// Advances one dot, at 4 MHz whatever the CPU speed
+    pub fn tick(&mut self, vram: &[u8], oam: &[u8]) -> PpuEvents {
+        let mut events = PpuEvents::default();
+
+        if !self.is_enabled() {
+            return events;
+        }
+
+        self.dot += 1;
+
+        match self.mode {
+            Mode::OamScan if self.dot == OAM_SCAN_DOTS => {
+                self.scan_oam(oam);
+                self.mode = Mode::Drawing;
+
+                match self.renderer {
+                    Renderer::Scanline => self.render_line(vram),
+                    Renderer::Fifo => self.start_fifo_line(),
+                }
+            }
+            Mode::Drawing => {
+                let done = match self.renderer {
+                    Renderer::Scanline => self.dot == OAM_SCAN_DOTS + DRAWING_DOTS,
+                    Renderer::Fifo => self.fifo_tick(vram),
+                };
+
+                if done {
+                    self.mode = Mode::HBlank;
+                    events.hblank = true;
+                }
+            }
+            Mode::HBlank | Mode::VBlank if self.dot == DOTS_PER_LINE => {
+                self.dot = 0;
+                self.ly += 1;
+
+                if self.ly == LINES_PER_FRAME {
+                    self.ly = 0;
+                    self.window_line = 0;
+                    self.window_triggered = false;
+                }
+
+                self.start_line(&mut events);
+            }
+            _ => {}
+        }
+
+        self.update_stat_line(&mut events);
+
+        events
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF40 => self.lcdc,
+            0xFF41 => {
+                let coincidence = if self.ly == self.lyc { 0x04 } else { 0x00 };
+                0x80 | self.stat | coincidence | self.mode as u8
+            }
+            0xFF42 => self.scy,
+            0xFF43 => self.scx,
+            0xFF44 => self.ly,
+            0xFF45 => self.lyc,
+            0xFF47 => self.bgp,
+            0xFF48 => self.obp0,
+            0xFF49 => self.obp1,
+            0xFF4A => self.wy,
+            0xFF4B => self.wx,
+            0xFF68 => self.bg_palettes.read_specification(),
+            0xFF69 => self.bg_palettes.read_data(),
+            0xFF6A => self.obj_palettes.read_specification(),
+            0xFF6B => self.obj_palettes.read_data(),
+            0xFF6C => 0xFE | self.object_priority,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) -> PpuEvents {
+        let mut events = PpuEvents::default();
+
+        match address {
+            0xFF40 => {
+                let was_enabled = self.is_enabled();
+                self.lcdc = value;
+
+                if was_enabled && !self.is_enabled() {
+                    // LY stays at 0 while the LCD is off
+                    self.ly = 0;
+                    self.dot = 0;
+                    self.mode = Mode::HBlank;
+                    self.window_line = 0;
+                    self.window_triggered = false;
+                } else if !was_enabled && self.is_enabled() {
+                    self.start_line(&mut events);
+                }
+            }
+            0xFF41 => self.stat = value & 0x78,
+            0xFF42 => self.scy = value,
+            0xFF43 => self.scx = value,
+            // LY is read only
+            0xFF44 => {}
+            0xFF45 => self.lyc = value,
+            0xFF47 => self.bgp = value,
+            0xFF48 => self.obp0 = value,
+            0xFF49 => self.obp1 = value,
+            0xFF4A => self.wy = value,
+            0xFF4B => self.wx = value,
+            0xFF68 => self.bg_palettes.write_specification(value),
+            0xFF69 => self.bg_palettes.write_data(value),
+            0xFF6A => self.obj_palettes.write_specification(value),
+            0xFF6B => self.obj_palettes.write_data(value),
+            0xFF6C => self.object_priority = value & 0x01,
+            _ => {}
+        }
+
+        self.update_stat_line(&mut events);
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(ppu: &mut Ppu, vram: &[u8], dots: usize) -> u8 {
+        let oam = [0; 0xA0];
+        (0..dots).fold(0, |interrupts, _| {
+            interrupts | ppu.tick(vram, &oam).interrupts
+        })
+    }
+
+    #[test]
+    pub fn test_line_timing() {
+        let vram = vec![0; 0x4000];
+        let mut ppu = Ppu::new(Model::Dmg);
+        ppu.write(0xFF41, 0x40);
+        ppu.write(0xFF45, 0x01);
+        ppu.write(0xFF40, 0x91);
+
+        assert_eq!(Mode::OamScan, ppu.mode());
+        run(&mut ppu, &vram, 80);
+        assert_eq!(Mode::Drawing, ppu.mode());
+        run(&mut ppu, &vram, 172);
+        assert_eq!(Mode::HBlank, ppu.mode());
+
+        // LY=LYC on the next line
+        assert_eq!(INTERRUPT_STAT, run(&mut ppu, &vram, 204));
+        assert_eq!(1, ppu.read(0xFF44));
+        assert_eq!(0x04, ppu.read(0xFF41) & 0x04);
+
+        let interrupts = run(&mut ppu, &vram, 143 * 456);
+        assert_eq!(INTERRUPT_VBLANK, interrupts & INTERRUPT_VBLANK);
+        assert_eq!(Mode::VBlank, ppu.mode());
+        assert!(ppu.take_frame_ready());
+
+        run(&mut ppu, &vram, 10 * 456);
+        assert_eq!(0, ppu.read(0xFF44));
+        assert_eq!(Mode::OamScan, ppu.mode());
+    }
+
+    fn mode3_length(scx: u8, oam: &[u8]) -> usize {
+        let vram = vec![0; 0x4000];
+        let mut ppu = Ppu::new(Model::Dmg);
+        ppu.set_renderer(Renderer::Fifo);
+        ppu.write(0xFF43, scx);
+        ppu.write(0xFF40, 0x93);
+
+        for _ in 0..80 {
+            ppu.tick(&vram, oam);
+        }
+
+        let mut dots = 0;
+        while ppu.mode() == Mode::Drawing {
+            ppu.tick(&vram, oam);
+            dots += 1;
+        }
+        dots
+    }
+
+    #[test]
+    pub fn test_fifo_mode3_length() {
+        let mut oam = [0; 0xA0];
+        assert_eq!(172, mode3_length(0, &oam));
+        // Fine scrolling discards pixels
+        assert_eq!(175, mode3_length(3, &oam));
+
+        // A sprite at X 0 costs the full 11 dots
+        oam[0] = 16;
+        oam[1] = 0;
+        assert_eq!(183, mode3_length(0, &oam));
+
+        // A second one on the same tile only costs the fetch
+        oam[1] = 8;
+        oam[4] = 16;
+        oam[5] = 8;
+        assert_eq!(189, mode3_length(0, &oam));
+    }
+
+    #[test]
+    pub fn test_background_and_window() {
+        let mut vram = vec![0; 0x4000];
+        // Tile 1 is solid color 3, the window map uses it
+        vram[0x10..0x20].fill(0xFF);
+        vram[0x1C00..0x2000].fill(0x01);
+
+        let mut ppu = Ppu::new(Model::Dmg);
+        ppu.write(0xFF47, 0xE4);
+        ppu.write(0xFF4A, 0x08);
+        ppu.write(0xFF4B, 0x07 + 80);
+        ppu.write(0xFF40, 0xF1);
+
+        run(&mut ppu, &vram, 154 * 456);
+        let frame = ppu.frame();
+
+        // Background everywhere but the bottom right, where the window is
+        assert_eq!(0, frame[0]);
+        assert_eq!(0, frame[8 * SCREEN_WIDTH + 79]);
+        assert_eq!(3, frame[8 * SCREEN_WIDTH + 80]);
+        assert_eq!(3, frame[SCREEN_WIDTH * SCREEN_HEIGHT - 1]);
+    }
+
+    fn sprite_frame(renderer: Renderer, model: Model) -> Vec<u8> {
+        let mut vram = vec![0; 0x4000];
+        // Tile 1 is solid color 1, tile 2 solid color 3
+        vram[0x10..0x20].copy_from_slice(&[0xFF, 0x00].repeat(8));
+        vram[0x20..0x30].fill(0xFF);
+        // The background is color 3 from X 16
+        vram[0x1802] = 0x02;
+
+        let mut oam = [0; 0xA0];
+        // First in OAM but further right
+        oam[0..4].copy_from_slice(&[16, 12, 0x01, 0x00]);
+        oam[4..8].copy_from_slice(&[16, 8, 0x02, 0x10]);
+        // Behind the background, X flipped
+        oam[8..12].copy_from_slice(&[16, 20, 0x01, 0xA0]);
+
+        let mut ppu = Ppu::new(model);
+        ppu.set_renderer(renderer);
+        ppu.write(0xFF47, 0xE4);
+        ppu.write(0xFF48, 0xE4);
+        ppu.write(0xFF49, 0x1B);
+        ppu.write(0xFF40, 0x93);
+
+        for _ in 0..154 * 456 {
+            ppu.tick(&vram, &oam);
+        }
+        ppu.frame()[..SCREEN_WIDTH].to_vec()
+    }
+
+    #[test]
+    pub fn test_sprites() {
+        for renderer in [Renderer::Scanline, Renderer::Fifo] {
+            let line = sprite_frame(renderer, Model::Dmg);
+
+            // The leftmost sprite wins on DMG, through OBP1
+            assert_eq!(0, line[0]);
+            assert_eq!(0, line[7]);
+            // Then the other one shows through OBP0
+            assert_eq!(1, line[8]);
+            // The last one is only visible over background color 0
+            assert_eq!(1, line[12]);
+            assert_eq!(3, line[16]);
+
+            // The first sprite in OAM wins on CGB, frames hold color numbers
+            let line = sprite_frame(renderer, Model::Cgb);
+            assert_eq!(3, line[3]);
+            assert_eq!(1, line[4]);
+        }
+    }
+
+    #[test]
+    pub fn test_cgb_attributes() {
+        for renderer in [Renderer::Scanline, Renderer::Fifo] {
+            let mut vram = vec![0; 0x4000];
+            // Tile 1 is color 1 on its left half in bank 0, color 2 in bank 1
+            vram[0x10..0x20].copy_from_slice(&[0xF0, 0x00].repeat(8));
+            vram[0x2010..0x2020].copy_from_slice(&[0x00, 0xF0].repeat(8));
+            vram[0x1800] = 0x01;
+            vram[0x1801] = 0x01;
+            // Second tile from bank 1, X flipped, palette 2
+            vram[0x3801] = 0x2A;
+
+            let mut ppu = Ppu::new(Model::Cgb);
+            ppu.set_renderer(renderer);
+            // Palette 0 color 1 is red, palette 2 color 2 is blue
+            ppu.write(0xFF68, 0x82);
+            ppu.write(0xFF69, 0x1F);
+            ppu.write(0xFF69, 0x00);
+            ppu.write(0xFF68, 0x94);
+            ppu.write(0xFF69, 0x00);
+            ppu.write(0xFF69, 0x7C);
+            ppu.write(0xFF40, 0x91);
+
+            run(&mut ppu, &vram, 154 * 456);
+            let line = &ppu.rgb_frame()[..SCREEN_WIDTH];
+
+            assert_eq!(0x001F, line[0]);
+            assert_eq!(0x7FFF, line[4]);
+            assert_eq!(0x7FFF, line[8]);
+            assert_eq!(0x7C00, line[12]);
+        }
+    }
+
+    #[test]
+    pub fn test_compatibility_palettes() {
+        let mut vram = vec![0; 0x4000];
+        vram[0x10..0x20].fill(0xFF);
+        vram[0x1800] = 0x01;
+
+        let mut ppu = Ppu::new(Model::Cgb);
+        ppu.set_compatibility_palettes(CompatibilityPalettes::default());
+        ppu.reset();
+        ppu.write(0xFF47, 0xE4);
+        ppu.write(0xFF40, 0x91);
+
+        run(&mut ppu, &vram, 154 * 456);
+
+        // DMG shades index the boot ROM colors
+        assert_eq!(3, ppu.frame()[0]);
+        assert_eq!(0x0000, ppu.rgb_frame()[0]);
+        assert_eq!(0x7FFF, ppu.rgb_frame()[8]);
+        assert_eq!(0x01, ppu.read(0xFF6C) & 0x01);
+    }
+}
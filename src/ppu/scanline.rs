@@ -0,0 +1,146 @@
+use super::{
+    sprites::{BgPixel, ObjPixel},
+    Ppu, SCREEN_WIDTH,
+};
+
+impl Ppu {
+    /// Two bytes of a tile row, addressed as LCDC bit 4 says
+    ///
+    /// On CGB, the tile attributes pick the VRAM bank and can flip the tile vertically.
+    pub(super) fn tile_row(&self, vram: &[u8], tile: u8, row: usize, attributes: u8) -> (u8, u8) {
+        let base = if self.lcdc & 0x10 != 0 {
+            tile as usize * 16
+        } else {
+            // 0x8800 mode, tile numbers are signed around 0x9000
+            (0x1000 + tile as i8 as isize * 16) as usize
+        };
+        let base = base + ((attributes >> 3) as usize & 0x01) * 0x2000;
+        let row = if attributes & 0x40 != 0 { 7 - row } else { row };
+
+        (vram[base + row * 2], vram[base + row * 2 + 1])
+    }
+
+    /// Attributes of a tile map entry, kept in VRAM bank 1 on CGB
+    pub(super) fn tile_attributes(&self, vram: &[u8], offset: usize) -> u8 {
+        if self.cgb_mode() {
+            vram[0x2000 + offset]
+        } else {
+            0
+        }
+    }
+
+    /// Pixel of a tile map
+    pub(super) fn map_pixel(&self, vram: &[u8], map: usize, x: usize, y: usize) -> BgPixel {
+        let offset = map + (y / 8) * 32 + x / 8;
+        let tile = vram[offset];
+        let attributes = self.tile_attributes(vram, offset);
+
+        let (low, high) = self.tile_row(vram, tile, y % 8, attributes);
+        let bit = if attributes & 0x20 != 0 {
+            x % 8
+        } else {
+            7 - (x % 8)
+        };
+
+        BgPixel {
+            color: (((high >> bit) & 1) << 1) | ((low >> bit) & 1),
+            palette: attributes & 0x07,
+            priority: attributes & 0x80 != 0,
+        }
+    }
+
+    /// Draws the current line into the back buffer
+    pub(super) fn render_line(&mut self, vram: &[u8]) {
+        let y = self.ly as usize;
+        let line_start = y * SCREEN_WIDTH;
+
+        let background = self.render_background(vram);
+        let objects = self.render_sprites(vram);
+
+        for x in 0..SCREEN_WIDTH {
+            let (color, rgb) = self.mix_pixel(background[x], objects[x]);
+            self.back_buffer[line_start + x] = color;
+            self.back_rgb_buffer[line_start + x] = rgb;
+        }
+    }
+
+    /// Pixels of the background and window
+    fn render_background(&mut self, vram: &[u8]) -> [BgPixel; SCREEN_WIDTH] {
+        let mut colors = [BgPixel::default(); SCREEN_WIDTH];
+        let y = self.ly as usize;
+
+        // Only the DMG turns them off with LCDC bit 0
+        if self.lcdc & 0x01 == 0 && !self.cgb_mode() {
+            return colors;
+        }
+
+        let bg_map = if self.lcdc & 0x08 != 0 {
+            0x1C00
+        } else {
+            0x1800
+        };
+        let window_map = if self.lcdc & 0x40 != 0 {
+            0x1C00
+        } else {
+            0x1800
+        };
+
+        // WX is offset by 7, values past the screen hide the window
+        let window_x = self.wx as isize - 7;
+        let window_visible =
+            self.lcdc & 0x20 != 0 && self.window_triggered && self.wx as usize <= SCREEN_WIDTH + 6;
+
+        for (x, color) in colors.iter_mut().enumerate() {
+            *color = if window_visible && x as isize >= window_x {
+                let window_x = (x as isize - window_x) as usize;
+                self.map_pixel(vram, window_map, window_x, self.window_line as usize)
+            } else {
+                let bg_x = (x + self.scx as usize) & 0xFF;
+                let bg_y = (y + self.scy as usize) & 0xFF;
+                self.map_pixel(vram, bg_map, bg_x, bg_y)
+            };
+        }
+
+        // The window only moves down on lines where it was drawn
+        if window_visible {
+            self.window_line += 1;
+        }
+
+        colors
+    }
+
+    /// Highest priority opaque sprite pixel at each X
+    fn render_sprites(&mut self, vram: &[u8]) -> [ObjPixel; SCREEN_WIDTH] {
+        let mut pixels = [ObjPixel::default(); SCREEN_WIDTH];
+
+        if self.lcdc & 0x02 == 0 {
+            return pixels;
+        }
+
+        self.sort_sprites_by_priority();
+
+        for sprite in &self.line_sprites {
+            let colors = self.sprite_colors(vram, sprite);
+
+            for (i, color) in colors.iter().enumerate() {
+                let x = sprite.x as isize - 8 + i as isize;
+                if !(0..SCREEN_WIDTH as isize).contains(&x) || *color == 0 {
+                    continue;
+                }
+
+                // A higher priority sprite already drew here, even if it is behind the background
+                let pixel = &mut pixels[x as usize];
+                if pixel.color == 0 {
+                    *pixel = ObjPixel {
+                        color: *color,
+                        palette: self.sprite_palette(sprite),
+                        behind_background: sprite.behind_background(),
+                        index: sprite.index,
+                    };
+                }
+            }
+        }
+
+        pixels
+    }
+}
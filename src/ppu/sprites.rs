@@ -0,0 +1,141 @@
+use super::Ppu;
+
+// The PPU can't handle more per line, the others aren't drawn
+const MAX_SPRITES_PER_LINE: usize = 10;
+
+/// OAM entry selected for the current line
+#[derive(Copy, Clone, Debug)]
+pub(super) struct Sprite {
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub attributes: u8,
+    pub index: usize,
+}
+
+impl Sprite {
+    /// Drawn behind background colors 1-3
+    pub fn behind_background(&self) -> bool {
+        self.attributes & 0x80 != 0
+    }
+}
+
+/// Background or window pixel waiting to be mixed with sprites
+#[derive(Copy, Clone, Default, Debug)]
+pub(super) struct BgPixel {
+    pub color: u8,
+    /// CGB palette, from the tile attributes in VRAM bank 1
+    pub palette: u8,
+    /// CGB tile drawn over sprites
+    pub priority: bool,
+}
+
+/// Sprite pixel waiting to be mixed with the background
+#[derive(Copy, Clone, Default, Debug)]
+pub(super) struct ObjPixel {
+    /// 0 is transparent
+    pub color: u8,
+    pub palette: u8,
+    pub behind_background: bool,
+    pub index: usize,
+}
+
+impl Ppu {
+    /// 8x16 when LCDC bit 2 is set
+    pub(super) fn sprite_height(&self) -> u8 {
+        if self.lcdc & 0x04 != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    /// Mode 2: picks the first 10 sprites of OAM that overlap the current line
+    ///
+    /// The X coordinate doesn't matter, offscreen sprites still count.
+    pub(super) fn scan_oam(&mut self, oam: &[u8]) {
+        self.line_sprites.clear();
+        let line = self.ly as u16 + 16;
+        let height = self.sprite_height() as u16;
+
+        for (index, entry) in oam.chunks(4).enumerate() {
+            let y = entry[0] as u16;
+            if line < y || line >= y + height {
+                continue;
+            }
+
+            self.line_sprites.push(Sprite {
+                y: entry[0],
+                x: entry[1],
+                tile: entry[2],
+                attributes: entry[3],
+                index,
+            });
+
+            if self.line_sprites.len() == MAX_SPRITES_PER_LINE {
+                break;
+            }
+        }
+    }
+
+    /// Sorts the line's sprites from the highest priority to the lowest
+    ///
+    /// The DMG favours the leftmost sprite, then the first in OAM. The CGB
+    /// only goes by OAM order.
+    pub(super) fn sort_sprites_by_priority(&mut self) {
+        if self.index_priority() {
+            self.line_sprites.sort_by_key(|sprite| sprite.index);
+        } else {
+            self.line_sprites
+                .sort_by_key(|sprite| (sprite.x, sprite.index));
+        }
+    }
+
+    /// OBP0/OBP1 on DMG, one of the 8 OCPD palettes on CGB
+    pub(super) fn sprite_palette(&self, sprite: &Sprite) -> u8 {
+        if self.cgb_mode() {
+            sprite.attributes & 0x07
+        } else {
+            (sprite.attributes >> 4) & 0x01
+        }
+    }
+
+    /// Colors of the sprite's row on the current line, left to right
+    pub(super) fn sprite_colors(&self, vram: &[u8], sprite: &Sprite) -> [u8; 8] {
+        let height = self.sprite_height();
+        let mut row = (self.ly + 16).wrapping_sub(sprite.y);
+
+        // Y flip
+        if sprite.attributes & 0x40 != 0 {
+            row = height - 1 - row;
+        }
+
+        // In 8x16 mode the top tile is always the even one
+        let tile = if height == 16 {
+            sprite.tile & 0xFE
+        } else {
+            sprite.tile
+        };
+
+        // Sprites always use the 0x8000 addressing, CGB ones can be in bank 1
+        let bank = if self.cgb_mode() {
+            (sprite.attributes >> 3) as usize & 0x01
+        } else {
+            0
+        };
+        let address = bank * 0x2000 + tile as usize * 16 + row as usize * 2;
+        let (low, high) = (vram[address], vram[address + 1]);
+
+        let mut colors = [0; 8];
+        for (i, color) in colors.iter_mut().enumerate() {
+            // X flip
+            let bit = if sprite.attributes & 0x20 != 0 {
+                i
+            } else {
+                7 - i
+            };
+            *color = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+        }
+        colors
+    }
+}
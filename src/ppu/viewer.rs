@@ -0,0 +1,299 @@
+use std::fmt::Write;
+
+use crate::{
+    memory::Model,
+    video::{ColorCorrection, Image},
+};
+
+use super::{color::DMG_GREYS, Ppu, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const TILES_PER_BANK: usize = 384;
+// Sheet layout, 16 tiles across like most tile viewers
+const SHEET_COLUMNS: usize = 16;
+const MAP_SIZE: usize = 256;
+const SPRITE_COUNT: usize = 40;
+const OAM_COLUMNS: usize = 8;
+// Gap around each sprite of the OAM view
+const OAM_CELL_PADDING: usize = 2;
+
+const VIEWPORT_COLOR: u32 = 0xFF0000;
+const OAM_BACKGROUND: u32 = 0xFF00FF;
+
+// Debug views of VRAM and OAM, as images and text. Colors are the raw 15-bit
+// ones, without the display palette or color correction.
+impl Ppu {
+    fn tile_banks(&self) -> usize {
+        match self.model {
+            Model::Dmg => 1,
+            Model::Cgb => 2,
+        }
+    }
+
+    /// Every tile in VRAM, in greys as tiles have no palette of their own
+    ///
+    /// On CGB, the second bank is drawn to the right of the first.
+    pub fn tile_sheet(&self, vram: &[u8]) -> Image {
+        let rows = TILES_PER_BANK / SHEET_COLUMNS;
+        let bank_width = SHEET_COLUMNS * 8;
+        let width = bank_width * self.tile_banks();
+        let mut pixels = vec![0; width * rows * 8];
+
+        for bank in 0..self.tile_banks() {
+            for tile in 0..TILES_PER_BANK {
+                let left = bank * bank_width + (tile % SHEET_COLUMNS) * 8;
+                let top = (tile / SHEET_COLUMNS) * 8;
+                let colors = tile_colors(vram, bank * 0x2000 + tile * 16);
+
+                for (i, color) in colors.iter().enumerate() {
+                    let offset = (top + i / 8) * width + left + i % 8;
+                    pixels[offset] = rgb(DMG_GREYS[*color as usize]);
+                }
+            }
+        }
+
+        Image {
+            width,
+            height: rows * 8,
+            pixels,
+        }
+    }
+
+    /// One of the two 32x32 tile maps (0 for 0x9800, 1 for 0x9C00)
+    ///
+    /// The tiles are drawn with the current addressing mode and palettes. The
+    /// map used by the background gets the area shown on screen outlined.
+    pub fn tile_map(&self, vram: &[u8], map: usize) -> Image {
+        let base = if map == 0 { 0x1800 } else { 0x1C00 };
+        let mut pixels = vec![0; MAP_SIZE * MAP_SIZE];
+
+        for y in 0..MAP_SIZE {
+            for x in 0..MAP_SIZE {
+                let pixel = self.map_pixel(vram, base, x, y);
+                pixels[y * MAP_SIZE + x] = rgb(self.background_color(pixel).1);
+            }
+        }
+
+        let background_map = (self.lcdc >> 3) as usize & 0x01;
+        if map == background_map {
+            self.outline_viewport(&mut pixels);
+        }
+
+        Image {
+            width: MAP_SIZE,
+            height: MAP_SIZE,
+            pixels,
+        }
+    }
+
+    /// Frame around SCX/SCY, wrapping around the map edges
+    fn outline_viewport(&self, pixels: &mut [u32]) {
+        let (left, top) = (self.scx as usize, self.scy as usize);
+        let mut plot = |x: usize, y: usize| {
+            pixels[((top + y) % MAP_SIZE) * MAP_SIZE + (left + x) % MAP_SIZE] = VIEWPORT_COLOR;
+        };
+
+        for x in 0..SCREEN_WIDTH {
+            plot(x, 0);
+            plot(x, SCREEN_HEIGHT - 1);
+        }
+        for y in 0..SCREEN_HEIGHT {
+            plot(0, y);
+            plot(SCREEN_WIDTH - 1, y);
+        }
+    }
+
+    /// The 40 sprites in OAM order, 8 per row, with their flips and palettes
+    pub fn oam_sheet(&self, vram: &[u8], oam: &[u8]) -> Image {
+        let height = self.sprite_height() as usize;
+        let cell_width = 8 + OAM_CELL_PADDING * 2;
+        let cell_height = height + OAM_CELL_PADDING * 2;
+        let width = OAM_COLUMNS * cell_width;
+        let rows = SPRITE_COUNT / OAM_COLUMNS;
+        let mut pixels = vec![OAM_BACKGROUND; width * rows * cell_height];
+
+        for (index, entry) in oam.chunks(4).take(SPRITE_COUNT).enumerate() {
+            let (tile, attributes) = (entry[2], entry[3]);
+            let left = (index % OAM_COLUMNS) * cell_width + OAM_CELL_PADDING;
+            let top = (index / OAM_COLUMNS) * cell_height + OAM_CELL_PADDING;
+
+            let tile = if height == 16 { tile & 0xFE } else { tile };
+            let bank = if self.cgb_mode() {
+                (attributes >> 3) as usize & 0x01
+            } else {
+                0
+            };
+            let palette = if self.cgb_mode() {
+                attributes & 0x07
+            } else {
+                (attributes >> 4) & 0x01
+            };
+
+            for y in 0..height {
+                let row = if attributes & 0x40 != 0 {
+                    height - 1 - y
+                } else {
+                    y
+                };
+                // 8x16 sprites continue into the next tile
+                let address = bank * 0x2000 + (tile as usize + row / 8) * 16;
+                let colors = tile_colors(vram, address);
+
+                for x in 0..8 {
+                    let column = if attributes & 0x20 != 0 { 7 - x } else { x };
+                    let color = colors[(row % 8) * 8 + column];
+                    if color != 0 {
+                        let offset = (top + y) * width + left + x;
+                        pixels[offset] = rgb(self.obj_color(palette, color).1);
+                    }
+                }
+            }
+        }
+
+        Image {
+            width,
+            height: rows * cell_height,
+            pixels,
+        }
+    }
+
+    /// Hexadecimal dump of every tile, one line per tile
+    pub fn tile_sheet_text(&self, vram: &[u8]) -> String {
+        let mut text = String::new();
+
+        for bank in 0..self.tile_banks() {
+            for tile in 0..TILES_PER_BANK {
+                let start = bank * 0x2000 + tile * 16;
+                let bytes: Vec<String> = vram[start..start + 16]
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect();
+                writeln!(text, "{}:{:03X} {}", bank, tile, bytes.join(" ")).unwrap();
+            }
+        }
+
+        text
+    }
+
+    /// Tile numbers of a map, 32 per line, with the CGB attributes after them
+    pub fn tile_map_text(&self, vram: &[u8], map: usize) -> String {
+        let base = if map == 0 { 0x1800 } else { 0x1C00 };
+        let mut text = String::new();
+
+        for row in 0..32 {
+            let line: Vec<String> = (0..32)
+                .map(|column| {
+                    let offset = base + row * 32 + column;
+                    if self.cgb_mode() {
+                        format!("{:02X}/{:02X}", vram[offset], vram[0x2000 + offset])
+                    } else {
+                        format!("{:02X}", vram[offset])
+                    }
+                })
+                .collect();
+            writeln!(text, "{}", line.join(" ")).unwrap();
+        }
+
+        text
+    }
+
+    /// One line per OAM entry, with the attributes spelled out
+    pub fn oam_text(&self, oam: &[u8]) -> String {
+        let mut text = String::from("#   Y   X   tile attr palette bank flip priority\n");
+
+        for (index, entry) in oam.chunks(4).take(SPRITE_COUNT).enumerate() {
+            let attributes = entry[3];
+            let (palette, bank) = if self.cgb_mode() {
+                (
+                    format!("OCP{}", attributes & 0x07),
+                    (attributes >> 3) & 0x01,
+                )
+            } else {
+                (format!("OBP{}", (attributes >> 4) & 0x01), 0)
+            };
+            let flip = match attributes & 0x60 {
+                0x20 => "X",
+                0x40 => "Y",
+                0x60 => "XY",
+                _ => "-",
+            };
+            let priority = if attributes & 0x80 != 0 {
+                "behind"
+            } else {
+                "above"
+            };
+
+            writeln!(
+                text,
+                "{:<3} {:<3} {:<3} {:02X}   {:02X}   {:<7} {:<4} {:<4} {}",
+                index, entry[0], entry[1], entry[2], attributes, palette, bank, flip, priority
+            )
+            .unwrap();
+        }
+
+        text
+    }
+}
+
+/// Color numbers of a tile's 64 pixels, left to right and top to bottom
+fn tile_colors(vram: &[u8], address: usize) -> [u8; 64] {
+    let mut colors = [0; 64];
+
+    for (i, color) in colors.iter_mut().enumerate() {
+        let (low, high) = (vram[address + (i / 8) * 2], vram[address + (i / 8) * 2 + 1]);
+        let bit = 7 - (i % 8);
+        *color = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+    }
+
+    colors
+}
+
+fn rgb(color: u16) -> u32 {
+    ColorCorrection::Raw.apply(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_viewers() {
+        let mut vram = vec![0; 0x4000];
+        // Tile 1 is solid color 3, used at the top left of map 0
+        vram[0x10..0x20].fill(0xFF);
+        vram[0x1800] = 0x01;
+
+        let mut ppu = Ppu::new(Model::Dmg);
+        ppu.write(0xFF47, 0xE4);
+        ppu.write(0xFF49, 0xE4);
+        ppu.write(0xFF40, 0x91);
+        ppu.write(0xFF43, 200);
+
+        let sheet = ppu.tile_sheet(&vram);
+        assert_eq!((128, 192), (sheet.width, sheet.height));
+        assert_eq!(0xFFFFFF, sheet.pixels[0]);
+        assert_eq!(0x000000, sheet.pixels[8]);
+
+        // The viewport wraps around the right edge
+        let map = ppu.tile_map(&vram, 0);
+        assert_eq!(VIEWPORT_COLOR, map.pixels[200]);
+        assert_eq!(VIEWPORT_COLOR, map.pixels[103]);
+        assert_eq!(0x000000, map.pixels[MAP_SIZE + 1]);
+        assert_eq!(0xFFFFFF, ppu.tile_map(&vram, 1).pixels[0]);
+
+        let mut oam = [0; 0xA0];
+        oam[4..8].copy_from_slice(&[16, 8, 0x01, 0x30]);
+        let sheet = ppu.oam_sheet(&vram, &oam);
+        assert_eq!(OAM_BACKGROUND, sheet.pixels[0]);
+        let offset = OAM_CELL_PADDING * sheet.width + 12 + OAM_CELL_PADDING;
+        assert_eq!(0x000000, sheet.pixels[offset]);
+
+        let text = ppu.oam_text(&oam);
+        assert_eq!(41, text.lines().count());
+        assert!(text.lines().nth(2).unwrap().contains("OBP1"));
+        assert!(ppu.tile_map_text(&vram, 0).starts_with("01 00"));
+        assert!(ppu.tile_sheet_text(&vram).contains("0:001 FF FF"));
+
+        // CGB has a second bank of tiles
+        assert_eq!(256, Ppu::new(Model::Cgb).tile_sheet(&vram).width);
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static EXIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Turns SIGINT and SIGTERM into a request the emulation loop picks up, so
+/// the saves get written before exiting
+///
+/// A second signal exits right away, for when the loop isn't running.
+pub fn install() {
+    #[cfg(unix)]
+    unix::install();
+}
+
+/// Whether the host asked the emulator to stop
+pub fn exit_requested() -> bool {
+    EXIT_REQUESTED.load(Ordering::SeqCst)
+}
+
+// std has no signal handling, but it links against libc already
+#[cfg(unix)]
+mod unix {
+    use std::{process, sync::atomic::Ordering};
+
+    const SIGINT: i32 = 2;
+    const SIGTERM: i32 = 15;
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+
+    extern "C" fn handle(_signum: i32) {
+        if super::EXIT_REQUESTED.swap(true, Ordering::SeqCst) {
+            process::abort();
+        }
+    }
+
+    pub fn install() {
+        // The handler only touches an atomic or aborts, both signal safe
+        unsafe {
+            signal(SIGINT, handle);
+            signal(SIGTERM, handle);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    extern "C" {
+        fn raise(signum: i32) -> i32;
+    }
+
+    #[test]
+    pub fn test_signal_requests_exit() {
+        install();
+        assert!(!exit_requested());
+
+        unsafe {
+            raise(15);
+        }
+        assert!(exit_requested());
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::memory::Button;
+
+// Terminals only report key presses, repeated while held. The first repeat
+// comes after the keyboard's delay, up to 600 ms on most systems, then they
+// follow each other quickly.
+const FIRST_HOLD: Duration = Duration::from_millis(600);
+const REPEAT_HOLD: Duration = Duration::from_millis(100);
+
+/// What a key does
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Key {
+    Button(Button),
+    /// Write the saves now
+    FlushSaves,
+    Quit,
+}
+
+/// Arrows or WASD for the directions, X and Z for A and B, Enter for Start and
+/// Backspace or Tab for Select. Ctrl-S writes the saves, Q, Escape and Ctrl-C
+/// quit.
+fn key_for_byte(byte: u8) -> Option<Key> {
+    let button = match byte.to_ascii_lowercase() {
+        b'w' => Button::Up,
+        b'a' => Button::Left,
+        b's' => Button::Down,
+        b'd' => Button::Right,
+        b'x' => Button::A,
+        b'z' => Button::B,
+        b'\r' | b'\n' => Button::Start,
+        0x08 | 0x7F | b'\t' => Button::Select,
+        0x13 => return Some(Key::FlushSaves),
+        b'q' | 0x03 => return Some(Key::Quit),
+        _ => return None,
+    };
+    Some(Key::Button(button))
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum ParserState {
+    Ground,
+    Escape,
+    // After ESC [ or ESC O, until the final byte
+    Sequence,
+}
+
+/// Turns the bytes of a raw mode terminal into keys
+pub struct KeyParser {
+    state: ParserState,
+}
+
+impl Default for KeyParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyParser {
+    pub fn new() -> Self {
+        Self {
+            state: ParserState::Ground,
+        }
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Option<Key> {
+        match (self.state, byte) {
+            (ParserState::Ground, 0x1B) => {
+                self.state = ParserState::Escape;
+                None
+            }
+            (ParserState::Ground, _) => key_for_byte(byte),
+            (ParserState::Escape, b'[' | b'O') => {
+                self.state = ParserState::Sequence;
+                None
+            }
+            // Escape pressed on its own
+            (ParserState::Escape, _) => {
+                self.state = ParserState::Ground;
+                Some(Key::Quit)
+            }
+            // Parameters, as in ESC [ 1 ; 2 A
+            (ParserState::Sequence, 0x30..=0x3F) => None,
+            (ParserState::Sequence, _) => {
+                self.state = ParserState::Ground;
+                let button = match byte {
+                    b'A' => Button::Up,
+                    b'B' => Button::Down,
+                    b'C' => Button::Right,
+                    b'D' => Button::Left,
+                    _ => return None,
+                };
+                Some(Key::Button(button))
+            }
+        }
+    }
+
+    /// A lone Escape is only known once nothing follows it
+    pub fn flush(&mut self) -> Option<Key> {
+        let escape = self.state == ParserState::Escape;
+        self.state = ParserState::Ground;
+        escape.then_some(Key::Quit)
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Hold {
+    last_press: Instant,
+    // A press came while the button was held, the key is auto-repeating
+    repeating: bool,
+}
+
+/// Which buttons are held, guessed from the presses the terminal reports
+///
+/// A single press holds its button past the repeat delay, so holding a key
+/// doesn't release the button before the repeats start. Once repeating, the
+/// button is released soon after they stop.
+pub struct HeldButtons {
+    holds: [Option<Hold>; 8],
+}
+
+impl Default for HeldButtons {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeldButtons {
+    pub fn new() -> Self {
+        Self { holds: [None; 8] }
+    }
+
+    pub fn press(&mut self, button: Button, now: Instant) {
+        let hold = &mut self.holds[button as usize];
+        *hold = Some(Hold {
+            last_press: now,
+            repeating: hold.is_some(),
+        });
+    }
+
+    /// Whether the button is still held, releasing it once its keys stopped
+    pub fn is_held(&mut self, button: Button, now: Instant) -> bool {
+        let hold = &mut self.holds[button as usize];
+
+        if let Some(Hold {
+            last_press,
+            repeating,
+        }) = *hold
+        {
+            let duration = if repeating { REPEAT_HOLD } else { FIRST_HOLD };
+            if now - last_press > duration {
+                *hold = None;
+            }
+        }
+
+        hold.is_some()
+    }
+}
+
+/// The terminal in raw mode, without echo, restored when dropped
+///
+/// This goes through stty rather than termios to stay free of dependencies.
+pub struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    pub fn enable() -> io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        Ok(Self {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(File::open("/dev/tty")?)
+        .stderr(Stdio::inherit())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Bytes typed on the terminal, read on their own thread so the emulation
+/// never waits for a key
+pub fn spawn_reader() -> io::Result<Receiver<u8>> {
+    let mut tty = File::open("/dev/tty")?;
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut byte = [0];
+        while let Ok(1) = tty.read(&mut byte) {
+            if sender.send(byte[0]).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_key_parser() {
+        let mut parser = KeyParser::new();
+        let keys: Vec<Key> = b"x\x1B[A\x1B[1;2Dq"
+            .iter()
+            .filter_map(|byte| parser.feed(*byte))
+            .collect();
+        assert_eq!(
+            vec![
+                Key::Button(Button::A),
+                Key::Button(Button::Up),
+                Key::Button(Button::Left),
+                Key::Quit
+            ],
+            keys
+        );
+
+        assert_eq!(None, parser.feed(0x1B));
+        assert_eq!(Some(Key::Quit), parser.flush());
+        assert_eq!(None, parser.flush());
+    }
+
+    #[test]
+    pub fn test_held_buttons() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut held = HeldButtons::new();
+
+        // Held through a 500 ms repeat delay, then repeating every 30 ms
+        held.press(Button::A, at(0));
+        assert!(held.is_held(Button::A, at(450)));
+        held.press(Button::A, at(500));
+        held.press(Button::A, at(530));
+        assert!(held.is_held(Button::A, at(600)));
+        assert!(!held.is_held(Button::B, at(600)));
+
+        // Released shortly after the repeats stop
+        assert!(!held.is_held(Button::A, at(700)));
+
+        // A tap gets the full first hold again
+        held.press(Button::A, at(1000));
+        assert!(held.is_held(Button::A, at(1500)));
+        assert!(!held.is_held(Button::A, at(1700)));
+    }
+}
@@ -0,0 +1,151 @@
+mod input;
+mod screen;
+
+use std::{
+    io::{self, Write},
+    sync::mpsc::Receiver,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    memory::{Button, Mmu},
+    video::Image,
+};
+
+pub use input::{HeldButtons, Key, KeyParser};
+pub use screen::{ColorMode, Screen};
+
+use input::RawMode;
+
+const FPS_INTERVAL: Duration = Duration::from_secs(1);
+
+const ALL_BUTTONS: [Button; 8] = [
+    Button::Right,
+    Button::Left,
+    Button::Up,
+    Button::Down,
+    Button::A,
+    Button::B,
+    Button::Select,
+    Button::Start,
+];
+
+/// Interactive frontend running in the terminal, for headless machines
+///
+/// The frame is drawn with half blocks under a status line, and the keyboard
+/// drives the joypad. The terminal gets back to normal when this is dropped.
+pub struct Terminal {
+    screen: Screen,
+    title: String,
+    keys: Receiver<u8>,
+    parser: KeyParser,
+    held: HeldButtons,
+    quit: bool,
+    flush_requested: bool,
+    // Shown in the status line, like the result of a flush
+    message: String,
+    fps: f64,
+    frames: u32,
+    fps_start: Instant,
+    _raw_mode: RawMode,
+}
+
+impl Terminal {
+    pub fn new(mode: ColorMode, title: &str) -> io::Result<Self> {
+        let raw_mode = RawMode::enable()?;
+        let keys = input::spawn_reader()?;
+
+        // Alternate screen, cursor hidden, cleared
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x1B[?1049h\x1B[?25l\x1B[2J")?;
+        stdout.flush()?;
+
+        Ok(Self {
+            screen: Screen::new(mode),
+            title: title.trim_end_matches('\0').to_string(),
+            keys,
+            parser: KeyParser::new(),
+            held: HeldButtons::new(),
+            quit: false,
+            flush_requested: false,
+            message: String::new(),
+            fps: 0.0,
+            frames: 0,
+            fps_start: Instant::now(),
+            _raw_mode: raw_mode,
+        })
+    }
+
+    /// Draws a frame and the status line below it
+    pub fn draw(&mut self, image: &Image) -> io::Result<()> {
+        self.frames += 1;
+        let elapsed = self.fps_start.elapsed();
+        if elapsed >= FPS_INTERVAL {
+            self.fps = self.frames as f64 / elapsed.as_secs_f64();
+            self.frames = 0;
+            self.fps_start = Instant::now();
+        }
+
+        let mut output = self.screen.draw(image);
+        output.push_str(&format!(
+            "\x1B[{};1H\x1B[0m\x1B[K{} | {:.1} FPS | Ctrl-S to save, Q to quit {}",
+            Screen::rows(image) + 1,
+            self.title,
+            self.fps,
+            self.message
+        ));
+
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(output.as_bytes())?;
+        stdout.flush()
+    }
+
+    /// Applies the keys typed since the last call to the joypad
+    ///
+    /// Returns false once a quit key was pressed.
+    pub fn update_input(&mut self, mmu: &mut Mmu) -> bool {
+        let now = Instant::now();
+
+        let bytes: Vec<u8> = self.keys.try_iter().collect();
+        let keys: Vec<Key> = if bytes.is_empty() {
+            self.parser.flush().into_iter().collect()
+        } else {
+            bytes
+                .iter()
+                .filter_map(|byte| self.parser.feed(*byte))
+                .collect()
+        };
+
+        for key in keys {
+            match key {
+                Key::Button(button) => self.held.press(button, now),
+                Key::FlushSaves => self.flush_requested = true,
+                Key::Quit => self.quit = true,
+            }
+        }
+
+        for button in ALL_BUTTONS {
+            mmu.set_button(button, self.held.is_held(button, now));
+        }
+
+        !self.quit
+    }
+
+    /// Whether Ctrl-S was pressed since the last call
+    pub fn take_flush_request(&mut self) -> bool {
+        std::mem::take(&mut self.flush_requested)
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        self.message = message;
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        // Cursor shown, main screen back
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x1B[0m\x1B[?25h\x1B[?1049l");
+        let _ = stdout.flush();
+    }
+}
@@ -0,0 +1,211 @@
+use std::{fmt::Display, fmt::Write, str::FromStr};
+
+use crate::video::Image;
+
+// Top pixel in the foreground, bottom one in the background
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+/// How colors are sent to the terminal
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ColorMode {
+    /// 24-bit colors, exact
+    TrueColor,
+    /// The xterm 6x6x6 cube and grey ramp, for terminals without truecolor
+    Ansi256,
+}
+
+const NAMES: [(ColorMode, &str); 2] = [
+    (ColorMode::TrueColor, "truecolor"),
+    (ColorMode::Ansi256, "256"),
+];
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NAMES
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(s))
+            .map(|(mode, _)| *mode)
+            .ok_or_else(|| {
+                let names: Vec<&str> = NAMES.iter().map(|(_, name)| *name).collect();
+                format!(
+                    "Unknown color mode {}, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl Display for ColorMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (_, name) = NAMES.iter().find(|(mode, _)| mode == self).unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+impl ColorMode {
+    /// What gets compared and sent for a 24-bit color, the color itself or
+    /// its palette index
+    fn encode(self, rgb: u32) -> u32 {
+        match self {
+            ColorMode::TrueColor => rgb & 0xFFFFFF,
+            ColorMode::Ansi256 => ansi256(rgb) as u32,
+        }
+    }
+
+    /// SGR parameters, 38 for the foreground and 48 for the background
+    fn write_sgr(self, output: &mut String, layer: u8, color: u32) {
+        match self {
+            ColorMode::TrueColor => write!(
+                output,
+                "\x1B[{};2;{};{};{}m",
+                layer,
+                (color >> 16) & 0xFF,
+                (color >> 8) & 0xFF,
+                color & 0xFF
+            ),
+            ColorMode::Ansi256 => write!(output, "\x1B[{};5;{}m", layer, color),
+        }
+        .unwrap();
+    }
+}
+
+// Levels of the 6x6x6 cube, the grey ramp goes from 8 to 238 by 10
+const CUBE_LEVELS: [u32; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Closest xterm color, from the cube (16-231) or the grey ramp (232-255)
+fn ansi256(rgb: u32) -> u8 {
+    let channels = [(rgb >> 16) & 0xFF, (rgb >> 8) & 0xFF, rgb & 0xFF];
+    let squared = |a: u32, b: u32| (a as i32 - b as i32).pow(2) as u32;
+
+    let cube = channels.map(|channel| {
+        (0..6)
+            .min_by_key(|i| squared(channel, CUBE_LEVELS[*i]))
+            .unwrap()
+    });
+    let cube_error: u32 = (0..3)
+        .map(|i| squared(channels[i], CUBE_LEVELS[cube[i]]))
+        .sum();
+
+    let average = channels.iter().sum::<u32>() / 3;
+    let grey = (average.saturating_sub(3) / 10).min(23);
+    let grey_error: u32 = channels
+        .iter()
+        .map(|channel| squared(*channel, 8 + grey * 10))
+        .sum();
+
+    if grey_error < cube_error {
+        232 + grey as u8
+    } else {
+        16 + (cube[0] * 36 + cube[1] * 6 + cube[2]) as u8
+    }
+}
+
+/// A frame drawn with one character per two pixels, stacked vertically
+///
+/// The cells on screen are remembered so only the ones that changed get
+/// written, which keeps the output small enough for SSH.
+pub struct Screen {
+    mode: ColorMode,
+    // Encoded top and bottom colors of each cell, none before the first draw
+    cells: Vec<Option<(u32, u32)>>,
+    columns: usize,
+}
+
+impl Screen {
+    pub fn new(mode: ColorMode) -> Self {
+        Self {
+            mode,
+            cells: Vec::new(),
+            columns: 0,
+        }
+    }
+
+    /// Rows of characters the image takes
+    pub fn rows(image: &Image) -> usize {
+        image.height.div_ceil(2)
+    }
+
+    /// Escape sequences turning the last image drawn into this one, starting
+    /// at the top left of the terminal
+    pub fn draw(&mut self, image: &Image) -> String {
+        let rows = Self::rows(image);
+        if self.columns != image.width || self.cells.len() != image.width * rows {
+            self.columns = image.width;
+            self.cells = vec![None; image.width * rows];
+        }
+
+        let mut output = String::new();
+        // Where the cursor is, and the colors currently set
+        let mut cursor: Option<(usize, usize)> = None;
+        let mut colors: (Option<u32>, Option<u32>) = (None, None);
+
+        for row in 0..rows {
+            for column in 0..image.width {
+                let pixel = |y: usize| {
+                    let y = y.min(image.height - 1);
+                    self.mode.encode(image.pixels[y * image.width + column])
+                };
+                let cell = (pixel(row * 2), pixel(row * 2 + 1));
+
+                let index = row * image.width + column;
+                if self.cells[index] == Some(cell) {
+                    continue;
+                }
+                self.cells[index] = Some(cell);
+
+                if cursor != Some((row, column)) {
+                    write!(output, "\x1B[{};{}H", row + 1, column + 1).unwrap();
+                }
+                if colors.0 != Some(cell.0) {
+                    self.mode.write_sgr(&mut output, 38, cell.0);
+                }
+                if colors.1 != Some(cell.1) {
+                    self.mode.write_sgr(&mut output, 48, cell.1);
+                }
+                output.push(UPPER_HALF_BLOCK);
+
+                cursor = Some((row, column + 1));
+                colors = (Some(cell.0), Some(cell.1));
+            }
+        }
+
+        if !output.is_empty() {
+            output.push_str("\x1B[0m");
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_screen() {
+        let mut image = Image {
+            width: 2,
+            height: 4,
+            pixels: vec![0xFF0000, 0xFF0000, 0x0000FF, 0x0000FF, 0, 0, 0, 0],
+        };
+
+        let mut screen = Screen::new(ColorMode::TrueColor);
+        let output = screen.draw(&image);
+        assert!(output.starts_with("\x1B[1;1H\x1B[38;2;255;0;0m\x1B[48;2;0;0;255m\u{2580}\u{2580}"));
+        assert_eq!(4, output.matches(UPPER_HALF_BLOCK).count());
+
+        // Only the changed cell is written
+        assert_eq!("", screen.draw(&image));
+        image.pixels[7] = 0xFFFFFF;
+        let output = screen.draw(&image);
+        assert!(output.starts_with("\x1B[2;2H"));
+        assert_eq!(1, output.matches(UPPER_HALF_BLOCK).count());
+
+        assert_eq!(196, ansi256(0xFF0000));
+        assert_eq!(231, ansi256(0xFFFFFF));
+        assert_eq!(244, ansi256(0x808080));
+        assert_eq!(Ok(ColorMode::Ansi256), "256".parse());
+    }
+}
@@ -0,0 +1,105 @@
+use std::{fmt::Display, str::FromStr};
+
+/// How CGB colors are adjusted to look like they did on the real LCD
+///
+/// The CGB screen is darker and less saturated than a modern display, with
+/// colors bleeding into each other, so games picked bright and saturated
+/// values that look garish when shown as is.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ColorCorrection {
+    /// 15-bit values scaled to 8 bits, unchanged
+    Raw,
+    /// Green pulled towards blue like on the CGB LCD, without losing the
+    /// brightness of the original color
+    Balanced,
+    /// CGB games shown on a GBA, whose LCD is darker and bluer
+    Gba,
+}
+
+const NAMES: [(ColorCorrection, &str); 3] = [
+    (ColorCorrection::Raw, "raw"),
+    (ColorCorrection::Balanced, "balanced"),
+    (ColorCorrection::Gba, "gba"),
+];
+
+impl FromStr for ColorCorrection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NAMES
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(correction, _)| *correction)
+            .ok_or_else(|| {
+                let names: Vec<&str> = NAMES.iter().map(|(_, name)| *name).collect();
+                format!(
+                    "Unknown color correction {}, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl Display for ColorCorrection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = NAMES.iter().find(|(kind, _)| kind == self).unwrap().1;
+        write!(f, "{}", name)
+    }
+}
+
+impl ColorCorrection {
+    /// 24-bit RGB of a 15-bit CGB color
+    pub fn apply(&self, color: u16) -> u32 {
+        let r = (color & 0x1F) as u32;
+        let g = ((color >> 5) & 0x1F) as u32;
+        let b = ((color >> 10) & 0x1F) as u32;
+
+        let (r, g, b) = match self {
+            ColorCorrection::Raw => (expand(r), expand(g), expand(b)),
+            ColorCorrection::Balanced => balanced(expand(r), expand(g), expand(b)),
+            ColorCorrection::Gba => gba(r, g, b),
+        };
+
+        (r << 16) | (g << 8) | b
+    }
+}
+
+fn expand(channel: u32) -> u32 {
+    (channel << 3) | (channel >> 2)
+}
+
+fn balanced(r: u32, g: u32, b: u32) -> (u32, u32, u32) {
+    let mixed = (g * 3 + b) / 4;
+
+    // Scale back up so the brightest channel keeps its value
+    let old_max = r.max(g).max(b);
+    let new_max = r.max(mixed).max(b);
+    if new_max == 0 {
+        return (0, 0, 0);
+    }
+
+    let scale = |channel: u32| (channel * old_max + new_max / 2) / new_max;
+    (scale(r), scale(mixed), scale(b))
+}
+
+/// Linearised with the GBA LCD's steep gamma, mixed, then encoded for a
+/// regular display
+fn gba(r: u32, g: u32, b: u32) -> (u32, u32, u32) {
+    const LCD_GAMMA: f32 = 4.0;
+    const OUTPUT_GAMMA: f32 = 2.2;
+
+    let linear = |channel: u32| (channel as f32 / 31.0).powf(LCD_GAMMA);
+    let (r, g, b) = (linear(r), linear(g), linear(b));
+
+    let encode = |value: f32| {
+        let value = (value / 255.0).powf(1.0 / OUTPUT_GAMMA) * 255.0 * 255.0 / 280.0;
+        (value.round() as u32).min(0xFF)
+    };
+
+    (
+        encode(255.0 * r + 50.0 * g),
+        encode(10.0 * r + 230.0 * g + 30.0 * b),
+        encode(50.0 * r + 10.0 * g + 220.0 * b),
+    )
+}
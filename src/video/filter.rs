@@ -0,0 +1,368 @@
+use std::{fmt::Display, str::FromStr};
+
+use super::Image;
+
+// Brightness kept by the gaps between LCD pixels, in 256ths
+const LCD_GAP_BRIGHTNESS: u32 = 160;
+
+/// Upscaler working on the RGB output
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Filter {
+    /// Each pixel repeated in a square
+    Nearest(usize),
+    /// AdvanceMAME 2x, rounds diagonal edges without adding colors
+    Scale2x,
+    /// AdvanceMAME 3x
+    Scale3x,
+    /// Eric's Pixel Expansion, like Scale2x but leaves alone pixels with three
+    /// or more identical neighbours
+    Epx,
+    /// 2xBR, follows edges by comparing weighted color distances around each
+    /// corner and blends along them
+    Xbr,
+    /// Pixels drawn as dots separated by darker gaps, like the DMG LCD
+    LcdGrid(usize),
+}
+
+const NAMES: [&str; 6] = ["nearest", "scale2x", "scale3x", "epx", "xbr", "lcd"];
+
+impl Filter {
+    /// Width and height are multiplied by this
+    pub fn factor(&self) -> usize {
+        match self {
+            Filter::Nearest(factor) | Filter::LcdGrid(factor) => *factor,
+            Filter::Scale2x | Filter::Epx | Filter::Xbr => 2,
+            Filter::Scale3x => 3,
+        }
+    }
+
+    pub fn apply(&self, image: &Image) -> Image {
+        match self {
+            Filter::Nearest(factor) => image.scaled(*factor),
+            Filter::Scale2x => scale2x(image),
+            Filter::Scale3x => scale3x(image),
+            Filter::Epx => epx(image),
+            Filter::Xbr => xbr(image),
+            Filter::LcdGrid(factor) => lcd_grid(image, *factor),
+        }
+    }
+}
+
+/// A name, with the factor after a colon for nearest (default 2) and lcd
+/// (default 3)
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, factor) = match s.split_once(':') {
+            Some((name, factor)) => match factor.parse::<usize>() {
+                Ok(factor) if factor > 0 => (name, Some(factor)),
+                _ => return Err(format!("Invalid scale factor {}", factor)),
+            },
+            None => (s, None),
+        };
+
+        match (name, factor) {
+            ("nearest", _) => Ok(Filter::Nearest(factor.unwrap_or(2))),
+            ("lcd", _) => Ok(Filter::LcdGrid(factor.unwrap_or(3))),
+            ("scale2x", None) => Ok(Filter::Scale2x),
+            ("scale3x", None) => Ok(Filter::Scale3x),
+            ("epx", None) => Ok(Filter::Epx),
+            ("xbr", None) => Ok(Filter::Xbr),
+            _ => Err(format!(
+                "Unknown filter {}, expected one of: {}",
+                s,
+                NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Filter::Nearest(factor) => write!(f, "nearest:{}", factor),
+            Filter::Scale2x => write!(f, "scale2x"),
+            Filter::Scale3x => write!(f, "scale3x"),
+            Filter::Epx => write!(f, "epx"),
+            Filter::Xbr => write!(f, "xbr"),
+            Filter::LcdGrid(factor) => write!(f, "lcd:{}", factor),
+        }
+    }
+}
+
+/// Filters applied one after the other, e.g. scale2x then nearest:2
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct FilterChain {
+    pub filters: Vec<Filter>,
+}
+
+impl FilterChain {
+    pub fn apply(&self, image: &Image) -> Image {
+        self.filters
+            .iter()
+            .fold(image.clone(), |image, filter| filter.apply(&image))
+    }
+
+    /// Size of the filtered image
+    pub fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        let factor: usize = self.filters.iter().map(Filter::factor).product();
+        (width * factor, height * factor)
+    }
+}
+
+/// Comma separated filters, in the order they are applied
+impl FromStr for FilterChain {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let filters = s
+            .split(',')
+            .map(|filter| filter.trim().parse())
+            .collect::<Result<_, _>>()?;
+        Ok(Self { filters })
+    }
+}
+
+/// Pixel at (x, y), the edge pixels repeating outside the image
+fn pixel(image: &Image, x: isize, y: isize) -> u32 {
+    let x = x.clamp(0, image.width as isize - 1) as usize;
+    let y = y.clamp(0, image.height as isize - 1) as usize;
+    image.pixels[y * image.width + x]
+}
+
+/// Runs a filter producing factor x factor pixels from each source pixel
+fn expand<F>(image: &Image, factor: usize, mut block: F) -> Image
+where
+    F: FnMut(isize, isize, &mut [u32]),
+{
+    let width = image.width * factor;
+    let mut pixels = vec![0; width * image.height * factor];
+    let mut output = vec![0; factor * factor];
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            block(x as isize, y as isize, &mut output);
+
+            for (i, value) in output.iter().enumerate() {
+                let offset = (y * factor + i / factor) * width + x * factor + i % factor;
+                pixels[offset] = *value;
+            }
+        }
+    }
+
+    Image {
+        width,
+        height: image.height * factor,
+        pixels,
+    }
+}
+
+//  A B C
+//  D E F
+//  G H I
+fn neighbours(image: &Image, x: isize, y: isize) -> [u32; 9] {
+    let mut block = [0; 9];
+    for (i, value) in block.iter_mut().enumerate() {
+        *value = pixel(image, x + (i % 3) as isize - 1, y + (i / 3) as isize - 1);
+    }
+    block
+}
+
+fn scale2x(image: &Image) -> Image {
+    expand(image, 2, |x, y, output| {
+        let [_, b, _, d, e, f, _, h, _] = neighbours(image, x, y);
+
+        if b != h && d != f {
+            output[0] = if d == b { d } else { e };
+            output[1] = if b == f { f } else { e };
+            output[2] = if d == h { d } else { e };
+            output[3] = if h == f { f } else { e };
+        } else {
+            output.fill(e);
+        }
+    })
+}
+
+fn scale3x(image: &Image) -> Image {
+    expand(image, 3, |x, y, output| {
+        let [a, b, c, d, e, f, g, h, i] = neighbours(image, x, y);
+
+        if b != h && d != f {
+            output[0] = if d == b { d } else { e };
+            output[1] = if (d == b && e != c) || (b == f && e != a) {
+                b
+            } else {
+                e
+            };
+            output[2] = if b == f { f } else { e };
+            output[3] = if (d == b && e != g) || (d == h && e != a) {
+                d
+            } else {
+                e
+            };
+            output[4] = e;
+            output[5] = if (b == f && e != i) || (h == f && e != c) {
+                f
+            } else {
+                e
+            };
+            output[6] = if d == h { d } else { e };
+            output[7] = if (d == h && e != i) || (h == f && e != g) {
+                h
+            } else {
+                e
+            };
+            output[8] = if h == f { f } else { e };
+        } else {
+            output.fill(e);
+        }
+    })
+}
+
+fn epx(image: &Image) -> Image {
+    expand(image, 2, |x, y, output| {
+        let [_, a, _, c, p, b, _, d, _] = neighbours(image, x, y);
+
+        let same = [a == b, a == c, a == d, b == c, b == d, c == d];
+        // Three or more equal neighbours would flatten small details
+        if same.iter().filter(|same| **same).count() >= 3 {
+            output.fill(p);
+            return;
+        }
+
+        output[0] = if c == a { a } else { p };
+        output[1] = if a == b { b } else { p };
+        output[2] = if d == c { c } else { p };
+        output[3] = if b == d { d } else { p };
+    })
+}
+
+/// Perceptual distance between two colors, weighted towards luma
+fn distance(a: u32, b: u32) -> u32 {
+    let channel = |color: u32, shift: u32| ((color >> shift) & 0xFF) as i32;
+    let (r, g, b) = (
+        channel(a, 16) - channel(b, 16),
+        channel(a, 8) - channel(b, 8),
+        channel(a, 0) - channel(b, 0),
+    );
+
+    let y = (r * 299 + g * 587 + b * 114) / 1000;
+    let u = (b * 1000 - y * 1000) * 492 / 1_000_000;
+    let v = (r * 1000 - y * 1000) * 877 / 1_000_000;
+
+    (y.unsigned_abs() * 48) + (u.unsigned_abs() * 7) + (v.unsigned_abs() * 6)
+}
+
+/// Half way between two colors
+fn blend(a: u32, b: u32) -> u32 {
+    ((a & 0xFEFEFE) >> 1) + ((b & 0xFEFEFE) >> 1) + (a & b & 0x010101)
+}
+
+fn xbr(image: &Image) -> Image {
+    expand(image, 2, |x, y, output| {
+        let e = pixel(image, x, y);
+        output.fill(e);
+
+        // Each corner is the bottom right one of the block rotated
+        for (corner, (dx, dy)) in [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter().enumerate() {
+            // Neighbours of the rotated block, in its own coordinates
+            let at = |u: isize, v: isize| pixel(image, x + u * dx, y + v * dy);
+            let (f, h, i) = (at(1, 0), at(0, 1), at(1, 1));
+            let (c, g, d, b) = (at(1, -1), at(-1, 1), at(-1, 0), at(0, -1));
+            let (f4, h5, i4, i5) = (at(2, 0), at(0, 2), at(2, 1), at(1, 2));
+
+            // Along the edge from F to H, or across it from E to I
+            let along = distance(e, c)
+                + distance(e, g)
+                + distance(i, f4)
+                + distance(i, h5)
+                + 4 * distance(h, f);
+            let across = distance(h, d)
+                + distance(h, i5)
+                + distance(f, i4)
+                + distance(f, b)
+                + 4 * distance(e, i);
+
+            if along < across {
+                let closest = if distance(e, f) <= distance(e, h) {
+                    f
+                } else {
+                    h
+                };
+                output[corner] = blend(e, closest);
+            }
+        }
+    })
+}
+
+fn lcd_grid(image: &Image, factor: usize) -> Image {
+    let mut scaled = image.scaled(factor);
+    if factor < 2 {
+        return scaled;
+    }
+
+    let darken = |color: u32| {
+        [16, 8, 0].iter().fold(0, |result, shift| {
+            let channel = (color >> shift) & 0xFF;
+            result | ((channel * LCD_GAP_BRIGHTNESS / 256) << shift)
+        })
+    };
+
+    // The last row and column of each pixel are the gap
+    for (i, value) in scaled.pixels.iter_mut().enumerate() {
+        let (x, y) = (i % scaled.width, i / scaled.width);
+        if x % factor == factor - 1 || y % factor == factor - 1 {
+            *value = darken(*value);
+        }
+    }
+
+    scaled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A black diagonal on white
+    fn diagonal() -> Image {
+        let (w, k) = (0xFFFFFF, 0x000000);
+        Image {
+            width: 3,
+            height: 3,
+            pixels: vec![k, w, w, w, k, w, w, w, k],
+        }
+    }
+
+    #[test]
+    pub fn test_filters() {
+        let chain: FilterChain = "scale2x, nearest:3".parse().unwrap();
+        assert_eq!(vec![Filter::Scale2x, Filter::Nearest(3)], chain.filters);
+        assert_eq!((960, 864), chain.output_size(160, 144));
+        assert!("scale2x:2".parse::<Filter>().is_err());
+
+        let image = diagonal();
+        for filter in [
+            Filter::Nearest(2),
+            Filter::Scale2x,
+            Filter::Scale3x,
+            Filter::Epx,
+            Filter::Xbr,
+            Filter::LcdGrid(3),
+        ] {
+            let output = filter.apply(&image);
+            assert_eq!(3 * filter.factor(), output.width, "{}", filter);
+            assert_eq!(3 * filter.factor(), output.height, "{}", filter);
+        }
+
+        // The diagonal gets thicker where its pixels meet
+        let output = Filter::Scale2x.apply(&image);
+        assert_eq!(0x000000, output.pixels[6 + 2]);
+        assert_eq!(0xFFFFFF, output.pixels[6 + 3]);
+
+        // Gaps between the LCD dots
+        let output = Filter::LcdGrid(2).apply(&image);
+        assert_eq!(0xFFFFFF, output.pixels[2]);
+        assert_eq!(0x9F9F9F, output.pixels[3]);
+    }
+}
@@ -0,0 +1,103 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::clock::{CLOCK_FREQUENCY, CYCLES_PER_FRAME};
+
+// Time for a DMG LCD pixel to get most of the way to a new shade, about a
+// frame, enough for flickering sprites to look half transparent
+const RESPONSE_TIME_MS: f32 = 18.0;
+
+/// How the slow response of the LCD is emulated
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Ghosting {
+    /// Frames shown as the PPU made them
+    Off,
+    /// Each frame averaged with the previous one
+    Blend,
+    /// Pixels move towards their new value exponentially, leaving trails
+    /// over several frames
+    Decay,
+}
+
+const NAMES: [(Ghosting, &str); 3] = [
+    (Ghosting::Off, "off"),
+    (Ghosting::Blend, "blend"),
+    (Ghosting::Decay, "decay"),
+];
+
+impl FromStr for Ghosting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NAMES
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(ghosting, _)| *ghosting)
+            .ok_or_else(|| {
+                let names: Vec<&str> = NAMES.iter().map(|(_, name)| *name).collect();
+                format!(
+                    "Unknown ghosting mode {}, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl Display for Ghosting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = NAMES.iter().find(|(kind, _)| kind == self).unwrap().1;
+        write!(f, "{}", name)
+    }
+}
+
+/// Blends successive frames, given as levels between 0 and 1
+///
+/// Levels are shades or color channels, before any palette is applied so
+/// blended pixels get in-between colors of the palette.
+pub struct FrameBlender {
+    ghosting: Ghosting,
+    // Last frame as given for Blend, as displayed for Decay
+    previous: Vec<f32>,
+}
+
+impl FrameBlender {
+    pub fn new(ghosting: Ghosting) -> Self {
+        Self {
+            ghosting,
+            previous: Vec::new(),
+        }
+    }
+
+    pub fn ghosting(&self) -> Ghosting {
+        self.ghosting
+    }
+
+    /// Blends the frame in place with the ones before it
+    pub fn blend(&mut self, levels: &mut [f32]) {
+        // Nothing to blend with on the first frame or after a size change
+        if self.previous.len() != levels.len() {
+            self.previous = levels.to_vec();
+            return;
+        }
+
+        match self.ghosting {
+            Ghosting::Off => {}
+            Ghosting::Blend => {
+                for (level, previous) in levels.iter_mut().zip(self.previous.iter_mut()) {
+                    let current = *level;
+                    *level = (current + *previous) / 2.0;
+                    *previous = current;
+                }
+            }
+            Ghosting::Decay => {
+                let frame_ms = CYCLES_PER_FRAME as f32 * 1000.0 / CLOCK_FREQUENCY as f32;
+                let retained = (-frame_ms / RESPONSE_TIME_MS).exp();
+
+                for (level, previous) in levels.iter_mut().zip(self.previous.iter_mut()) {
+                    *level += (*previous - *level) * retained;
+                    *previous = *level;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::{fs, io, path::Path};
+
+use super::png;
+
+/// RGB image, one 0xRRGGBB value per pixel
+#[derive(Clone, PartialEq, Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Image {
+    /// Every pixel repeated factor times in both directions
+    pub fn scaled(&self, factor: usize) -> Image {
+        let factor = factor.max(1);
+        let width = self.width * factor;
+        let mut pixels = Vec::with_capacity(width * self.height * factor);
+
+        for line in self.pixels.chunks_exact(self.width.max(1)) {
+            let start = pixels.len();
+            for pixel in line {
+                pixels.extend(std::iter::repeat_n(*pixel, factor));
+            }
+            for _ in 1..factor {
+                pixels.extend_from_within(start..start + width);
+            }
+        }
+
+        Image {
+            width,
+            height: self.height * factor,
+            pixels,
+        }
+    }
+
+    pub fn to_png(&self) -> Vec<u8> {
+        png::encode(self)
+    }
+
+    /// Binary PPM (P6), trivial to read back and compare
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for pixel in &self.pixels {
+            ppm.extend_from_slice(&pixel.to_be_bytes()[1..]);
+        }
+        ppm
+    }
+
+    /// Writes a PPM for a .ppm extension, a PNG otherwise
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let is_ppm = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("ppm"));
+
+        let data = if is_ppm { self.to_ppm() } else { self.to_png() };
+        fs::write(path, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compression::inflate, hash::crc32};
+
+    fn image() -> Image {
+        Image {
+            width: 2,
+            height: 1,
+            pixels: vec![0xFF0000, 0x0000FF],
+        }
+    }
+
+    #[test]
+    pub fn test_scaled() {
+        let scaled = image().scaled(2);
+        assert_eq!(4, scaled.width);
+        assert_eq!(2, scaled.height);
+        assert_eq!(
+            [0xFF0000, 0xFF0000, 0x0000FF, 0x0000FF].repeat(2),
+            scaled.pixels
+        );
+    }
+
+    #[test]
+    pub fn test_ppm() {
+        assert_eq!(
+            b"P6\n2 1\n255\n\xFF\x00\x00\x00\x00\xFF".to_vec(),
+            image().to_ppm()
+        );
+    }
+
+    #[test]
+    pub fn test_png() {
+        let png = image().to_png();
+        assert_eq!(b"\x89PNG\r\n\x1A\n", &png[..8]);
+
+        // IHDR, then IDAT holding the zlib stream
+        assert_eq!(b"IHDR", &png[12..16]);
+        assert_eq!(crc32(&png[12..29]).to_be_bytes(), png[29..33]);
+        let length = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(b"IDAT", &png[37..41]);
+
+        let zlib = &png[41..41 + length];
+        assert_eq!(0, u16::from_be_bytes([zlib[0], zlib[1]]) % 31);
+        assert_eq!(
+            vec![0, 0xFF, 0, 0, 0, 0, 0xFF],
+            inflate(&zlib[2..zlib.len() - 4], usize::MAX).unwrap()
+        );
+        assert!(png.ends_with(b"IEND\xAE\x42\x60\x82"));
+    }
+}
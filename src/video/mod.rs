@@ -0,0 +1,233 @@
+mod correction;
+mod filter;
+mod ghosting;
+mod image;
+mod palette;
+mod png;
+mod y4m;
+
+use std::{io, path::Path};
+
+use crate::{
+    memory::Model,
+    ppu::{Ppu, SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+
+pub use correction::ColorCorrection;
+pub use filter::{Filter, FilterChain};
+pub use ghosting::{FrameBlender, Ghosting};
+pub use image::Image;
+pub use palette::DmgPalette;
+pub use y4m::Y4mWriter;
+
+/// Saves a frame, a PPM for a .ppm path and a PNG otherwise
+///
+/// The frame is the one `PostProcessor::process` returned for the display,
+/// processing it again would blend it twice with ghosting on.
+pub fn save_screenshot(frame: &Image, filters: &FilterChain, path: &Path) -> io::Result<()> {
+    filters.apply(frame).save(path)
+}
+
+/// Turns the PPU's frames into the colors shown on screen
+///
+/// The PPU only produces DMG shades or CGB 15-bit colors, what they look like
+/// is decided here so tests can keep comparing the raw values. Ghosting comes
+/// first, then the palette or the color correction.
+pub struct PostProcessor {
+    palette: DmgPalette,
+    correction: ColorCorrection,
+    // Every 15-bit color, already corrected
+    colors: Vec<u32>,
+    blender: FrameBlender,
+}
+
+impl PostProcessor {
+    pub fn new(palette: DmgPalette, correction: ColorCorrection) -> Self {
+        Self {
+            palette,
+            correction,
+            colors: (0..0x8000).map(|color| correction.apply(color)).collect(),
+            blender: FrameBlender::new(Ghosting::Off),
+        }
+    }
+
+    pub fn palette(&self) -> DmgPalette {
+        self.palette
+    }
+
+    pub fn correction(&self) -> ColorCorrection {
+        self.correction
+    }
+
+    pub fn ghosting(&self) -> Ghosting {
+        self.blender.ghosting()
+    }
+
+    pub fn set_ghosting(&mut self, ghosting: Ghosting) {
+        self.blender = FrameBlender::new(ghosting);
+    }
+
+    /// Last complete frame of the PPU, shades for a DMG and colors for a CGB
+    ///
+    /// With ghosting on, this is to be called once per frame.
+    pub fn process(&mut self, ppu: &Ppu) -> Image {
+        match ppu.model() {
+            Model::Dmg => self.map_shades(ppu.frame(), SCREEN_WIDTH),
+            Model::Cgb => self.map_colors(ppu.rgb_frame(), SCREEN_WIDTH),
+        }
+    }
+
+    /// What the screen shows while the LCD is off
+    pub fn lcd_off(&self, model: Model) -> Image {
+        let white = match model {
+            Model::Dmg => self.palette.colors[0],
+            Model::Cgb => self.colors[0x7FFF],
+        };
+
+        Image {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            pixels: vec![white; SCREEN_WIDTH * SCREEN_HEIGHT],
+        }
+    }
+
+    pub fn map_shades(&mut self, shades: &[u8], width: usize) -> Image {
+        let pixels = if self.ghosting() == Ghosting::Off {
+            shades
+                .iter()
+                .map(|shade| self.palette.colors[(shade & 0x03) as usize])
+                .collect()
+        } else {
+            let mut levels: Vec<f32> = shades
+                .iter()
+                .map(|shade| (shade & 0x03) as f32 / 3.0)
+                .collect();
+            self.blender.blend(&mut levels);
+
+            levels
+                .iter()
+                .map(|level| self.shade_between(level * 3.0))
+                .collect()
+        };
+
+        Image {
+            width,
+            height: shades.len() / width,
+            pixels,
+        }
+    }
+
+    pub fn map_colors(&mut self, colors: &[u16], width: usize) -> Image {
+        let pixels = if self.ghosting() == Ghosting::Off {
+            colors
+                .iter()
+                .map(|color| self.colors[(color & 0x7FFF) as usize])
+                .collect()
+        } else {
+            let mut levels: Vec<f32> = colors
+                .iter()
+                .flat_map(|color| [0, 5, 10].map(|shift| ((color >> shift) & 0x1F) as f32 / 31.0))
+                .collect();
+            self.blender.blend(&mut levels);
+
+            levels
+                .chunks_exact(3)
+                .map(|channels| {
+                    let color = channels
+                        .iter()
+                        .zip([0, 5, 10])
+                        .fold(0, |color, (level, shift)| {
+                            color | (((level * 31.0).round() as u16) << shift)
+                        });
+                    self.colors[color as usize]
+                })
+                .collect()
+        };
+
+        Image {
+            width,
+            height: colors.len() / width,
+            pixels,
+        }
+    }
+
+    /// Palette color for a fractional shade, mixing its two neighbours
+    fn shade_between(&self, shade: f32) -> u32 {
+        let shade = shade.clamp(0.0, 3.0);
+        let low = (shade.floor() as usize).min(2);
+        let weight = shade - low as f32;
+
+        let (from, to) = (self.palette.colors[low], self.palette.colors[low + 1]);
+        [16, 8, 0].iter().fold(0, |color, shift| {
+            let a = ((from >> shift) & 0xFF) as f32;
+            let b = ((to >> shift) & 0xFF) as f32;
+            color | (((a + (b - a) * weight).round() as u32) << shift)
+        })
+    }
+}
+
+impl Default for PostProcessor {
+    fn default() -> Self {
+        Self::new(DmgPalette::default(), ColorCorrection::Balanced)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_dmg_palette() {
+        let palette: DmgPalette = "ffffff,AAAAAA,#555555,000000".parse().unwrap();
+        assert_eq!([0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000], palette.colors);
+        assert_eq!(DmgPalette::LIGHT, "light".parse().unwrap());
+        assert!("ffffff,000000".parse::<DmgPalette>().is_err());
+
+        let mut processor = PostProcessor::new(DmgPalette::GREY, ColorCorrection::Raw);
+        let image = processor.map_shades(&[0, 1, 2, 3], 2);
+        assert_eq!(2, image.height);
+        assert_eq!(DmgPalette::GREY.colors.to_vec(), image.pixels);
+    }
+
+    #[test]
+    pub fn test_color_correction() {
+        for correction in [ColorCorrection::Raw, ColorCorrection::Balanced] {
+            // Black and white stay as they are
+            assert_eq!(0x000000, correction.apply(0x0000));
+            assert_eq!(0xFFFFFF, correction.apply(0x7FFF), "{}", correction);
+        }
+
+        // The GBA screen never gets as bright
+        assert!(ColorCorrection::Gba.apply(0x7FFF) & 0xFF < 0xFF);
+
+        assert_eq!(0x00FF00, ColorCorrection::Raw.apply(0x03E0));
+        // Green pulled towards blue is scaled back to full brightness
+        assert_eq!(0x00FF00, ColorCorrection::Balanced.apply(0x03E0));
+        let cyan = ColorCorrection::Balanced.apply(0x7FE0);
+        assert_eq!(0x00FFFF, cyan);
+        // Which brightens the other channels of mixed colors
+        let teal = ColorCorrection::Balanced.apply(0x3FE0);
+        assert_eq!(0x00FF8D, teal);
+    }
+
+    #[test]
+    pub fn test_ghosting() {
+        let palette: DmgPalette = "ffffff,aaaaaa,555555,000000".parse().unwrap();
+        let mut processor = PostProcessor::new(palette, ColorCorrection::Raw);
+        processor.set_ghosting(Ghosting::Blend);
+
+        // A sprite flickering every other frame looks half transparent
+        processor.map_shades(&[0, 3], 2);
+        let image = processor.map_shades(&[3, 3], 2);
+        assert_eq!(vec![0x808080, 0x000000], image.pixels);
+        let image = processor.map_shades(&[0, 3], 2);
+        assert_eq!(vec![0x808080, 0x000000], image.pixels);
+
+        // The decay takes a few frames to settle
+        processor.set_ghosting(Ghosting::Decay);
+        processor.map_colors(&[0x0000], 1);
+        let first = processor.map_colors(&[0x7FFF], 1).pixels[0];
+        let second = processor.map_colors(&[0x7FFF], 1).pixels[0];
+        assert!(first < second && second < 0xFFFFFF);
+    }
+}
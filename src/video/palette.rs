@@ -0,0 +1,67 @@
+use std::str::FromStr;
+
+/// Colors the four DMG shades are displayed with, lightest first
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct DmgPalette {
+    pub colors: [u32; 4],
+}
+
+const PRESETS: [(&str, DmgPalette); 3] = [
+    ("green", DmgPalette::GREEN),
+    ("grey", DmgPalette::GREY),
+    ("light", DmgPalette::LIGHT),
+];
+
+impl DmgPalette {
+    /// Original Game Boy, green on a green tinted LCD
+    pub const GREEN: Self = Self {
+        colors: [0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F],
+    };
+    /// Game Boy Pocket, black and white with a slight olive cast
+    pub const GREY: Self = Self {
+        colors: [0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F],
+    };
+    /// Game Boy Light, with the backlight on
+    pub const LIGHT: Self = Self {
+        colors: [0x00B581, 0x009A71, 0x00694A, 0x004F3B],
+    };
+}
+
+impl Default for DmgPalette {
+    fn default() -> Self {
+        Self::GREEN
+    }
+}
+
+/// A preset name, or four comma separated RRGGBB colors
+impl FromStr for DmgPalette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, palette)) = PRESETS.iter().find(|(name, _)| *name == s) {
+            return Ok(*palette);
+        }
+
+        let colors: Vec<u32> = s
+            .split(',')
+            .map(|color| {
+                let color = color.trim().trim_start_matches('#');
+                match u32::from_str_radix(color, 16) {
+                    Ok(value) if color.len() == 6 => Ok(value),
+                    _ => Err(format!("Invalid color {}, expected RRGGBB", color)),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        let colors: [u32; 4] = colors.try_into().map_err(|_| {
+            let names: Vec<&str> = PRESETS.iter().map(|(name, _)| *name).collect();
+            format!(
+                "Unknown palette {}, expected one of: {}, or 4 RRGGBB colors",
+                s,
+                names.join(", ")
+            )
+        })?;
+
+        Ok(Self { colors })
+    }
+}
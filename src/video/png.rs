@@ -0,0 +1,56 @@
+use crate::{
+    compression::deflate,
+    hash::{adler32, crc32},
+};
+
+use super::Image;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+// 8 bits per channel, truecolor
+const BIT_DEPTH: u8 = 8;
+const COLOR_TYPE_RGB: u8 = 2;
+
+/// Encodes an image as an RGB PNG
+pub fn encode(image: &Image) -> Vec<u8> {
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    // Default compression, filtering and no interlacing
+    header.extend_from_slice(&[BIT_DEPTH, COLOR_TYPE_RGB, 0, 0, 0]);
+
+    // Each line starts with its filter, none as pixel art compresses well as is
+    let mut pixels = Vec::with_capacity((image.width * 3 + 1) * image.height);
+    for line in image.pixels.chunks_exact(image.width.max(1)) {
+        pixels.push(0);
+        for pixel in line {
+            pixels.extend_from_slice(&pixel.to_be_bytes()[1..]);
+        }
+    }
+
+    let mut png = SIGNATURE.to_vec();
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &zlib(&pixels));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Deflate stream wrapped with the zlib header and checksum
+fn zlib(data: &[u8]) -> Vec<u8> {
+    // 32K window, no dictionary, header checksum making it a multiple of 31
+    let mut output = vec![0x78, 0x01];
+    output.extend(deflate(data));
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
@@ -0,0 +1,138 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::clock::{CLOCK_FREQUENCY, CYCLES_PER_FRAME};
+
+use super::Image;
+
+/// Records frames to a YUV4MPEG2 stream, at the exact refresh rate
+///
+/// Frames are stored uncompressed in full range 4:4:4, which any ffmpeg can
+/// read and transcode.
+pub struct Y4mWriter<W: Write> {
+    output: W,
+    width: usize,
+    height: usize,
+    // Keeps a single frame for each time the LCD is turned off
+    dedupe_lcd_off: bool,
+    lcd_off: bool,
+    frames: u64,
+}
+
+impl Y4mWriter<BufWriter<File>> {
+    pub fn create(path: &Path, width: usize, height: usize) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), width, height)
+    }
+}
+
+impl<W: Write> Y4mWriter<W> {
+    pub fn new(mut output: W, width: usize, height: usize) -> io::Result<Self> {
+        writeln!(
+            output,
+            "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444 XCOLORRANGE=FULL",
+            width, height, CLOCK_FREQUENCY, CYCLES_PER_FRAME
+        )?;
+
+        Ok(Self {
+            output,
+            width,
+            height,
+            dedupe_lcd_off: false,
+            lcd_off: false,
+            frames: 0,
+        })
+    }
+
+    pub fn set_dedupe_lcd_off(&mut self, dedupe: bool) {
+        self.dedupe_lcd_off = dedupe;
+    }
+
+    /// Frames written so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Adds a frame, the blank screen shown while the LCD is off if lcd_on is
+    /// false
+    pub fn write_frame(&mut self, image: &Image, lcd_on: bool) -> io::Result<()> {
+        if image.width != self.width || image.height != self.height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "frame is {}x{}, the video {}x{}",
+                    image.width, image.height, self.width, self.height
+                ),
+            ));
+        }
+
+        let repeated = !lcd_on && self.lcd_off;
+        self.lcd_off = !lcd_on;
+        if repeated && self.dedupe_lcd_off {
+            return Ok(());
+        }
+
+        let mut planes = vec![0; image.pixels.len() * 3];
+        let (y, chroma) = planes.split_at_mut(image.pixels.len());
+        let (u, v) = chroma.split_at_mut(image.pixels.len());
+
+        for (i, pixel) in image.pixels.iter().enumerate() {
+            (y[i], u[i], v[i]) = to_yuv(*pixel);
+        }
+
+        self.output.write_all(b"FRAME\n")?;
+        self.output.write_all(&planes)?;
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// Full range BT.601, which is what ffmpeg expects from RGB sources
+fn to_yuv(rgb: u32) -> (u8, u8, u8) {
+    let r = ((rgb >> 16) & 0xFF) as i32;
+    let g = ((rgb >> 8) & 0xFF) as i32;
+    let b = (rgb & 0xFF) as i32;
+
+    let y = (77 * r + 150 * g + 29 * b + 128) >> 8;
+    let u = ((-43 * r - 85 * g + 128 * b + 128) >> 8) + 128;
+    let v = ((128 * r - 107 * g - 21 * b + 128) >> 8) + 128;
+
+    let clamp = |value: i32| value.clamp(0, 0xFF) as u8;
+    (clamp(y), clamp(u), clamp(v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_y4m() {
+        let image = Image {
+            width: 2,
+            height: 1,
+            pixels: vec![0xFFFFFF, 0x000000],
+        };
+
+        let mut writer = Y4mWriter::new(Vec::new(), 2, 1).unwrap();
+        writer.set_dedupe_lcd_off(true);
+        writer.write_frame(&image, true).unwrap();
+        // Only the first of a run of LCD off frames is kept
+        writer.write_frame(&image, false).unwrap();
+        writer.write_frame(&image, false).unwrap();
+        writer.write_frame(&image, true).unwrap();
+        assert_eq!(3, writer.frames());
+
+        let header = b"YUV4MPEG2 W2 H1 F4194304:70224 Ip A1:1 C444 XCOLORRANGE=FULL\n";
+        let frame = b"FRAME\n\xFF\x00\x80\x80\x80\x80";
+        assert_eq!([&header[..], &frame.repeat(3)].concat(), writer.output);
+
+        assert!(writer.write_frame(&image.scaled(2), true).is_err());
+    }
+}